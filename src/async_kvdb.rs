@@ -1,10 +1,18 @@
-use crate::io;
+use core::ops::Range;
+
+use crate::{
+    error::is_unsupported,
+    io,
+    meta::{seeding_key, META_TABLE},
+    BatchOp, CompareAndSwapError, Unsupported, WriteBatch,
+};
 #[cfg(not(feature = "std"))]
 use alloc::{boxed::Box, string::String, vec::Vec};
 
 use async_trait::async_trait;
+use xxhash_rust::xxh3::xxh3_64;
 
-use crate::kvdb::KeyValueDB;
+use crate::kvdb::{clamp_range, KeyValueDB};
 
 #[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
 #[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
@@ -20,6 +28,11 @@ pub trait AsyncKeyValueDB: Send + Sync {
     async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error>;
     async fn table_names(&self) -> Result<Vec<String>, io::Error>;
 
+    /// Creates `table_name` if it does not exist yet. Backends whose tables
+    /// only exist through their keys have nothing to create.
+    async fn create_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Ok(())
+    }
     async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
         for (key, _) in self.iter(table_name).await? {
             self.remove(table_name, &key).await?;
@@ -39,6 +52,20 @@ pub trait AsyncKeyValueDB: Send + Sync {
         }
         Ok(result)
     }
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let mut result = Vec::new();
+        for (key, value) in self.iter(table_name).await? {
+            if start_key <= key.as_str() && key.as_str() < end_key {
+                result.push((key, value));
+            }
+        }
+        Ok(result)
+    }
     async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
         Ok(self.get(table_name, key).await?.is_some())
     }
@@ -56,12 +83,255 @@ pub trait AsyncKeyValueDB: Send + Sync {
         }
         Ok(values)
     }
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        Ok(self.keys(table_name).await?.len() as u64)
+    }
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        Ok(self.len(table_name).await? == 0)
+    }
     async fn clear(&self) -> Result<(), io::Error> {
         for table_name in self.table_names().await? {
             self.delete_table(&table_name).await?;
         }
         Ok(())
     }
+    /// Swaps the entries of `table_a` and `table_b`.
+    ///
+    /// The default implementation reads both tables and writes the swapped
+    /// entries with a single `apply_batch`, then removes a table whose
+    /// counterpart did not exist. Nothing is removed before the new entries
+    /// are written, but the swap is only as atomic as the backend's
+    /// `apply_batch`: where that applies one operation at a time, readers may
+    /// see a mix of both tables and a failure may leave the swap partially
+    /// applied.
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let table_names = self.table_names().await?;
+        let entries_a = self.iter(table_a).await?;
+        let entries_b = self.iter(table_b).await?;
+        self.apply_batch(WriteBatch::swap_tables(
+            table_a, &entries_a, table_b, &entries_b,
+        ))
+        .await?;
+        for (table_name, other) in [(table_a, table_b), (table_b, table_a)] {
+            if !table_names.iter().any(|name| name == other) {
+                self.delete_table(table_name).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates `table_name` filled with the entries returned by `init`, unless
+    /// the table already exists. Returns whether the table was created.
+    ///
+    /// The default implementation claims the seeding with `compare_and_swap`
+    /// on a marker key in [`META_TABLE`](crate::meta::META_TABLE), so only one
+    /// of concurrent callers runs `init`, and removes the marker once the
+    /// table is created and seeded. A crash in between leaves the marker
+    /// behind, and later calls then report the table as existing. On backends
+    /// without `compare_and_swap` concurrent callers may both run `init`, and
+    /// on those whose tables only exist through their keys a table seeded
+    /// with no entries still does not exist afterwards. Backends override it
+    /// to make the check and the seeding atomic where they can.
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        if self
+            .table_names()
+            .await?
+            .iter()
+            .any(|name| name == table_name)
+        {
+            return Ok(false);
+        }
+        let marker = seeding_key(table_name);
+        let claimed = match self
+            .compare_and_swap(META_TABLE, &marker, None, Some(&[]))
+            .await
+        {
+            Ok(()) => true,
+            Err(CompareAndSwapError::Mismatch { .. }) => return Ok(false),
+            Err(CompareAndSwapError::Io(e)) if is_unsupported(&e) => false,
+            Err(CompareAndSwapError::Io(e)) => return Err(e),
+        };
+        // Another caller may have seeded the table and released its claim
+        // since the first check.
+        let seeded = async {
+            if self
+                .table_names()
+                .await?
+                .iter()
+                .any(|name| name == table_name)
+            {
+                return Ok(false);
+            }
+            self.create_table(table_name).await?;
+            for (key, value) in init() {
+                self.insert(table_name, &key, &value).await?;
+            }
+            Ok(true)
+        }
+        .await;
+        if claimed {
+            self.remove(META_TABLE, &marker).await?;
+        }
+        seeded
+    }
+    /// Atomically replaces the value of `key` with `new` if its current value
+    /// is `expected`. `None` stands for a missing key on both sides.
+    async fn compare_and_swap(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        Err(Unsupported {
+            backend: core::any::type_name::<Self>(),
+            operation: "compare_and_swap",
+        }
+        .into())
+    }
+    /// Returns the 64-bit XXH3 hash of the value of `key`, so entries can be
+    /// compared across databases without transferring their values.
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        Ok(self
+            .get(table_name, key)
+            .await?
+            .map(|value| xxh3_64(&value)))
+    }
+    /// Returns the bytes of the value of `key` in `range`, which is clamped
+    /// to the length of the value. The file system and S3 backends only read
+    /// the requested bytes; by default the whole value is read.
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        Ok(self
+            .get(table_name, key)
+            .await?
+            .map(|value| value[clamp_range(range, value.len() as u64)].to_vec()))
+    }
+    /// Applies the operations of `batch` in order. In-memory and redb apply
+    /// the whole batch atomically, the file system backend isolates it from
+    /// concurrent calls on the same `FsDB` but may leave it partially applied
+    /// on a crash, and the remaining backends apply it one operation at a
+    /// time.
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        for op in batch {
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => {
+                    self.insert(&table_name, &key, &value).await?;
+                }
+                BatchOp::Remove { table_name, key } => {
+                    self.remove(&table_name, &key).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Persists the writes that the backend has not made durable yet.
+    /// Backends that write through do nothing.
+    async fn flush(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+    /// Reclaims the space left behind by removed entries, where the backend
+    /// supports it.
+    async fn compact(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Returns a handle bound to `table_name`. Named apart from
+    /// [`KeyValueDB::table`](crate::KeyValueDB::table) so that calls stay
+    /// unambiguous on databases implementing both traits.
+    fn table_async(&self, table_name: &str) -> AsyncTableHandle<'_, Self>
+    where
+        Self: Sized,
+    {
+        AsyncTableHandle::new(self, table_name)
+    }
+}
+
+/// A handle bound to a single table of an [`AsyncKeyValueDB`].
+///
+/// It only holds the table name and forwards to the database, so calls cost
+/// the same as the database's own methods.
+#[derive(Debug)]
+pub struct AsyncTableHandle<'a, D: ?Sized> {
+    db: &'a D,
+    name: String,
+}
+
+impl<'a, D: AsyncKeyValueDB + ?Sized> AsyncTableHandle<'a, D> {
+    pub fn new(db: &'a D, table_name: &str) -> Self {
+        Self {
+            db,
+            name: table_name.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn insert(&self, key: &str, value: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.insert(&self.name, key, value).await
+    }
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.get(&self.name, key).await
+    }
+    pub async fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.remove(&self.name, key).await
+    }
+    pub async fn iter(&self) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db.iter(&self.name).await
+    }
+    pub async fn iter_from_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db.iter_from_prefix(&self.name, prefix).await
+    }
+    pub async fn iter_from_range(
+        &self,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db
+            .iter_from_range(&self.name, start_key, end_key)
+            .await
+    }
+    pub async fn contains_key(&self, key: &str) -> Result<bool, io::Error> {
+        self.db.contains_key(&self.name, key).await
+    }
+    pub async fn keys(&self) -> Result<Vec<String>, io::Error> {
+        self.db.keys(&self.name).await
+    }
+    pub async fn values(&self) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.db.values(&self.name).await
+    }
+    pub async fn len(&self) -> Result<u64, io::Error> {
+        self.db.len(&self.name).await
+    }
+    pub async fn is_empty(&self) -> Result<bool, io::Error> {
+        self.db.is_empty(&self.name).await
+    }
+    pub async fn create(&self) -> Result<(), io::Error> {
+        self.db.create_table(&self.name).await
+    }
+    pub async fn delete(&self) -> Result<(), io::Error> {
+        self.db.delete_table(&self.name).await
+    }
 }
 
 #[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
@@ -95,6 +365,14 @@ impl AsyncKeyValueDB for dyn KeyValueDB {
     ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
         KeyValueDB::iter_from_prefix(self, table_name, prefix)
     }
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        KeyValueDB::iter_from_range(self, table_name, start_key, end_key)
+    }
     async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
         KeyValueDB::contains_key(self, table_name, key)
     }
@@ -104,12 +382,60 @@ impl AsyncKeyValueDB for dyn KeyValueDB {
     async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
         KeyValueDB::values(self, table_name)
     }
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        KeyValueDB::len(self, table_name)
+    }
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        KeyValueDB::is_empty(self, table_name)
+    }
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        KeyValueDB::create_table(self, table_name)
+    }
     async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
         KeyValueDB::delete_table(self, table_name)
     }
     async fn clear(&self) -> Result<(), io::Error> {
         KeyValueDB::clear(self)
     }
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        KeyValueDB::swap_tables(self, table_a, table_b)
+    }
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        KeyValueDB::ensure_table_with(self, table_name, init)
+    }
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        KeyValueDB::compare_and_swap(self, table_name, key, expected, new)
+    }
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        KeyValueDB::checksum(self, table_name, key)
+    }
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get_range(self, table_name, key, range)
+    }
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        KeyValueDB::apply_batch(self, batch)
+    }
+    async fn flush(&self) -> Result<(), io::Error> {
+        KeyValueDB::flush(self)
+    }
+    async fn compact(&self) -> Result<(), io::Error> {
+        KeyValueDB::compact(self)
+    }
 }
 
 #[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
@@ -143,6 +469,14 @@ impl<T: KeyValueDB> AsyncKeyValueDB for T {
     ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
         KeyValueDB::iter_from_prefix(self, table_name, prefix)
     }
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        KeyValueDB::iter_from_range(self, table_name, start_key, end_key)
+    }
     async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
         KeyValueDB::contains_key(self, table_name, key)
     }
@@ -152,12 +486,60 @@ impl<T: KeyValueDB> AsyncKeyValueDB for T {
     async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
         KeyValueDB::values(self, table_name)
     }
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        KeyValueDB::len(self, table_name)
+    }
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        KeyValueDB::is_empty(self, table_name)
+    }
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        KeyValueDB::create_table(self, table_name)
+    }
     async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
         KeyValueDB::delete_table(self, table_name)
     }
     async fn clear(&self) -> Result<(), io::Error> {
         KeyValueDB::clear(self)
     }
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        KeyValueDB::swap_tables(self, table_a, table_b)
+    }
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        KeyValueDB::ensure_table_with(self, table_name, init)
+    }
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        KeyValueDB::compare_and_swap(self, table_name, key, expected, new)
+    }
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        KeyValueDB::checksum(self, table_name, key)
+    }
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get_range(self, table_name, key, range)
+    }
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        KeyValueDB::apply_batch(self, batch)
+    }
+    async fn flush(&self) -> Result<(), io::Error> {
+        KeyValueDB::flush(self)
+    }
+    async fn compact(&self) -> Result<(), io::Error> {
+        KeyValueDB::compact(self)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,440 @@
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+    CreateTable,
+    DeleteTable,
+}
+
+/// Decides whether `actor` may perform `operation` on `table_name`.
+pub trait AccessPolicy<A>: Send + Sync {
+    fn allows(&self, actor: &A, table_name: &str, operation: Operation) -> bool;
+}
+
+impl<A, F: Fn(&A, &str, Operation) -> bool + Send + Sync> AccessPolicy<A> for F {
+    fn allows(&self, actor: &A, table_name: &str, operation: Operation) -> bool {
+        self(actor, table_name, operation)
+    }
+}
+
+/// Checks every operation of `actor` against a policy before handing it to
+/// the wrapped database, and fails with `PermissionDenied` if the policy
+/// refuses it. `table_names` only lists the tables the actor may read.
+///
+/// Writes return the previous value, so an actor allowed to write a table can
+/// read the keys it overwrites. `compare_and_swap` reports the current value
+/// on a mismatch and `swap_tables` moves each table's entries into the other,
+/// so both also require `Read` on the tables they touch.
+#[derive(Debug)]
+pub struct AuthorizedDB<T, A, P> {
+    inner: T,
+    access: Access<A, P>,
+}
+
+impl<T, A: Send + Sync, P: AccessPolicy<A>> AuthorizedDB<T, A, P> {
+    pub fn new(inner: T, actor: A, policy: P) -> Self {
+        Self {
+            inner,
+            access: Access { actor, policy },
+        }
+    }
+
+    pub fn actor(&self) -> &A {
+        &self.access.actor
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: KeyValueDB, A: Send + Sync, P: AccessPolicy<A>> KeyValueDB for AuthorizedDB<T, A, P> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.insert(table_name, key, value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.get(table_name, key)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.remove(table_name, key)
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.iter(table_name)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(self.access.readable(self.inner.table_names()?))
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.access.check(table_name, Operation::CreateTable)?;
+        self.inner.create_table(table_name)
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.access.check(table_name, Operation::DeleteTable)?;
+        self.inner.delete_table(table_name)
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.iter_from_prefix(table_name, prefix)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.iter_from_range(table_name, start_key, end_key)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.contains_key(table_name, key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.keys(table_name)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.values(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.is_empty(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        for table_name in self.inner.table_names()? {
+            self.access.check(&table_name, Operation::DeleteTable)?;
+        }
+        self.inner.clear()
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        for table_name in [table_a, table_b] {
+            self.access.check(table_name, Operation::Read)?;
+            self.access.check(table_name, Operation::Write)?;
+        }
+        self.inner.swap_tables(table_a, table_b)
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::CreateTable)?;
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.ensure_table_with(table_name, init)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.access.check(table_name, Operation::Read)?;
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.compare_and_swap(table_name, key, expected, new)
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.checksum(table_name, key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.get_range(table_name, key, range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.access.check_batch(&batch)?;
+        self.inner.apply_batch(batch)
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`AuthorizedDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncAuthorizedDB<T, A, P> {
+    inner: T,
+    access: Access<A, P>,
+}
+
+#[cfg(feature = "async")]
+impl<T, A: Send + Sync, P: AccessPolicy<A>> AsyncAuthorizedDB<T, A, P> {
+    pub fn new(inner: T, actor: A, policy: P) -> Self {
+        Self {
+            inner,
+            access: Access { actor, policy },
+        }
+    }
+
+    pub fn actor(&self) -> &A {
+        &self.access.actor
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB, A: Send + Sync, P: AccessPolicy<A>> AsyncKeyValueDB
+    for AsyncAuthorizedDB<T, A, P>
+{
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.insert(table_name, key, value).await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.get(table_name, key).await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.remove(table_name, key).await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(self.access.readable(self.inner.table_names().await?))
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.access.check(table_name, Operation::CreateTable)?;
+        self.inner.create_table(table_name).await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.access.check(table_name, Operation::DeleteTable)?;
+        self.inner.delete_table(table_name).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.iter_from_prefix(table_name, prefix).await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.contains_key(table_name, key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.keys(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.values(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        for table_name in self.inner.table_names().await? {
+            self.access.check(&table_name, Operation::DeleteTable)?;
+        }
+        self.inner.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        for table_name in [table_a, table_b] {
+            self.access.check(table_name, Operation::Read)?;
+            self.access.check(table_name, Operation::Write)?;
+        }
+        self.inner.swap_tables(table_a, table_b).await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.access.check(table_name, Operation::CreateTable)?;
+        self.access.check(table_name, Operation::Write)?;
+        self.inner.ensure_table_with(table_name, init).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.access.check(table_name, Operation::Read)?;
+        self.access.check(table_name, Operation::Write)?;
+        self.inner
+            .compare_and_swap(table_name, key, expected, new)
+            .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.checksum(table_name, key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.access.check(table_name, Operation::Read)?;
+        self.inner.get_range(table_name, key, range).await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.access.check_batch(&batch)?;
+        self.inner.apply_batch(batch).await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
+
+#[derive(Debug)]
+struct Access<A, P> {
+    actor: A,
+    policy: P,
+}
+
+impl<A, P: AccessPolicy<A>> Access<A, P> {
+    fn check(&self, table_name: &str, operation: Operation) -> Result<(), io::Error> {
+        if self.policy.allows(&self.actor, table_name, operation) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "operation not permitted on this table",
+            ))
+        }
+    }
+
+    fn check_batch(&self, batch: &WriteBatch) -> Result<(), io::Error> {
+        for op in batch.ops() {
+            self.check(op.table_name(), Operation::Write)?;
+        }
+        Ok(())
+    }
+
+    fn readable(&self, table_names: Vec<String>) -> Vec<String> {
+        table_names
+            .into_iter()
+            .filter(|table_name| self.policy.allows(&self.actor, table_name, Operation::Read))
+            .collect()
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Limits how many requests run at the same time across every `AwsS3DB`
+/// sharing it, e.g. so that a large scan leaves room for the other requests
+/// made through the same client. Clones share the limit.
+///
+/// Waiting requests start in the order they started waiting: a released
+/// permit is handed to the first of them.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    available: usize,
+    /// The pending `Acquire`s, by id, in the order they started waiting.
+    waiters: VecDeque<(u64, Waker)>,
+    next_id: u64,
+}
+
+impl State {
+    /// Hands the permit to the first waiter, or makes it available.
+    fn release(&mut self) -> Option<Waker> {
+        match self.waiters.pop_front() {
+            Some((_, waker)) => Some(waker),
+            None => {
+                self.available += 1;
+                None
+            }
+        }
+    }
+
+    fn position(&self, id: u64) -> Option<usize> {
+        self.waiters.iter().position(|(waiter, _)| *waiter == id)
+    }
+}
+
+impl ConcurrencyLimit {
+    /// Allows `max_concurrent` requests at a time, at least one.
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                available: max_concurrent.max(1),
+                waiters: VecDeque::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// The number of requests that can start without waiting.
+    pub fn available(&self) -> usize {
+        self.state.lock().unwrap().available
+    }
+
+    /// Waits until a request can start. It may run until the returned permit
+    /// is dropped.
+    pub fn acquire(&self) -> Acquire<'_> {
+        Acquire {
+            limit: self,
+            waiting: None,
+        }
+    }
+
+    fn release(&self) {
+        let waker = self.state.lock().unwrap().release();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// The future returned by [`ConcurrencyLimit::acquire`].
+#[derive(Debug)]
+pub struct Acquire<'a> {
+    limit: &'a ConcurrencyLimit,
+    /// The id under which this future waits in the queue. Once it has left
+    /// the queue, a released permit was handed to it.
+    waiting: Option<u64>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.limit.state.lock().unwrap();
+        match this.waiting {
+            Some(id) => match state.position(id) {
+                Some(position) => {
+                    let (_, waker) = &mut state.waiters[position];
+                    if !waker.will_wake(cx.waker()) {
+                        *waker = cx.waker().clone();
+                    }
+                    return Poll::Pending;
+                }
+                None => this.waiting = None,
+            },
+            None if state.available > 0 && state.waiters.is_empty() => state.available -= 1,
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.waiters.push_back((id, cx.waker().clone()));
+                this.waiting = Some(id);
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(Permit { limit: this.limit })
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiting else {
+            return;
+        };
+        let mut state = self.limit.state.lock().unwrap();
+        match state.position(id) {
+            Some(position) => {
+                state.waiters.remove(position);
+            }
+            // The permit handed to this future is passed on.
+            None => {
+                drop(state);
+                self.limit.release();
+            }
+        }
+    }
+}
+
+/// Allows one request to run until it is dropped.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    limit: &'a ConcurrencyLimit,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limit.release();
+    }
+}
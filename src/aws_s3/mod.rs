@@ -1,20 +1,51 @@
-use std::{collections::HashSet, io};
+use std::{collections::HashSet, future::Future, io, ops::Range};
 
 use async_trait::async_trait;
 use aws_config::{BehaviorVersion, Region};
 pub use aws_credential_types::Credentials;
-use aws_sdk_s3::{operation::get_object::GetObjectError, primitives::ByteStream, Client};
-
-use crate::AsyncKeyValueDB;
+use aws_sdk_s3::{
+    error::SdkError,
+    operation::{
+        get_object::GetObjectError, head_object::HeadObjectError,
+        list_object_versions::ListObjectVersionsOutput,
+    },
+    primitives::{ByteStream, DateTime},
+    Client,
+};
+use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{AsyncKeyValueDB, CompareAndSwapError};
 
 mod client;
+mod limit;
 
 use self::client::{HttpClientImpl, SleepImpl, TimeSourceImpl};
+pub use self::limit::{Acquire, ConcurrencyLimit, Permit};
+
+const DEFAULT_MAX_CONCURRENT_GETS: usize = 16;
 
-#[derive(Debug)]
+/// A database on an S3 bucket. Clones share the client, so a clone with
+/// other concurrency settings can be made for a single call.
+///
+/// Batches are applied one object at a time, so `apply_batch` and the
+/// `swap_tables` built on it are not atomic: readers may see them partially
+/// applied, and a failed request leaves them so.
+#[derive(Debug, Clone)]
 pub struct AwsS3DB {
     client: Client,
     bucket_name: String,
+    max_concurrent_gets: usize,
+    concurrency_limit: Option<ConcurrencyLimit>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectVersion {
+    pub version_id: String,
+    pub is_latest: bool,
+    /// Whether this version records a removal rather than a value.
+    pub is_delete_marker: bool,
+    pub last_modified: Option<DateTime>,
 }
 
 impl AwsS3DB {
@@ -35,39 +66,483 @@ impl AwsS3DB {
             .await;
 
         let client = Client::new(&config);
+        ensure_bucket(&client, bucket_name).await?;
+
+        Ok(Self {
+            client,
+            bucket_name: bucket_name.to_string(),
+            max_concurrent_gets: DEFAULT_MAX_CONCURRENT_GETS,
+            concurrency_limit: None,
+        })
+    }
+
+    /// Copies every object into the bucket `bucket_name`, creating it if
+    /// needed, and returns a database on that bucket. Objects are copied
+    /// server side, `max_concurrent_gets` at a time, so the values never
+    /// leave S3. Writes made while the fork runs may or may not be copied.
+    pub async fn fork(&self, bucket_name: &str) -> io::Result<Self> {
+        ensure_bucket(&self.client, bucket_name).await?;
+
+        futures::stream::iter(self.list_object_keys("").await?)
+            .map(|object_key| async move {
+                let _permit = self.acquire().await;
+                self.client
+                    .copy_object()
+                    .copy_source(format!(
+                        "{}/{}",
+                        self.bucket_name,
+                        encode_copy_source(&object_key)
+                    ))
+                    .bucket(bucket_name)
+                    .key(&object_key)
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+            })
+            .buffer_unordered(self.max_concurrent_gets)
+            .try_collect::<()>()
+            .await?;
+
+        Ok(Self {
+            client: self.client.clone(),
+            bucket_name: bucket_name.to_string(),
+            max_concurrent_gets: self.max_concurrent_gets,
+            concurrency_limit: self.concurrency_limit.clone(),
+        })
+    }
+
+    /// Sets how many objects `iter` and `iter_from_prefix` fetch at the same
+    /// time. Defaults to 16.
+    pub fn with_max_concurrent_gets(mut self, max_concurrent_gets: usize) -> Self {
+        self.max_concurrent_gets = max_concurrent_gets.max(1);
+        self
+    }
+
+    /// Makes the requests that `iter`, `iter_from_prefix` and `fork` issue in
+    /// parallel wait for `limit`, which can be shared with other databases
+    /// to bound their requests as a whole. The per-database bound of
+    /// `with_max_concurrent_gets` still applies.
+    pub fn with_concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.concurrency_limit = Some(limit);
+        self
+    }
+
+    /// Returns the version id of the current value of `key`, or `None` if the
+    /// key does not exist. The bucket must have versioning enabled for S3 to
+    /// assign version ids.
+    pub async fn get_version_id(&self, table_name: &str, key: &str) -> io::Result<Option<String>> {
+        let table_key = format!("{}/{}", table_name, key);
 
-        let buckets = client
-            .list_buckets()
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket_name)
+            .key(&table_key)
             .send()
             .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?
-            .buckets
-            .unwrap_or_default();
+        {
+            Ok(output) => Ok(output.version_id),
+            Err(e) => {
+                if let Some(HeadObjectError::NotFound(_)) = e.as_service_error() {
+                    Ok(None)
+                } else {
+                    Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+                }
+            }
+        }
+    }
 
-        if !buckets
-            .iter()
-            .any(|bucket| bucket.name().unwrap_or_default() == bucket_name)
+    pub async fn get_at_version(
+        &self,
+        table_name: &str,
+        key: &str,
+        version_id: &str,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let table_key = format!("{}/{}", table_name, key);
+
+        Ok(self
+            .get_object(&table_key, Some(version_id))
+            .await?
+            .map(|(data, _)| data))
+    }
+
+    /// Returns the value of `key` along with its ETag, to be passed to
+    /// `insert_if_match` or `remove_if_match`.
+    pub async fn get_with_e_tag(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> io::Result<Option<(Vec<u8>, String)>> {
+        let table_key = format!("{}/{}", table_name, key);
+
+        Ok(self
+            .get_object(&table_key, None)
+            .await?
+            .map(|(data, e_tag)| (data, e_tag.unwrap_or_default())))
+    }
+
+    /// Writes `value` only if the current ETag of `key` is `e_tag`, or, when
+    /// `e_tag` is `None`, only if `key` does not exist. Returns `false`
+    /// without writing if the condition does not hold.
+    pub async fn insert_if_match(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+        e_tag: Option<&str>,
+    ) -> io::Result<bool> {
+        let table_key = format!("{}/{}", table_name, key);
+
+        let put_object = self
+            .client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&table_key)
+            .body(ByteStream::from(value.to_vec()));
+        let put_object = match e_tag {
+            Some(e_tag) => put_object.if_match(e_tag),
+            None => put_object.if_none_match("*"),
+        };
+
+        match put_object.send().await {
+            Ok(_) => Ok(true),
+            Err(e) if is_precondition_failure(&e) => Ok(false),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e))),
+        }
+    }
+
+    /// Removes `key` only if its current ETag is `e_tag`. Returns `false`
+    /// without removing it if the condition does not hold.
+    pub async fn remove_if_match(
+        &self,
+        table_name: &str,
+        key: &str,
+        e_tag: &str,
+    ) -> io::Result<bool> {
+        let table_key = format!("{}/{}", table_name, key);
+
+        match self
+            .client
+            .delete_object()
+            .bucket(&self.bucket_name)
+            .key(&table_key)
+            .if_match(e_tag)
+            .send()
+            .await
         {
-            client
-                .create_bucket()
-                .bucket(bucket_name)
+            Ok(_) => Ok(true),
+            Err(e) if is_precondition_failure(&e) => Ok(false),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e))),
+        }
+    }
+
+    /// Lists every version of `key`, delete markers included, newest first.
+    pub async fn list_versions(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> io::Result<Vec<ObjectVersion>> {
+        let table_key = format!("{}/{}", table_name, key);
+
+        let mut versions = Vec::new();
+
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+
+        loop {
+            let output = self
+                .client
+                .list_object_versions()
+                .bucket(&self.bucket_name)
+                .prefix(&table_key)
+                .set_key_marker(key_marker)
+                .set_version_id_marker(version_id_marker)
                 .send()
                 .await
-                .map_err(|e| {
-                    io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Failed to create bucket: {:?}", e),
-                    )
-                })?;
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+            let is_truncated = output.is_truncated.unwrap_or_default();
+            key_marker = output.next_key_marker.clone();
+            version_id_marker = output.next_version_id_marker.clone();
+
+            collect_versions(&table_key, output, &mut versions);
+
+            if !is_truncated {
+                break;
+            }
         }
 
-        Ok(Self {
-            client,
-            bucket_name: bucket_name.to_string(),
+        versions.sort_by_key(|version| std::cmp::Reverse(version.last_modified));
+
+        Ok(versions)
+    }
+
+    /// Lists the keys of `table_name` starting with `key_prefix` without
+    /// fetching their values. The prefix is applied by S3.
+    async fn list_keys(&self, table_name: &str, key_prefix: &str) -> io::Result<Vec<String>> {
+        let object_keys = self
+            .list_object_keys(&format!("{}/{}", table_name, key_prefix))
+            .await?;
+
+        Ok(table_keys(table_name, object_keys))
+    }
+
+    async fn list_object_keys(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut object_keys = Vec::new();
+
+        let mut continuation_token = None;
+
+        loop {
+            let list_objects = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket_name)
+                .prefix(prefix);
+
+            let list_objects = if let Some(token) = continuation_token {
+                list_objects.continuation_token(token)
+            } else {
+                list_objects
+            };
+
+            let output = list_objects
+                .send()
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+            for object in output.contents.unwrap_or_default() {
+                object_keys.push(object.key.unwrap_or_default());
+            }
+
+            if let Some(token) = output.next_continuation_token {
+                continuation_token = Some(token);
+            } else {
+                break;
+            }
+        }
+
+        Ok(object_keys)
+    }
+
+    /// Fetches the values of `keys`, at most `max_concurrent_gets` at a time.
+    /// Keys removed in the meantime are skipped.
+    async fn get_all(
+        &self,
+        table_name: &str,
+        keys: Vec<String>,
+    ) -> io::Result<Vec<(String, Vec<u8>)>> {
+        fetch_all(keys, self.max_concurrent_gets, |key| async move {
+            let _permit = self.acquire().await;
+            let value = self.get(table_name, &key).await?;
+            Ok(value.map(|value| (key, value)))
         })
+        .await
+    }
+
+    async fn acquire(&self) -> Option<Permit<'_>> {
+        match &self.concurrency_limit {
+            Some(limit) => Some(limit.acquire().await),
+            None => None,
+        }
+    }
+
+    async fn get_object(
+        &self,
+        table_key: &str,
+        version_id: Option<&str>,
+    ) -> io::Result<Option<(Vec<u8>, Option<String>)>> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(table_key)
+            .set_version_id(version_id.map(str::to_string))
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                if let Some(GetObjectError::NoSuchKey(_)) = e.as_service_error() {
+                    return Ok(None);
+                } else {
+                    return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                }
+            }
+        };
+
+        let e_tag = output.e_tag.clone();
+        let data = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        Ok(Some((data.to_vec(), e_tag)))
     }
 }
 
+async fn ensure_bucket(client: &Client, bucket_name: &str) -> io::Result<()> {
+    let buckets = client
+        .list_buckets()
+        .send()
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?
+        .buckets
+        .unwrap_or_default();
+
+    if !buckets
+        .iter()
+        .any(|bucket| bucket.name().unwrap_or_default() == bucket_name)
+    {
+        client
+            .create_bucket()
+            .bucket(bucket_name)
+            .send()
+            .await
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Failed to create bucket: {:?}", e),
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Percent-encodes an object key for the `x-amz-copy-source` header.
+fn encode_copy_source(object_key: &str) -> String {
+    let mut encoded = String::with_capacity(object_key.len());
+    for byte in object_key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn is_unsatisfiable_range<E>(e: &SdkError<E, HttpResponse>) -> bool {
+    e.raw_response()
+        .map(|response| response.status().as_u16() == 416)
+        .unwrap_or(false)
+}
+
+fn is_precondition_failure<E>(e: &SdkError<E, HttpResponse>) -> bool {
+    e.raw_response()
+        .map(|response| is_precondition_status(response.status().as_u16()))
+        .unwrap_or(false)
+}
+
+/// Strips `table_name` from the object keys listed under it.
+fn table_keys(table_name: &str, object_keys: Vec<String>) -> Vec<String> {
+    let table_prefix = format!("{}/", table_name);
+    object_keys
+        .into_iter()
+        .filter_map(|object_key| object_key.strip_prefix(&table_prefix).map(str::to_string))
+        .collect()
+}
+
+/// Runs `fetch` on every key, at most `max_concurrent` at a time, and
+/// collects the results in key order, skipping `None`s.
+async fn fetch_all<T, F, Fut>(
+    keys: Vec<String>,
+    max_concurrent: usize,
+    fetch: F,
+) -> io::Result<Vec<T>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = io::Result<Option<T>>>,
+{
+    futures::stream::iter(keys)
+        .map(fetch)
+        .buffered(max_concurrent)
+        .try_filter_map(|entry| async move { Ok(entry) })
+        .try_collect()
+        .await
+}
+
+/// Appends the versions and delete markers of exactly `table_key` in one
+/// page of `ListObjectVersions`. The listing is by prefix, so it also holds
+/// longer keys, which are skipped.
+fn collect_versions(
+    table_key: &str,
+    output: ListObjectVersionsOutput,
+    versions: &mut Vec<ObjectVersion>,
+) {
+    for version in output.versions.unwrap_or_default() {
+        if version.key.as_deref() != Some(table_key) {
+            continue;
+        }
+        versions.push(ObjectVersion {
+            version_id: version.version_id.unwrap_or_default(),
+            is_latest: version.is_latest.unwrap_or_default(),
+            is_delete_marker: false,
+            last_modified: version.last_modified,
+        });
+    }
+    for marker in output.delete_markers.unwrap_or_default() {
+        if marker.key.as_deref() != Some(table_key) {
+            continue;
+        }
+        versions.push(ObjectVersion {
+            version_id: marker.version_id.unwrap_or_default(),
+            is_latest: marker.is_latest.unwrap_or_default(),
+            is_delete_marker: true,
+            last_modified: marker.last_modified,
+        });
+    }
+}
+
+/// S3 answers 412 when the condition of a conditional write does not hold,
+/// and 409 when a concurrent conditional write to the object is in progress.
+fn is_precondition_status(status: u16) -> bool {
+    matches!(status, 409 | 412)
+}
+
+/// The request `compare_and_swap` makes once the current value matched.
+#[derive(Debug, PartialEq, Eq)]
+enum ConditionalWrite<'a> {
+    /// Writes `value` if the object still has `e_tag`, or, without one, if
+    /// it still does not exist.
+    Put {
+        value: &'a [u8],
+        e_tag: Option<&'a str>,
+    },
+    /// Removes the object if it still has `e_tag`.
+    Delete { e_tag: &'a str },
+    /// The object does not exist and must not.
+    Nothing,
+}
+
+/// Decides how to replace an object that `exists` with ETag `e_tag` by
+/// `new`. An existing object without an ETag cannot be written
+/// conditionally, which is an error rather than a reason to write blindly.
+fn conditional_write<'a>(
+    exists: bool,
+    e_tag: Option<&'a str>,
+    new: Option<&'a [u8]>,
+) -> io::Result<ConditionalWrite<'a>> {
+    if exists && e_tag.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "S3 returned no ETag for an existing object",
+        ));
+    }
+    Ok(match (new, e_tag) {
+        (Some(value), e_tag) => ConditionalWrite::Put { value, e_tag },
+        (None, Some(e_tag)) => ConditionalWrite::Delete { e_tag },
+        (None, None) => ConditionalWrite::Nothing,
+    })
+}
+
+impl crate::meta::Backend for AwsS3DB {
+    const NAME: &'static str = "aws-s3";
+}
+
 #[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
 #[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
 impl AsyncKeyValueDB for AwsS3DB {
@@ -96,11 +571,33 @@ impl AsyncKeyValueDB for AwsS3DB {
     async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
         let table_key = format!("{}/{}", table_name, key);
 
+        Ok(self
+            .get_object(&table_key, None)
+            .await?
+            .map(|(data, _)| data))
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let table_key = format!("{}/{}", table_name, key);
+        // An empty range cannot be requested, so the first byte is requested
+        // instead to learn whether the key exists.
+        let (first, last) = if range.is_empty() {
+            (0, 0)
+        } else {
+            (range.start, range.end - 1)
+        };
+
         let output = match self
             .client
             .get_object()
             .bucket(&self.bucket_name)
             .key(&table_key)
+            .range(format!("bytes={}-{}", first, last))
             .send()
             .await
         {
@@ -108,11 +605,17 @@ impl AsyncKeyValueDB for AwsS3DB {
             Err(e) => {
                 if let Some(GetObjectError::NoSuchKey(_)) = e.as_service_error() {
                     return Ok(None);
+                } else if is_unsatisfiable_range(&e) {
+                    // The range starts past the end of the value.
+                    return Ok(Some(Vec::new()));
                 } else {
                     return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
                 }
             }
         };
+        if range.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
 
         let data = output
             .body
@@ -140,52 +643,63 @@ impl AsyncKeyValueDB for AwsS3DB {
     }
 
     async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
-        let prefix = format!("{}/", table_name);
-
-        let mut keys_and_values = Vec::new();
-
-        let mut continuation_token = None;
+        let keys = self.list_keys(table_name, "").await?;
+        self.get_all(table_name, keys).await
+    }
 
-        loop {
-            let list_objects = self
-                .client
-                .list_objects_v2()
-                .bucket(&self.bucket_name)
-                .prefix(&prefix);
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let keys = self.list_keys(table_name, prefix).await?;
+        self.get_all(table_name, keys).await
+    }
 
-            let list_objects = if let Some(token) = continuation_token {
-                list_objects.continuation_token(token)
-            } else {
-                list_objects
-            };
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.list_keys(table_name, "").await
+    }
 
-            let output = list_objects
-                .send()
-                .await
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        Ok(self.list_keys(table_name, "").await?.len() as u64)
+    }
 
-            for object in output.contents.unwrap_or_default() {
-                let key = object.key.unwrap_or_default();
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let table_key = format!("{}/{}", table_name, key);
 
-                let key = if let Some(key) = key.strip_prefix(&prefix) {
-                    key
-                } else {
-                    continue;
-                };
+        let (current, e_tag) = match self.get_object(&table_key, None).await? {
+            Some((data, e_tag)) => (Some(data), e_tag),
+            None => (None, None),
+        };
+        if current.as_deref() != expected {
+            return Err(CompareAndSwapError::Mismatch { current });
+        }
 
-                if let Some(data) = self.get(table_name, key).await? {
-                    keys_and_values.push((key.to_string(), data));
-                }
+        let applied = match conditional_write(current.is_some(), e_tag.as_deref(), new)? {
+            ConditionalWrite::Put { value, e_tag } => {
+                self.insert_if_match(table_name, key, value, e_tag).await?
             }
-
-            if let Some(token) = output.next_continuation_token {
-                continuation_token = Some(token);
-            } else {
-                break;
+            ConditionalWrite::Delete { e_tag } => {
+                self.remove_if_match(table_name, key, e_tag).await?
             }
+            ConditionalWrite::Nothing => true,
+        };
+
+        if !applied {
+            let current = self
+                .get_object(&table_key, None)
+                .await?
+                .map(|(data, _)| data);
+            return Err(CompareAndSwapError::Mismatch { current });
         }
 
-        Ok(keys_and_values)
+        Ok(())
     }
 
     async fn table_names(&self) -> Result<Vec<String>, io::Error> {
@@ -228,3 +742,156 @@ impl AsyncKeyValueDB for AwsS3DB {
         Ok(table_names.into_iter().collect())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conditional_write_matches_the_current_object() {
+        assert_eq!(
+            conditional_write(false, None, Some(b"new")).unwrap(),
+            ConditionalWrite::Put {
+                value: b"new",
+                e_tag: None
+            }
+        );
+        assert_eq!(
+            conditional_write(true, Some("\"tag\""), Some(b"new")).unwrap(),
+            ConditionalWrite::Put {
+                value: b"new",
+                e_tag: Some("\"tag\"")
+            }
+        );
+        assert_eq!(
+            conditional_write(true, Some("\"tag\""), None).unwrap(),
+            ConditionalWrite::Delete { e_tag: "\"tag\"" }
+        );
+        assert_eq!(
+            conditional_write(false, None, None).unwrap(),
+            ConditionalWrite::Nothing
+        );
+    }
+
+    #[test]
+    fn conditional_write_needs_the_e_tag_of_an_existing_object() {
+        assert!(conditional_write(true, None, Some(b"new")).is_err());
+        assert!(conditional_write(true, None, None).is_err());
+    }
+
+    #[test]
+    fn precondition_statuses() {
+        assert!(is_precondition_status(412));
+        assert!(is_precondition_status(409));
+        assert!(!is_precondition_status(404));
+        assert!(!is_precondition_status(200));
+    }
+
+    #[test]
+    fn collect_versions_keeps_only_the_exact_key() {
+        let version = |key: &str, id: &str, is_latest: bool, secs: i64| {
+            aws_sdk_s3::types::ObjectVersion::builder()
+                .key(key)
+                .version_id(id)
+                .is_latest(is_latest)
+                .last_modified(DateTime::from_secs(secs))
+                .build()
+        };
+        let output = ListObjectVersionsOutput::builder()
+            .versions(version("table/key", "v1", false, 1))
+            .versions(version("table/key2", "other", true, 2))
+            .versions(version("table/key", "v2", false, 2))
+            .delete_markers(
+                aws_sdk_s3::types::DeleteMarkerEntry::builder()
+                    .key("table/key")
+                    .version_id("v3")
+                    .is_latest(true)
+                    .last_modified(DateTime::from_secs(3))
+                    .build(),
+            )
+            .build();
+
+        let mut versions = Vec::new();
+        collect_versions("table/key", output, &mut versions);
+
+        assert_eq!(
+            versions,
+            vec![
+                ObjectVersion {
+                    version_id: "v1".to_string(),
+                    is_latest: false,
+                    is_delete_marker: false,
+                    last_modified: Some(DateTime::from_secs(1)),
+                },
+                ObjectVersion {
+                    version_id: "v2".to_string(),
+                    is_latest: false,
+                    is_delete_marker: false,
+                    last_modified: Some(DateTime::from_secs(2)),
+                },
+                ObjectVersion {
+                    version_id: "v3".to_string(),
+                    is_latest: true,
+                    is_delete_marker: true,
+                    last_modified: Some(DateTime::from_secs(3)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn table_keys_strip_the_table() {
+        let object_keys = vec!["table/a".to_string(), "table/b/c".to_string()];
+        assert_eq!(table_keys("table", object_keys), vec!["a", "b/c"]);
+    }
+
+    #[test]
+    fn fetch_all_bounds_concurrency_and_keeps_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let keys = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+
+        let values = futures::executor::block_on(fetch_all(keys, 3, |key| {
+            let running = &running;
+            let max_running = &max_running;
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                // Let the other fetches start before this one finishes.
+                let mut yielded = false;
+                futures::future::poll_fn(|cx| {
+                    if yielded {
+                        std::task::Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        std::task::Poll::Pending
+                    }
+                })
+                .await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                let i = key.parse::<u32>().unwrap();
+                Ok((i % 2 == 0).then_some(i))
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(values, vec![0, 2, 4, 6, 8]);
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn fetch_all_stops_at_the_first_error() {
+        let keys = vec!["ok".to_string(), "err".to_string()];
+        let result = futures::executor::block_on(fetch_all(keys, 2, |key| async move {
+            if key == "err" {
+                Err(io::Error::new(io::ErrorKind::Other, "failed"))
+            } else {
+                Ok(Some(key))
+            }
+        }));
+        assert!(result.is_err());
+    }
+}
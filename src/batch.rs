@@ -0,0 +1,118 @@
+use alloc::collections::BTreeSet;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Insert {
+        table_name: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    Remove {
+        table_name: String,
+        key: String,
+    },
+}
+
+impl BatchOp {
+    pub fn table_name(&self) -> &str {
+        match self {
+            BatchOp::Insert { table_name, .. } | BatchOp::Remove { table_name, .. } => table_name,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            BatchOp::Insert { key, .. } | BatchOp::Remove { key, .. } => key,
+        }
+    }
+}
+
+/// Inserts and removes across any number of tables, applied in order by
+/// `apply_batch`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, table_name: &str, key: &str, value: &[u8]) -> &mut Self {
+        self.ops.push(BatchOp::Insert {
+            table_name: table_name.into(),
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn remove(&mut self, table_name: &str, key: &str) -> &mut Self {
+        self.ops.push(BatchOp::Remove {
+            table_name: table_name.into(),
+            key: key.into(),
+        });
+        self
+    }
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The batch giving `table_a` the entries of `table_b` and the other way
+    /// round, given the current entries of both.
+    pub(crate) fn swap_tables(
+        table_a: &str,
+        entries_a: &[(String, Vec<u8>)],
+        table_b: &str,
+        entries_b: &[(String, Vec<u8>)],
+    ) -> Self {
+        let mut batch = Self::new();
+        batch.replace_table(table_a, entries_a, entries_b);
+        batch.replace_table(table_b, entries_b, entries_a);
+        batch
+    }
+
+    fn replace_table(
+        &mut self,
+        table_name: &str,
+        current: &[(String, Vec<u8>)],
+        new: &[(String, Vec<u8>)],
+    ) {
+        let new_keys = new.iter().map(|(key, _)| key).collect::<BTreeSet<_>>();
+        for (key, value) in new {
+            self.insert(table_name, key, value);
+        }
+        for (key, _) in current {
+            if !new_keys.contains(key) {
+                self.remove(table_name, key);
+            }
+        }
+    }
+}
+
+impl From<Vec<BatchOp>> for WriteBatch {
+    fn from(ops: Vec<BatchOp>) -> Self {
+        Self { ops }
+    }
+}
+
+impl IntoIterator for WriteBatch {
+    type Item = BatchOp;
+    type IntoIter = alloc::vec::IntoIter<BatchOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
@@ -0,0 +1,297 @@
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encodes a binary key as the lowercase hex string it is stored under.
+/// Encoded keys sort like the bytes they encode, and the encoding of a
+/// prefix is a prefix of the encoding, so ranges and prefixes are preserved.
+pub fn encode_key(key: &[u8]) -> String {
+    let mut encoded = String::with_capacity(key.len() * 2);
+    for byte in key {
+        encoded.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        encoded.push(HEX_DIGITS[(byte & 0xF) as usize] as char);
+    }
+    encoded
+}
+
+pub fn decode_key(key: &str) -> Result<Vec<u8>, io::Error> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "key is not a binary key");
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        _ => Err(invalid()),
+    };
+    let pairs = key.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(invalid());
+    }
+    pairs
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+#[allow(clippy::type_complexity)]
+fn decode_entries(entries: Vec<(String, Vec<u8>)>) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+    entries
+        .into_iter()
+        .map(|(key, value)| Ok((decode_key(&key)?, value)))
+        .collect()
+}
+
+/// [`KeyValueDB`] with binary keys, such as hashes. It is implemented for
+/// every database by storing the keys hex-encoded with [`encode_key`], so a
+/// table should hold either binary or string keys: reading string keys
+/// through this trait fails with `InvalidData`. Its methods are those of
+/// `KeyValueDB` with a `_bytes` suffix, so both traits can be in scope.
+pub trait KeyValueDBBytes {
+    fn insert_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error>;
+    fn get_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error>;
+    fn remove_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    fn iter_bytes(&self, table_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    fn iter_from_prefix_bytes(
+        &self,
+        table_name: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    fn iter_from_range_bytes(
+        &self,
+        table_name: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    fn contains_key_bytes(&self, table_name: &str, key: &[u8]) -> Result<bool, io::Error>;
+    fn keys_bytes(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error>;
+    fn compare_and_swap_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError>;
+    fn get_range_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error>;
+}
+
+impl<T: KeyValueDB + ?Sized> KeyValueDBBytes for T {
+    fn insert_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::insert(self, table_name, &encode_key(key), value)
+    }
+    fn get_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get(self, table_name, &encode_key(key))
+    }
+    fn remove_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::remove(self, table_name, &encode_key(key))
+    }
+    fn iter_bytes(&self, table_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(KeyValueDB::iter(self, table_name)?)
+    }
+    fn iter_from_prefix_bytes(
+        &self,
+        table_name: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(KeyValueDB::iter_from_prefix(
+            self,
+            table_name,
+            &encode_key(prefix),
+        )?)
+    }
+    fn iter_from_range_bytes(
+        &self,
+        table_name: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(KeyValueDB::iter_from_range(
+            self,
+            table_name,
+            &encode_key(start_key),
+            &encode_key(end_key),
+        )?)
+    }
+    fn contains_key_bytes(&self, table_name: &str, key: &[u8]) -> Result<bool, io::Error> {
+        KeyValueDB::contains_key(self, table_name, &encode_key(key))
+    }
+    fn keys_bytes(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        KeyValueDB::keys(self, table_name)?
+            .iter()
+            .map(|key| decode_key(key))
+            .collect()
+    }
+    fn compare_and_swap_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        KeyValueDB::compare_and_swap(self, table_name, &encode_key(key), expected, new)
+    }
+    fn get_range_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get_range(self, table_name, &encode_key(key), range)
+    }
+}
+
+/// Async counterpart of [`KeyValueDBBytes`], implemented for every
+/// [`AsyncKeyValueDB`].
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+pub trait AsyncKeyValueDBBytes {
+    async fn insert_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error>;
+    async fn get_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error>;
+    async fn remove_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    async fn iter_bytes(&self, table_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    async fn iter_from_prefix_bytes(
+        &self,
+        table_name: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    #[allow(clippy::type_complexity)]
+    async fn iter_from_range_bytes(
+        &self,
+        table_name: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error>;
+    async fn contains_key_bytes(&self, table_name: &str, key: &[u8]) -> Result<bool, io::Error>;
+    async fn keys_bytes(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error>;
+    async fn compare_and_swap_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError>;
+    async fn get_range_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error>;
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB + ?Sized> AsyncKeyValueDBBytes for T {
+    async fn insert_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        AsyncKeyValueDB::insert(self, table_name, &encode_key(key), value).await
+    }
+    async fn get_bytes(&self, table_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        AsyncKeyValueDB::get(self, table_name, &encode_key(key)).await
+    }
+    async fn remove_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        AsyncKeyValueDB::remove(self, table_name, &encode_key(key)).await
+    }
+    async fn iter_bytes(&self, table_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(AsyncKeyValueDB::iter(self, table_name).await?)
+    }
+    async fn iter_from_prefix_bytes(
+        &self,
+        table_name: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(
+            AsyncKeyValueDB::iter_from_prefix(self, table_name, &encode_key(prefix)).await?,
+        )
+    }
+    async fn iter_from_range_bytes(
+        &self,
+        table_name: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, io::Error> {
+        decode_entries(
+            AsyncKeyValueDB::iter_from_range(
+                self,
+                table_name,
+                &encode_key(start_key),
+                &encode_key(end_key),
+            )
+            .await?,
+        )
+    }
+    async fn contains_key_bytes(&self, table_name: &str, key: &[u8]) -> Result<bool, io::Error> {
+        AsyncKeyValueDB::contains_key(self, table_name, &encode_key(key)).await
+    }
+    async fn keys_bytes(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        AsyncKeyValueDB::keys(self, table_name)
+            .await?
+            .iter()
+            .map(|key| decode_key(key))
+            .collect()
+    }
+    async fn compare_and_swap_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        AsyncKeyValueDB::compare_and_swap(self, table_name, &encode_key(key), expected, new).await
+    }
+    async fn get_range_bytes(
+        &self,
+        table_name: &str,
+        key: &[u8],
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        AsyncKeyValueDB::get_range(self, table_name, &encode_key(key), range).await
+    }
+}
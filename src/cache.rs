@@ -0,0 +1,345 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io,
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use futures::lock::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+
+pub use crate::lru::CacheLimits;
+use crate::{lru::Lru, AsyncKeyValueDB, CompareAndSwapError, WriteBatch};
+
+/// Write-through cache: reads are served from `fast` when possible and fall
+/// back to `slow`, writes go to `slow` first and then to `fast`. `slow` is
+/// the source of truth, so listing operations are always answered by it.
+///
+/// Least recently used entries are removed from `fast` once `limits` are
+/// exceeded. `fast` must not be written to by anything else.
+///
+/// A read that misses fills `fast` with the value read from `slow` only if
+/// no write to the same key went through this cache in the meantime, so a
+/// concurrent write is never overwritten by the stale value. Writes to the
+/// same key are serialized, so they reach `fast` in the order they reached
+/// `slow`; a write that reached `slow` but could not be cached leaves the
+/// key uncached.
+#[derive(Debug)]
+pub struct CachedDB<Fast, Slow> {
+    fast: Fast,
+    slow: Slow,
+    limits: CacheLimits,
+    lru: Mutex<Lru>,
+    /// Serializes the writes of each key, sharded by the hash of the key.
+    write_locks: Vec<AsyncMutex<()>>,
+}
+
+/// The number of locks the writes are sharded over.
+const WRITE_LOCK_SHARDS: usize = 64;
+
+impl<Fast: AsyncKeyValueDB, Slow: AsyncKeyValueDB> CachedDB<Fast, Slow> {
+    pub fn new(fast: Fast, slow: Slow, limits: CacheLimits) -> Self {
+        Self {
+            fast,
+            slow,
+            limits,
+            lru: Mutex::new(Lru::default()),
+            write_locks: (0..WRITE_LOCK_SHARDS)
+                .map(|_| AsyncMutex::new(()))
+                .collect(),
+        }
+    }
+
+    pub fn fast(&self) -> &Fast {
+        &self.fast
+    }
+
+    pub fn slow(&self) -> &Slow {
+        &self.slow
+    }
+
+    /// Returns the number of cached entries and their total size in bytes.
+    pub fn cache_usage(&self) -> (usize, usize) {
+        let lru = self.lru.lock().unwrap();
+        (lru.len(), lru.bytes())
+    }
+
+    /// Locks the writes of the keys of `table_name` in `keys`, in shard order.
+    async fn lock_keys<'a>(
+        &self,
+        keys: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Vec<AsyncMutexGuard<'_, ()>> {
+        let mut shards = keys
+            .into_iter()
+            .map(|(table_name, key)| {
+                let mut hasher = DefaultHasher::new();
+                (table_name, key).hash(&mut hasher);
+                hasher.finish() as usize % WRITE_LOCK_SHARDS
+            })
+            .collect::<Vec<_>>();
+        shards.sort_unstable();
+        shards.dedup();
+        let mut guards = Vec::with_capacity(shards.len());
+        for shard in shards {
+            guards.push(self.write_locks[shard].lock().await);
+        }
+        guards
+    }
+
+    /// Locks the writes of every key, for the operations on whole tables.
+    async fn lock_all(&self) -> Vec<AsyncMutexGuard<'_, ()>> {
+        let mut guards = Vec::with_capacity(WRITE_LOCK_SHARDS);
+        for lock in &self.write_locks {
+            guards.push(lock.lock().await);
+        }
+        guards
+    }
+
+    /// Caches `value`, just written to `slow`, or leaves the key uncached if
+    /// that fails. Only fails if the key could not be uncached either.
+    async fn cache_written(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), io::Error> {
+        if self.cache(table_name, key, value).await.is_err() {
+            self.invalidate(table_name, key).await?;
+        }
+        Ok(())
+    }
+
+    async fn cache(&self, table_name: &str, key: &str, value: &[u8]) -> Result<(), io::Error> {
+        let cache_key = (table_name.to_string(), key.to_string());
+        // Fills still in progress would overwrite `value` with an older one.
+        self.lru.lock().unwrap().cancel_fills(&cache_key);
+        self.fast.insert(table_name, key, value).await?;
+        let evicted =
+            self.lru
+                .lock()
+                .unwrap()
+                .insert(cache_key, key.len() + value.len(), self.limits);
+        self.evict(evicted).await
+    }
+
+    /// Caches `value`, read from `slow` by the read `fill`, unless a write
+    /// cancelled the fill. The value is written to `fast` before checking,
+    /// and removed again if the fill was cancelled, so that it can never
+    /// land after the write's own value.
+    async fn fill(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+        fill: u64,
+    ) -> Result<(), io::Error> {
+        let cache_key = (table_name.to_string(), key.to_string());
+        self.fast.insert(table_name, key, value).await?;
+        let evicted = {
+            let mut lru = self.lru.lock().unwrap();
+            lru.finish_fill(&cache_key, fill)
+                .then(|| lru.insert(cache_key, key.len() + value.len(), self.limits))
+        };
+        match evicted {
+            Some(evicted) => self.evict(evicted).await,
+            None => {
+                self.fast.remove(table_name, key).await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn evict(&self, evicted: Vec<(String, String)>) -> Result<(), io::Error> {
+        for (table_name, key) in evicted {
+            self.fast.remove(&table_name, &key).await?;
+        }
+        Ok(())
+    }
+
+    async fn invalidate(&self, table_name: &str, key: &str) -> Result<(), io::Error> {
+        {
+            let cache_key = (table_name.to_string(), key.to_string());
+            let mut lru = self.lru.lock().unwrap();
+            lru.cancel_fills(&cache_key);
+            lru.remove(&cache_key);
+        }
+        self.fast.remove(table_name, key).await?;
+        Ok(())
+    }
+
+    async fn invalidate_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.lru.lock().unwrap().remove_table(table_name);
+        self.fast.delete_table(table_name).await
+    }
+}
+
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<Fast: AsyncKeyValueDB, Slow: AsyncKeyValueDB> AsyncKeyValueDB for CachedDB<Fast, Slow> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let _guards = self.lock_keys([(table_name, key)]).await;
+        let old_value = self.slow.insert(table_name, key, value).await?;
+        self.cache_written(table_name, key, value).await?;
+
+        Ok(old_value)
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        if let Some(value) = self.fast.get(table_name, key).await? {
+            self.lru
+                .lock()
+                .unwrap()
+                .touch(&(table_name.to_string(), key.to_string()));
+            return Ok(Some(value));
+        }
+
+        let cache_key = (table_name.to_string(), key.to_string());
+        let fill = self.lru.lock().unwrap().begin_fill(cache_key.clone());
+        let value = self.slow.get(table_name, key).await;
+        match &value {
+            Ok(Some(value)) => self.fill(table_name, key, value, fill).await?,
+            _ => {
+                self.lru.lock().unwrap().finish_fill(&cache_key, fill);
+            }
+        }
+        value
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let _guards = self.lock_keys([(table_name, key)]).await;
+        let old_value = self.slow.remove(table_name, key).await?;
+        self.invalidate(table_name, key).await?;
+
+        Ok(old_value)
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.slow.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.slow.table_names().await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        let _guards = self.lock_all().await;
+        self.slow.delete_table(table_name).await?;
+        self.invalidate_table(table_name).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.slow.iter_from_prefix(table_name, prefix).await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.slow
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        Ok(self.get(table_name, key).await?.is_some())
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.slow.keys(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.slow.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.slow.is_empty(table_name).await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.slow.create_table(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.slow.values(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        let _guards = self.lock_all().await;
+        self.slow.clear().await?;
+        *self.lru.lock().unwrap() = Lru::default();
+        self.fast.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        let _guards = self.lock_all().await;
+        self.slow.swap_tables(table_a, table_b).await?;
+        self.invalidate_table(table_a).await?;
+        self.invalidate_table(table_b).await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        let _guards = self.lock_all().await;
+        let created = self.slow.ensure_table_with(table_name, init).await?;
+        self.invalidate_table(table_name).await?;
+        Ok(created)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let _guards = self.lock_keys([(table_name, key)]).await;
+        let result = self
+            .slow
+            .compare_and_swap(table_name, key, expected, new)
+            .await;
+        match (&result, new) {
+            (Ok(()), Some(value)) => self.cache_written(table_name, key, value).await?,
+            _ => self.invalidate(table_name, key).await?,
+        }
+        result
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        let keys = batch
+            .ops()
+            .iter()
+            .map(|op| (op.table_name().to_string(), op.key().to_string()))
+            .collect::<Vec<_>>();
+        let _guards = self
+            .lock_keys(keys.iter().map(|(table_name, key)| (&**table_name, &**key)))
+            .await;
+        self.slow.apply_batch(batch).await?;
+        for (table_name, key) in keys {
+            self.invalidate(&table_name, &key).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.slow.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.slow.compact().await
+    }
+}
@@ -0,0 +1,226 @@
+use std::{fmt, io, str::FromStr, sync::Mutex};
+
+use crate::meta::META_TABLE;
+#[cfg(feature = "async")]
+use crate::{update::run_update_async, AsyncKeyValueDB};
+use crate::{
+    update::{run_update, update_error, DEFAULT_MAX_ATTEMPTS},
+    KeyValueDB,
+};
+
+/// The key of the last persisted timestamp in [`META_TABLE`].
+pub const HLC_KEY: &str = "hlc";
+
+const LOGICAL_BITS: u32 = 16;
+const WALL_MASK: u64 = (1 << 48) - 1;
+
+/// A hybrid logical clock timestamp: a 48-bit wall clock time in milliseconds
+/// and a 16-bit counter ordering the events within a millisecond, or those
+/// received from a node whose clock is ahead.
+///
+/// It converts to a `u64` and to 16 hex digits that sort in the same order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hlc(u64);
+
+impl Hlc {
+    /// Only the low 48 bits of `wall_ms` are kept.
+    pub fn new(wall_ms: u64, logical: u16) -> Self {
+        Self((wall_ms & WALL_MASK) << LOGICAL_BITS | logical as u64)
+    }
+
+    pub fn wall_ms(&self) -> u64 {
+        self.0 >> LOGICAL_BITS
+    }
+
+    pub fn logical(&self) -> u16 {
+        self.0 as u16
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        Self(value)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// The next timestamp of the same millisecond, or the first of the next
+    /// millisecond when the counter is exhausted.
+    fn successor(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Hlc {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid HLC"));
+        }
+        u64::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Hands out [`Hlc`] timestamps that increase on every call and stay ahead
+/// of every timestamp received from other nodes, so events are ordered
+/// consistently across devices whose clocks are skewed.
+///
+/// The last timestamp is kept in memory. Call [`HlcClock::load`] when
+/// starting, and [`HlcClock::save`] after handing out timestamps that are
+/// stored, so timestamps stay monotonic across restarts even if the wall
+/// clock goes back.
+#[derive(Debug)]
+pub struct HlcClock {
+    last: Mutex<Hlc>,
+    max_drift_ms: u64,
+    now_ms: fn() -> u64,
+}
+
+impl Default for HlcClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HlcClock {
+    /// The default of [`HlcClock::with_max_drift`]: one minute.
+    pub const DEFAULT_MAX_DRIFT_MS: u64 = 60_000;
+
+    pub fn new() -> Self {
+        Self::with_time_source(system_time_ms)
+    }
+
+    /// Reads the wall clock from `now_ms`, in milliseconds since the Unix
+    /// epoch.
+    pub fn with_time_source(now_ms: fn() -> u64) -> Self {
+        Self {
+            last: Mutex::new(Hlc::default()),
+            max_drift_ms: Self::DEFAULT_MAX_DRIFT_MS,
+            now_ms,
+        }
+    }
+
+    /// Rejects remote timestamps more than `max_drift_ms` ahead of the wall
+    /// clock, so that one node with a wrong clock cannot drag every other
+    /// clock into the future.
+    pub fn with_max_drift(mut self, max_drift_ms: u64) -> Self {
+        self.max_drift_ms = max_drift_ms;
+        self
+    }
+
+    /// The last timestamp handed out or received.
+    pub fn last(&self) -> Hlc {
+        *self.last.lock().unwrap()
+    }
+
+    /// Returns a timestamp for a local event, such as a write to be synced.
+    pub fn now(&self) -> Hlc {
+        let wall = Hlc::new((self.now_ms)(), 0);
+        let mut last = self.last.lock().unwrap();
+        *last = if wall > *last { wall } else { last.successor() };
+        *last
+    }
+
+    /// Merges the timestamp of an event received from another node and
+    /// returns a timestamp for receiving it, which is after both.
+    pub fn update(&self, remote: Hlc) -> Result<Hlc, io::Error> {
+        let now_ms = (self.now_ms)();
+        if remote.wall_ms() > now_ms.saturating_add(self.max_drift_ms) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "remote timestamp {} is {} ms ahead of the local clock",
+                    remote,
+                    remote.wall_ms() - now_ms
+                ),
+            ));
+        }
+        let mut last = self.last.lock().unwrap();
+        let wall = Hlc::new(now_ms, 0);
+        *last = if wall > *last && wall > remote {
+            wall
+        } else {
+            (*last).max(remote).successor()
+        };
+        Ok(*last)
+    }
+
+    /// Moves the clock forward to the timestamp saved in `db`, if it is
+    /// ahead.
+    pub fn load(&self, db: &dyn KeyValueDB) -> Result<Hlc, io::Error> {
+        let stored = decode(db.get(META_TABLE, HLC_KEY)?.as_deref())?;
+        Ok(self.advance_to(stored))
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn load_async(&self, db: &dyn AsyncKeyValueDB) -> Result<Hlc, io::Error> {
+        let stored = decode(db.get(META_TABLE, HLC_KEY).await?.as_deref())?;
+        Ok(self.advance_to(stored))
+    }
+
+    /// Saves the last timestamp in `db`, unless a later one is already
+    /// saved, e.g. by another clock sharing the database. Fails if the saved
+    /// timestamp changed during each of [`DEFAULT_MAX_ATTEMPTS`] attempts.
+    pub fn save(&self, db: &dyn KeyValueDB) -> Result<(), io::Error> {
+        let last = self.last();
+        run_update(db, META_TABLE, HLC_KEY, DEFAULT_MAX_ATTEMPTS, |current| {
+            later(current, last)
+        })
+        .map_err(update_error)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn save_async(&self, db: &dyn AsyncKeyValueDB) -> Result<(), io::Error> {
+        let last = self.last();
+        run_update_async(db, META_TABLE, HLC_KEY, DEFAULT_MAX_ATTEMPTS, |current| {
+            later(current, last)
+        })
+        .await
+        .map_err(update_error)?;
+        Ok(())
+    }
+
+    fn advance_to(&self, hlc: Hlc) -> Hlc {
+        let mut last = self.last.lock().unwrap();
+        *last = (*last).max(hlc);
+        *last
+    }
+}
+
+/// The value to store so that the saved timestamp is at least `last`.
+fn later(current: Option<&[u8]>, last: Hlc) -> Result<Option<Vec<u8>>, io::Error> {
+    Ok(Some(
+        decode(current)?.max(last).as_u64().to_le_bytes().to_vec(),
+    ))
+}
+
+fn decode(value: Option<&[u8]>) -> Result<Hlc, io::Error> {
+    match value {
+        Some(value) => <[u8; 8]>::try_from(value)
+            .map(|bytes| Hlc(u64::from_le_bytes(bytes)))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid HLC value")),
+        None => Ok(Hlc::default()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn system_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_time_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
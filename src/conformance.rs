@@ -0,0 +1,628 @@
+use std::{
+    fmt::Write,
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{error::is_unsupported, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+const TABLE: &str = "__keyvalue_conformance";
+/// The table swapped with [`TABLE`].
+const OTHER_TABLE: &str = "__keyvalue_conformance_other";
+/// The table filled to measure whether prefixes are pushed down.
+const PUSHDOWN_TABLE: &str = "__keyvalue_conformance_pushdown";
+const UNORDERED_KEYS: [&str; 5] = ["c", "a", "e", "b", "d"];
+/// The writers racing on one key to measure whether `insert` returns the
+/// replaced values atomically, and the writes of each.
+const RACING_WRITERS: usize = 4;
+const RACING_WRITES: usize = 50;
+/// The batches written while a reader looks for partially applied ones.
+const RACING_BATCHES: usize = 100;
+const BATCH_PREFIX: &str = "batch::";
+/// The entries of [`PUSHDOWN_TABLE`], of which one matches the prefix read.
+const PUSHDOWN_ENTRIES: usize = 1000;
+const PUSHDOWN_PREFIX: &str = "match";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub checks: Vec<CheckResult>,
+    /// Whether `iter` returned keys in lexicographic order.
+    pub ordered_iteration: bool,
+    /// Whether `iter_from_prefix` returned keys in lexicographic order.
+    pub ordered_prefix_iteration: bool,
+    /// Whether `compare_and_swap` is supported. Its check passes either way.
+    pub compare_and_swap: bool,
+    /// Whether `insert` returned each replaced value exactly once while
+    /// several writers raced on one key, or `None` if it was not measured.
+    pub atomic_old_value: Option<bool>,
+    /// Whether a concurrent reader never saw a batch partially applied, or
+    /// `None` if it was not measured.
+    pub atomic_batches: Option<bool>,
+    /// Whether `iter_from_prefix` took less than a quarter of the time of
+    /// `iter` on a table where one entry in a thousand matches, which
+    /// suggests that the backend applies the prefix itself. `None` if it was
+    /// not measured, as on wasm, which has no clock.
+    pub prefix_pushdown: Option<bool>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn deviations(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"checks\":[");
+        for (i, check) in self.checks.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"passed\":{}",
+                check.name, check.passed
+            );
+            if let Some(detail) = &check.detail {
+                json.push_str(",\"detail\":");
+                push_json_string(&mut json, detail);
+            }
+            json.push('}');
+        }
+        let _ = write!(
+            json,
+            "],\"ordered_iteration\":{},\"ordered_prefix_iteration\":{},\"compare_and_swap\":{}",
+            self.ordered_iteration, self.ordered_prefix_iteration, self.compare_and_swap
+        );
+        for (name, value) in [
+            ("atomic_old_value", self.atomic_old_value),
+            ("atomic_batches", self.atomic_batches),
+            ("prefix_pushdown", self.prefix_pushdown),
+        ] {
+            let _ = match value {
+                Some(value) => write!(json, ",\"{}\":{}", name, value),
+                None => write!(json, ",\"{}\":null", name),
+            };
+        }
+        json.push('}');
+        json
+    }
+
+    fn check(&mut self, name: &'static str, result: Result<Option<String>, io::Error>) {
+        let (passed, detail) = match result {
+            Ok(None) => (true, None),
+            Ok(Some(detail)) => (false, Some(detail)),
+            Err(e) => (false, Some(format!("error: {}", e))),
+        };
+        self.checks.push(CheckResult {
+            name,
+            passed,
+            detail,
+        });
+    }
+}
+
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn expect<T: PartialEq + std::fmt::Debug>(actual: T, expected: T) -> Option<String> {
+    if actual == expected {
+        None
+    } else {
+        Some(format!("expected {:?}, got {:?}", expected, actual))
+    }
+}
+
+fn is_sorted(keys: &[String]) -> bool {
+    keys.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+fn check_iter(report: &mut ConformanceReport, iter: Result<Vec<(String, Vec<u8>)>, io::Error>) {
+    let result = iter.map(|entries| {
+        let mut keys = entries
+            .into_iter()
+            .filter(|(k, v)| k.as_bytes() == v.as_slice())
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        report.ordered_iteration = is_sorted(&keys);
+        keys.sort();
+        expect(keys, ["a", "b", "c", "d", "e"].map(String::from).to_vec())
+    });
+    report.check("iter", result);
+}
+
+fn check_iter_from_prefix(
+    report: &mut ConformanceReport,
+    iter: Result<Vec<(String, Vec<u8>)>, io::Error>,
+) {
+    let result = iter.map(|entries| {
+        let mut keys = entries.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        report.ordered_prefix_iteration = is_sorted(&keys);
+        keys.sort();
+        expect(keys, ["a", "ab"].map(String::from).to_vec())
+    });
+    report.check("iter_from_prefix", result);
+}
+
+fn sorted_keys(entries: Vec<(String, Vec<u8>)>) -> Vec<String> {
+    let mut keys = entries.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+/// The batch applied by the `apply_batch` check.
+fn check_batch() -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    batch.insert(TABLE, "f", b"f").remove(TABLE, "a");
+    batch
+}
+
+/// Checks `compare_and_swap` from the results of claiming a missing key,
+/// claiming it again and removing it, and of reading it afterwards. Passes
+/// if the backend does not support it.
+fn check_compare_and_swap(
+    report: &mut ConformanceReport,
+    claimed: Result<(), CompareAndSwapError>,
+    claimed_again: Result<(), CompareAndSwapError>,
+    removed: Result<(), CompareAndSwapError>,
+    after: Result<Option<Vec<u8>>, io::Error>,
+) {
+    if matches!(&claimed, Err(CompareAndSwapError::Io(e)) if is_unsupported(e)) {
+        report.check("compare_and_swap", Ok(None));
+        return;
+    }
+    report.compare_and_swap = true;
+    let result = match (claimed, claimed_again, removed) {
+        (Ok(()), Err(CompareAndSwapError::Mismatch { current }), Ok(())) => {
+            after.map(|after| expect((current, after), (Some(b"1".to_vec()), None)))
+        }
+        (claimed, claimed_again, removed) => Ok(Some(format!(
+            "expected a swap, a mismatch and a swap, got {:?}, {:?} and {:?}",
+            claimed, claimed_again, removed
+        ))),
+    };
+    report.check("compare_and_swap", result);
+}
+
+fn racing_value(writer: usize, write: usize) -> String {
+    format!("{}:{}", writer, write)
+}
+
+/// Whether the values returned by the racing inserts and the value they
+/// left hold every written value and the initial `None` exactly once, as
+/// every insert replaces a distinct value when they are atomic.
+fn old_values_atomic(mut values: Vec<Option<Vec<u8>>>, last: Option<Vec<u8>>) -> bool {
+    values.push(last);
+    let len = values.len();
+    values.sort();
+    values.dedup();
+    values.len() == len
+}
+
+fn racing_batch(i: usize) -> WriteBatch {
+    let value = i.to_string();
+    let mut batch = WriteBatch::new();
+    batch
+        .insert(TABLE, &format!("{}a", BATCH_PREFIX), value.as_bytes())
+        .insert(TABLE, &format!("{}b", BATCH_PREFIX), value.as_bytes());
+    batch
+}
+
+/// Whether `entries`, the keys written by the racing batches, show a batch
+/// partially applied.
+fn torn(entries: &[(String, Vec<u8>)]) -> bool {
+    entries.len() == 1 || entries.windows(2).any(|pair| pair[0].1 != pair[1].1)
+}
+
+fn pushdown_batch() -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    for i in 0..PUSHDOWN_ENTRIES - 1 {
+        batch.insert(PUSHDOWN_TABLE, &format!("{:04}", i), &[0; 1024]);
+    }
+    batch.insert(PUSHDOWN_TABLE, PUSHDOWN_PREFIX, &[0; 1024]);
+    batch
+}
+
+fn pushed_down(full: Duration, prefix: Duration) -> bool {
+    prefix * 4 < full
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn measure_old_values(db: &dyn KeyValueDB) -> Result<bool, io::Error> {
+    let returned = std::thread::scope(|scope| {
+        let writers = (0..RACING_WRITERS)
+            .map(|writer| {
+                scope.spawn(move || {
+                    (0..RACING_WRITES)
+                        .map(|i| db.insert(TABLE, "race", racing_value(writer, i).as_bytes()))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        writers
+            .into_iter()
+            .map(|writer| writer.join().expect("writer panicked"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    let last = db.get(TABLE, "race")?;
+    Ok(old_values_atomic(returned.concat(), last))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn measure_batches(db: &dyn KeyValueDB) -> Result<bool, io::Error> {
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            let written = (0..RACING_BATCHES).try_for_each(|i| db.apply_batch(racing_batch(i)));
+            done.store(true, Ordering::SeqCst);
+            written
+        });
+        let mut atomic = true;
+        while atomic && !done.load(Ordering::SeqCst) {
+            atomic = !torn(&db.iter_from_prefix(TABLE, BATCH_PREFIX)?);
+        }
+        writer.join().expect("writer panicked")?;
+        Ok(atomic)
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn measure_pushdown(db: &dyn KeyValueDB) -> Result<bool, io::Error> {
+    use std::time::Instant;
+
+    db.apply_batch(pushdown_batch())?;
+    let (mut full, mut prefix) = (Duration::MAX, Duration::MAX);
+    for _ in 0..3 {
+        let start = Instant::now();
+        db.iter(PUSHDOWN_TABLE)?;
+        full = full.min(start.elapsed());
+        let start = Instant::now();
+        db.iter_from_prefix(PUSHDOWN_TABLE, PUSHDOWN_PREFIX)?;
+        prefix = prefix.min(start.elapsed());
+    }
+    Ok(pushed_down(full, prefix))
+}
+
+/// Lets the other future of a `join` run.
+#[cfg(feature = "async")]
+async fn yield_now() {
+    let mut yielded = false;
+    futures::future::poll_fn(|cx| {
+        if yielded {
+            std::task::Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(feature = "async")]
+async fn measure_old_values_async(db: &dyn AsyncKeyValueDB) -> Result<bool, io::Error> {
+    let returned = futures::future::try_join_all((0..RACING_WRITERS).map(|writer| async move {
+        let mut returned = Vec::with_capacity(RACING_WRITES);
+        for i in 0..RACING_WRITES {
+            returned.push(
+                db.insert(TABLE, "race", racing_value(writer, i).as_bytes())
+                    .await?,
+            );
+        }
+        Ok::<_, io::Error>(returned)
+    }))
+    .await?;
+    let last = db.get(TABLE, "race").await?;
+    Ok(old_values_atomic(returned.concat(), last))
+}
+
+#[cfg(feature = "async")]
+async fn measure_batches_async(db: &dyn AsyncKeyValueDB) -> Result<bool, io::Error> {
+    let done = AtomicBool::new(false);
+    let writer = async {
+        for i in 0..RACING_BATCHES {
+            if let Err(e) = db.apply_batch(racing_batch(i)).await {
+                done.store(true, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        done.store(true, Ordering::SeqCst);
+        Ok(())
+    };
+    let reader = async {
+        let mut atomic = true;
+        while atomic && !done.load(Ordering::SeqCst) {
+            atomic = !torn(&db.iter_from_prefix(TABLE, BATCH_PREFIX).await?);
+            yield_now().await;
+        }
+        Ok::<_, io::Error>(atomic)
+    };
+    let (written, atomic) = futures::future::join(writer, reader).await;
+    written?;
+    atomic
+}
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+async fn measure_pushdown_async(db: &dyn AsyncKeyValueDB) -> Result<bool, io::Error> {
+    use std::time::Instant;
+
+    db.apply_batch(pushdown_batch()).await?;
+    let (mut full, mut prefix) = (Duration::MAX, Duration::MAX);
+    for _ in 0..3 {
+        let start = Instant::now();
+        db.iter(PUSHDOWN_TABLE).await?;
+        full = full.min(start.elapsed());
+        let start = Instant::now();
+        db.iter_from_prefix(PUSHDOWN_TABLE, PUSHDOWN_PREFIX).await?;
+        prefix = prefix.min(start.elapsed());
+    }
+    Ok(pushed_down(full, prefix))
+}
+
+/// Runs the contract checks against `db` inside dedicated tables, which are
+/// deleted afterwards, and measures the semantics that the contract leaves
+/// to the backend.
+pub fn run(db: &dyn KeyValueDB) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+
+    report.check(
+        "get_missing",
+        db.get(TABLE, "missing").map(|v| expect(v, None)),
+    );
+    report.check(
+        "insert_new_returns_none",
+        db.insert(TABLE, "key", b"value").map(|v| expect(v, None)),
+    );
+    report.check(
+        "insert_returns_old_value",
+        db.insert(TABLE, "key", b"other")
+            .map(|v| expect(v, Some(b"value".to_vec()))),
+    );
+    report.check(
+        "empty_value",
+        db.insert(TABLE, "key", &[])
+            .and_then(|_| db.get(TABLE, "key"))
+            .map(|v| expect(v, Some(Vec::new()))),
+    );
+    report.check(
+        "remove_returns_old_value",
+        db.remove(TABLE, "key").map(|v| expect(v, Some(Vec::new()))),
+    );
+    report.check(
+        "remove_missing",
+        db.remove(TABLE, "key").map(|v| expect(v, None)),
+    );
+    report.check(
+        "contains_key",
+        db.insert(TABLE, "key", b"value")
+            .and_then(|_| db.contains_key(TABLE, "key"))
+            .map(|v| expect(v, true)),
+    );
+    report.check(
+        "table_names",
+        db.table_names()
+            .map(|names| expect(names.iter().any(|name| name == TABLE), true)),
+    );
+    report.check(
+        "delete_table",
+        db.delete_table(TABLE)
+            .and_then(|_| db.iter(TABLE))
+            .map(|entries| expect(entries, Vec::new())),
+    );
+
+    let inserted = UNORDERED_KEYS
+        .iter()
+        .try_for_each(|key| db.insert(TABLE, key, key.as_bytes()).map(|_| ()));
+    let iter = inserted.and_then(|_| db.iter(TABLE));
+    check_iter(&mut report, iter);
+    let iter_from_prefix = db
+        .insert(TABLE, "ab", b"ab")
+        .and_then(|_| db.iter_from_prefix(TABLE, "a"));
+    check_iter_from_prefix(&mut report, iter_from_prefix);
+
+    report.check("len", db.len(TABLE).map(|len| expect(len, 6)));
+    report.check(
+        "iter_from_range",
+        db.iter_from_range(TABLE, "ab", "d")
+            .map(|entries| expect(sorted_keys(entries), strings(&["ab", "b", "c"]))),
+    );
+    let batch = db
+        .apply_batch(check_batch())
+        .and_then(|_| Ok((db.get(TABLE, "f")?, db.get(TABLE, "a")?)));
+    report.check(
+        "apply_batch",
+        batch.map(|values| expect(values, (Some(b"f".to_vec()), None))),
+    );
+    let claimed = db.compare_and_swap(TABLE, "cas", None, Some(b"1"));
+    let claimed_again = db.compare_and_swap(TABLE, "cas", None, Some(b"2"));
+    let removed = db.compare_and_swap(TABLE, "cas", Some(b"1"), None);
+    let after = db.get(TABLE, "cas");
+    check_compare_and_swap(&mut report, claimed, claimed_again, removed, after);
+    let swapped = db
+        .insert(OTHER_TABLE, "x", b"x")
+        .and_then(|_| db.swap_tables(TABLE, OTHER_TABLE))
+        .and_then(|_| {
+            Ok((
+                sorted_keys(db.iter(TABLE)?),
+                sorted_keys(db.iter(OTHER_TABLE)?),
+            ))
+        });
+    report.check(
+        "swap_tables",
+        swapped.map(|tables| {
+            expect(
+                tables,
+                (strings(&["x"]), strings(&["ab", "b", "c", "d", "e", "f"])),
+            )
+        }),
+    );
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        report.atomic_old_value = measure_old_values(db).ok();
+        report.atomic_batches = measure_batches(db).ok();
+        report.prefix_pushdown = measure_pushdown(db).ok();
+    }
+
+    for table in [TABLE, OTHER_TABLE, PUSHDOWN_TABLE] {
+        let _ = db.delete_table(table);
+    }
+    report
+}
+
+#[cfg(feature = "async")]
+pub async fn run_async(db: &dyn AsyncKeyValueDB) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+
+    report.check(
+        "get_missing",
+        db.get(TABLE, "missing").await.map(|v| expect(v, None)),
+    );
+    report.check(
+        "insert_new_returns_none",
+        db.insert(TABLE, "key", b"value")
+            .await
+            .map(|v| expect(v, None)),
+    );
+    report.check(
+        "insert_returns_old_value",
+        db.insert(TABLE, "key", b"other")
+            .await
+            .map(|v| expect(v, Some(b"value".to_vec()))),
+    );
+    let empty_value = match db.insert(TABLE, "key", &[]).await {
+        Ok(_) => db.get(TABLE, "key").await,
+        Err(e) => Err(e),
+    };
+    report.check(
+        "empty_value",
+        empty_value.map(|v| expect(v, Some(Vec::new()))),
+    );
+    report.check(
+        "remove_returns_old_value",
+        db.remove(TABLE, "key")
+            .await
+            .map(|v| expect(v, Some(Vec::new()))),
+    );
+    report.check(
+        "remove_missing",
+        db.remove(TABLE, "key").await.map(|v| expect(v, None)),
+    );
+    let contains_key = match db.insert(TABLE, "key", b"value").await {
+        Ok(_) => db.contains_key(TABLE, "key").await,
+        Err(e) => Err(e),
+    };
+    report.check("contains_key", contains_key.map(|v| expect(v, true)));
+    report.check(
+        "table_names",
+        db.table_names()
+            .await
+            .map(|names| expect(names.iter().any(|name| name == TABLE), true)),
+    );
+    let delete_table = match db.delete_table(TABLE).await {
+        Ok(_) => db.iter(TABLE).await,
+        Err(e) => Err(e),
+    };
+    report.check(
+        "delete_table",
+        delete_table.map(|entries| expect(entries, Vec::new())),
+    );
+
+    let mut inserted = Ok(());
+    for key in UNORDERED_KEYS {
+        if let Err(e) = db.insert(TABLE, key, key.as_bytes()).await {
+            inserted = Err(e);
+            break;
+        }
+    }
+    let iter = match inserted {
+        Ok(_) => db.iter(TABLE).await,
+        Err(e) => Err(e),
+    };
+    check_iter(&mut report, iter);
+    let iter_from_prefix = match db.insert(TABLE, "ab", b"ab").await {
+        Ok(_) => db.iter_from_prefix(TABLE, "a").await,
+        Err(e) => Err(e),
+    };
+    check_iter_from_prefix(&mut report, iter_from_prefix);
+
+    report.check("len", db.len(TABLE).await.map(|len| expect(len, 6)));
+    report.check(
+        "iter_from_range",
+        db.iter_from_range(TABLE, "ab", "d")
+            .await
+            .map(|entries| expect(sorted_keys(entries), strings(&["ab", "b", "c"]))),
+    );
+    let batch = async {
+        db.apply_batch(check_batch()).await?;
+        Ok::<_, io::Error>((db.get(TABLE, "f").await?, db.get(TABLE, "a").await?))
+    }
+    .await;
+    report.check(
+        "apply_batch",
+        batch.map(|values| expect(values, (Some(b"f".to_vec()), None))),
+    );
+    let claimed = db.compare_and_swap(TABLE, "cas", None, Some(b"1")).await;
+    let claimed_again = db.compare_and_swap(TABLE, "cas", None, Some(b"2")).await;
+    let removed = db.compare_and_swap(TABLE, "cas", Some(b"1"), None).await;
+    let after = db.get(TABLE, "cas").await;
+    check_compare_and_swap(&mut report, claimed, claimed_again, removed, after);
+    let swapped = async {
+        db.insert(OTHER_TABLE, "x", b"x").await?;
+        db.swap_tables(TABLE, OTHER_TABLE).await?;
+        Ok::<_, io::Error>((
+            sorted_keys(db.iter(TABLE).await?),
+            sorted_keys(db.iter(OTHER_TABLE).await?),
+        ))
+    }
+    .await;
+    report.check(
+        "swap_tables",
+        swapped.map(|tables| {
+            expect(
+                tables,
+                (strings(&["x"]), strings(&["ab", "b", "c", "d", "e", "f"])),
+            )
+        }),
+    );
+
+    report.atomic_old_value = measure_old_values_async(db).await.ok();
+    report.atomic_batches = measure_batches_async(db).await.ok();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        report.prefix_pushdown = measure_pushdown_async(db).await.ok();
+    }
+
+    for table in [TABLE, OTHER_TABLE, PUSHDOWN_TABLE] {
+        let _ = db.delete_table(table).await;
+    }
+    report
+}
@@ -0,0 +1,114 @@
+use std::{
+    env, io,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::KeyValueDB;
+
+pub const CHILD_ENV: &str = "KEYVALUE_CRASH_TEST_PATH";
+
+const DATA_TABLE: &str = "crash_data";
+const VALUE_LEN: usize = 1024;
+
+/// Runs a write workload against the database at `path` in a child process,
+/// kills the child at a pseudo-random point and reopens the database to
+/// check that every write that became visible is complete and that writes
+/// became durable in order. This is repeated `iterations` times against the
+/// same database. Returns the number of entries that survived the last crash.
+///
+/// The child process is the current test binary, re-executed with
+/// `test_name` as an exact filter, so this must be called from the test
+/// named `test_name`. When running as the child, this never returns.
+pub fn run<D, F>(test_name: &str, path: &Path, iterations: usize, open: F) -> io::Result<u64>
+where
+    D: KeyValueDB,
+    F: Fn(&Path) -> io::Result<D>,
+{
+    if let Some(child_path) = env::var_os(CHILD_ENV) {
+        let db = open(Path::new(&child_path))?;
+        run_workload(&db)?;
+        unreachable!("crash workload never finishes");
+    }
+
+    let mut written = 0;
+    for iteration in 0..iterations {
+        let mut child = Command::new(env::current_exe()?)
+            .args([test_name, "--exact", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        thread::sleep(kill_delay(iteration));
+        child.kill()?;
+        child.wait()?;
+
+        let db = open(path)?;
+        let count = check_invariants(&db)?;
+        if count < written {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} entries were durable before iteration {}, only {} after",
+                    written, iteration, count
+                ),
+            ));
+        }
+        written = count;
+    }
+
+    Ok(written)
+}
+
+fn run_workload(db: &dyn KeyValueDB) -> io::Result<()> {
+    let mut next = db.keys(DATA_TABLE)?.len() as u64;
+    loop {
+        db.insert(DATA_TABLE, &entry_key(next), &entry_value(next))?;
+        next += 1;
+    }
+}
+
+fn check_invariants(db: &dyn KeyValueDB) -> io::Result<u64> {
+    let mut entries = db.iter(DATA_TABLE)?;
+    entries.sort();
+    for (index, (key, value)) in entries.iter().enumerate() {
+        let index = index as u64;
+        if *key != entry_key(index) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected key {}, found {}", entry_key(index), key),
+            ));
+        }
+        if *value != entry_value(index) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("value of key {} is torn", key),
+            ));
+        }
+    }
+    Ok(entries.len() as u64)
+}
+
+fn entry_key(index: u64) -> String {
+    format!("{:020}", index)
+}
+
+fn entry_value(index: u64) -> Vec<u8> {
+    index
+        .to_le_bytes()
+        .iter()
+        .copied()
+        .cycle()
+        .take(VALUE_LEN)
+        .collect()
+}
+
+fn kill_delay(iteration: usize) -> Duration {
+    let jitter = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or_default();
+    Duration::from_millis(100 + (jitter + iteration as u64 * 37) % 200)
+}
@@ -0,0 +1,151 @@
+use crate::io;
+use alloc::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::KeyValueDB;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Differences between two databases, keyed by table name. Keys are reported
+/// relative to the first database: `added` keys only exist in the second one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    pub tables: BTreeMap<String, TableDiff>,
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+pub fn diff(a: &dyn KeyValueDB, b: &dyn KeyValueDB) -> Result<DiffReport, io::Error> {
+    let mut table_names = BTreeSet::new();
+    table_names.extend(a.table_names()?);
+    table_names.extend(b.table_names()?);
+
+    let mut report = DiffReport::default();
+    for table_name in table_names {
+        let table_diff = diff_entries(a.iter(&table_name)?, b.iter(&table_name)?);
+        if !table_diff.is_empty() {
+            report.tables.insert(table_name, table_diff);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(feature = "async")]
+pub async fn diff_async(
+    a: &dyn AsyncKeyValueDB,
+    b: &dyn AsyncKeyValueDB,
+) -> Result<DiffReport, io::Error> {
+    let mut table_names = BTreeSet::new();
+    table_names.extend(a.table_names().await?);
+    table_names.extend(b.table_names().await?);
+
+    let mut report = DiffReport::default();
+    for table_name in table_names {
+        let table_diff = diff_entries(a.iter(&table_name).await?, b.iter(&table_name).await?);
+        if !table_diff.is_empty() {
+            report.tables.insert(table_name, table_diff);
+        }
+    }
+    Ok(report)
+}
+
+/// Like [`diff`], but compares the `checksum` of every value instead of the
+/// value, so backends that hash values where they are stored do not have to
+/// transfer them. Values with equal checksums are taken to be equal.
+pub fn diff_checksums(a: &dyn KeyValueDB, b: &dyn KeyValueDB) -> Result<DiffReport, io::Error> {
+    let mut table_names = BTreeSet::new();
+    table_names.extend(a.table_names()?);
+    table_names.extend(b.table_names()?);
+
+    let mut report = DiffReport::default();
+    for table_name in table_names {
+        let table_diff = diff_entries(checksums(a, &table_name)?, checksums(b, &table_name)?);
+        if !table_diff.is_empty() {
+            report.tables.insert(table_name, table_diff);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(feature = "async")]
+pub async fn diff_checksums_async(
+    a: &dyn AsyncKeyValueDB,
+    b: &dyn AsyncKeyValueDB,
+) -> Result<DiffReport, io::Error> {
+    let mut table_names = BTreeSet::new();
+    table_names.extend(a.table_names().await?);
+    table_names.extend(b.table_names().await?);
+
+    let mut report = DiffReport::default();
+    for table_name in table_names {
+        let table_diff = diff_entries(
+            checksums_async(a, &table_name).await?,
+            checksums_async(b, &table_name).await?,
+        );
+        if !table_diff.is_empty() {
+            report.tables.insert(table_name, table_diff);
+        }
+    }
+    Ok(report)
+}
+
+/// Returns the checksum of every value of `table_name`, skipping keys removed
+/// after they were listed.
+fn checksums(db: &dyn KeyValueDB, table_name: &str) -> Result<Vec<(String, u64)>, io::Error> {
+    let mut checksums = Vec::new();
+    for key in db.keys(table_name)? {
+        if let Some(checksum) = db.checksum(table_name, &key)? {
+            checksums.push((key, checksum));
+        }
+    }
+    Ok(checksums)
+}
+
+#[cfg(feature = "async")]
+async fn checksums_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+) -> Result<Vec<(String, u64)>, io::Error> {
+    let mut checksums = Vec::new();
+    for key in db.keys(table_name).await? {
+        if let Some(checksum) = db.checksum(table_name, &key).await? {
+            checksums.push((key, checksum));
+        }
+    }
+    Ok(checksums)
+}
+
+fn diff_entries<V: PartialEq>(a: Vec<(String, V)>, b: Vec<(String, V)>) -> TableDiff {
+    let mut a = a.into_iter().collect::<BTreeMap<_, _>>();
+
+    let mut table_diff = TableDiff::default();
+    for (key, value) in b {
+        match a.remove(&key) {
+            Some(old_value) if old_value != value => table_diff.changed.push(key),
+            Some(_) => {}
+            None => table_diff.added.push(key),
+        }
+    }
+    table_diff.removed.extend(a.into_keys());
+    table_diff.added.sort();
+    table_diff.changed.sort();
+    table_diff
+}
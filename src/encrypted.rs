@@ -0,0 +1,687 @@
+use core::fmt;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+const NONCE_LEN: usize = 24;
+
+/// Supplies the key of an [`EncryptedDB`], so that applications can keep it
+/// in a keychain or a key management service instead of handling raw bytes.
+pub trait KeyProvider {
+    fn key(&self) -> Result<[u8; 32], io::Error>;
+}
+
+impl KeyProvider for [u8; 32] {
+    fn key(&self) -> Result<[u8; 32], io::Error> {
+        Ok(*self)
+    }
+}
+
+/// Keeps the key in the keychain of the operating system, under `service`
+/// and `user`. A random key is generated and stored on first use.
+#[cfg(feature = "keyring")]
+pub struct KeyringKeyProvider {
+    entry: keyring::Entry,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringKeyProvider {
+    pub fn new(service: &str, user: &str) -> Result<Self, io::Error> {
+        let entry = keyring::Entry::new(service, user).map_err(keyring_error_to_io_error)?;
+        Ok(Self { entry })
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl KeyProvider for KeyringKeyProvider {
+    fn key(&self) -> Result<[u8; 32], io::Error> {
+        match self.entry.get_secret() {
+            Ok(secret) => <[u8; 32]>::try_from(secret.as_slice()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stored secret is not a 32-byte key",
+                )
+            }),
+            Err(keyring::Error::NoEntry) => {
+                let key: [u8; 32] = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+                self.entry
+                    .set_secret(&key)
+                    .map_err(keyring_error_to_io_error)?;
+                Ok(key)
+            }
+            Err(e) => Err(keyring_error_to_io_error(e)),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_error_to_io_error(e: keyring::Error) -> io::Error {
+    match e {
+        keyring::Error::NoStorageAccess(_) => io::Error::new(io::ErrorKind::PermissionDenied, e),
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// Returned by `insert` and `remove` when the write went through but the
+/// value it replaced could not be decrypted, for instance because it was
+/// written with another key. Holds that value as stored. Converts into an
+/// `io::Error` of kind `InvalidData`, from which it can be recovered with
+/// [`UndecryptableOldValue::from_io_error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndecryptableOldValue {
+    pub stored: Vec<u8>,
+}
+
+impl UndecryptableOldValue {
+    /// Returns the `UndecryptableOldValue` wrapped by `e`, if any.
+    #[cfg(feature = "std")]
+    pub fn from_io_error(e: &io::Error) -> Option<&UndecryptableOldValue> {
+        e.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for UndecryptableOldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the value was written, but the one it replaced could not be decrypted"
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UndecryptableOldValue {}
+
+#[cfg(feature = "std")]
+impl From<UndecryptableOldValue> for io::Error {
+    fn from(e: UndecryptableOldValue) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<UndecryptableOldValue> for io::Error {
+    fn from(_: UndecryptableOldValue) -> Self {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the value was written, but the one it replaced could not be decrypted",
+        )
+    }
+}
+
+/// Encrypts values with XChaCha20-Poly1305 before handing them to the
+/// wrapped database. Every value is stored as a random nonce followed by the
+/// ciphertext, and the table name and key are authenticated with it so that
+/// a value cannot be moved to another key or table unnoticed. Table names
+/// and keys are stored in plaintext.
+///
+/// As values are bound to their table, `swap_tables` decrypts and encrypts
+/// again the values of both tables, and writes them in one `apply_batch`.
+///
+/// `insert` and `remove` write first and decrypt the value they replaced
+/// afterwards, so that the write stays a single call of the wrapped
+/// database. If that value cannot be decrypted they fail with
+/// [`UndecryptableOldValue`] even though the write happened.
+pub struct EncryptedDB<T> {
+    inner: T,
+    cipher: ValueCipher,
+}
+
+impl<T> EncryptedDB<T> {
+    pub fn new(inner: T, key: &[u8; 32]) -> Self {
+        Self {
+            inner,
+            cipher: ValueCipher::new(key),
+        }
+    }
+
+    pub fn with_key_provider(inner: T, provider: &dyn KeyProvider) -> Result<Self, io::Error> {
+        Ok(Self::new(inner, &provider.key()?))
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for EncryptedDB<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedDB")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for EncryptedDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let value = self.cipher.encrypt(table_name, key, value)?;
+        self.cipher
+            .decrypt_replaced(table_name, key, self.inner.insert(table_name, key, &value)?)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.cipher
+            .decrypt_optional(table_name, key, self.inner.get(table_name, key)?)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.cipher
+            .decrypt_replaced(table_name, key, self.inner.remove(table_name, key)?)
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher
+            .decrypt_entries(table_name, self.inner.iter(table_name)?)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names()
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name)
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher
+            .decrypt_entries(table_name, self.inner.iter_from_prefix(table_name, prefix)?)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher.decrypt_entries(
+            table_name,
+            self.inner.iter_from_range(table_name, start_key, end_key)?,
+        )
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name)
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear()
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let entries_a = self.inner.iter(table_a)?;
+        let entries_b = self.inner.iter(table_b)?;
+        self.inner.apply_batch(
+            self.cipher
+                .swap_batch(table_a, entries_a, table_b, entries_b)?,
+        )
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        let mut error = None;
+        let created = self.inner.ensure_table_with(table_name, &mut || {
+            self.cipher
+                .encrypt_entries(table_name, init())
+                .unwrap_or_else(|e| {
+                    error = Some(e);
+                    Vec::new()
+                })
+        })?;
+        error.map_or(Ok(created), Err)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        // Ciphertexts of equal values differ, so the comparison happens on the
+        // decrypted value and the inner swap is conditioned on the exact
+        // ciphertext that was read.
+        let stored = self.inner.get(table_name, key)?;
+        let current = self
+            .cipher
+            .decrypt_optional(table_name, key, stored.clone())?;
+        if current.as_deref() != expected {
+            return Err(CompareAndSwapError::Mismatch { current });
+        }
+        let new = new
+            .map(|value| self.cipher.encrypt(table_name, key, value))
+            .transpose()?;
+        match self
+            .inner
+            .compare_and_swap(table_name, key, stored.as_deref(), new.as_deref())
+        {
+            Err(CompareAndSwapError::Mismatch { current }) => Err(CompareAndSwapError::Mismatch {
+                current: self.cipher.decrypt_optional(table_name, key, current)?,
+            }),
+            result => result,
+        }
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner.apply_batch(self.cipher.encrypt_batch(batch)?)
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`EncryptedDB`].
+#[cfg(feature = "async")]
+pub struct AsyncEncryptedDB<T> {
+    inner: T,
+    cipher: ValueCipher,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncEncryptedDB<T> {
+    pub fn new(inner: T, key: &[u8; 32]) -> Self {
+        Self {
+            inner,
+            cipher: ValueCipher::new(key),
+        }
+    }
+
+    pub fn with_key_provider(inner: T, provider: &dyn KeyProvider) -> Result<Self, io::Error> {
+        Ok(Self::new(inner, &provider.key()?))
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: fmt::Debug> fmt::Debug for AsyncEncryptedDB<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncEncryptedDB")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncEncryptedDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let value = self.cipher.encrypt(table_name, key, value)?;
+        self.cipher.decrypt_replaced(
+            table_name,
+            key,
+            self.inner.insert(table_name, key, &value).await?,
+        )
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.cipher
+            .decrypt_optional(table_name, key, self.inner.get(table_name, key).await?)
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.cipher
+            .decrypt_replaced(table_name, key, self.inner.remove(table_name, key).await?)
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher
+            .decrypt_entries(table_name, self.inner.iter(table_name).await?)
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names().await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher.decrypt_entries(
+            table_name,
+            self.inner.iter_from_prefix(table_name, prefix).await?,
+        )
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.cipher.decrypt_entries(
+            table_name,
+            self.inner
+                .iter_from_range(table_name, start_key, end_key)
+                .await?,
+        )
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let entries_a = self.inner.iter(table_a).await?;
+        let entries_b = self.inner.iter(table_b).await?;
+        self.inner
+            .apply_batch(
+                self.cipher
+                    .swap_batch(table_a, entries_a, table_b, entries_b)?,
+            )
+            .await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        let mut error = None;
+        let created = self
+            .inner
+            .ensure_table_with(table_name, &mut || {
+                self.cipher
+                    .encrypt_entries(table_name, init())
+                    .unwrap_or_else(|e| {
+                        error = Some(e);
+                        Vec::new()
+                    })
+            })
+            .await?;
+        error.map_or(Ok(created), Err)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let stored = self.inner.get(table_name, key).await?;
+        let current = self
+            .cipher
+            .decrypt_optional(table_name, key, stored.clone())?;
+        if current.as_deref() != expected {
+            return Err(CompareAndSwapError::Mismatch { current });
+        }
+        let new = new
+            .map(|value| self.cipher.encrypt(table_name, key, value))
+            .transpose()?;
+        match self
+            .inner
+            .compare_and_swap(table_name, key, stored.as_deref(), new.as_deref())
+            .await
+        {
+            Err(CompareAndSwapError::Mismatch { current }) => Err(CompareAndSwapError::Mismatch {
+                current: self.cipher.decrypt_optional(table_name, key, current)?,
+            }),
+            result => result,
+        }
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner
+            .apply_batch(self.cipher.encrypt_batch(batch)?)
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
+
+struct ValueCipher(XChaCha20Poly1305);
+
+impl ValueCipher {
+    fn new(key: &[u8; 32]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    fn encrypt(&self, table_name: &str, key: &str, value: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value,
+                    aad: &aad(table_name, key),
+                },
+            )
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "failed to encrypt value"))?;
+
+        let mut encrypted = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    fn decrypt(&self, table_name: &str, key: &str, value: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "failed to decrypt value");
+        if value.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let (nonce, ciphertext) = value.split_at(NONCE_LEN);
+        self.0
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad(table_name, key),
+                },
+            )
+            .map_err(|_| invalid())
+    }
+
+    fn encrypt_batch(&self, batch: WriteBatch) -> Result<WriteBatch, io::Error> {
+        batch
+            .into_iter()
+            .map(|op| match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => {
+                    let value = self.encrypt(&table_name, &key, &value)?;
+                    Ok(BatchOp::Insert {
+                        table_name,
+                        key,
+                        value,
+                    })
+                }
+                op => Ok(op),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(WriteBatch::from)
+    }
+
+    fn encrypt_entries(
+        &self,
+        table_name: &str,
+        entries: Vec<(String, Vec<u8>)>,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.encrypt(table_name, &key, &value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Empties both tables and fills each with the entries of the other,
+    /// encrypted again for the table they move to.
+    fn swap_batch(
+        &self,
+        table_a: &str,
+        entries_a: Vec<(String, Vec<u8>)>,
+        table_b: &str,
+        entries_b: Vec<(String, Vec<u8>)>,
+    ) -> Result<WriteBatch, io::Error> {
+        let mut batch = WriteBatch::new();
+        for (key, _) in &entries_a {
+            batch.remove(table_a, key);
+        }
+        for (key, _) in &entries_b {
+            batch.remove(table_b, key);
+        }
+        for (key, value) in self.decrypt_entries(table_b, entries_b)? {
+            batch.insert(table_a, &key, &self.encrypt(table_a, &key, &value)?);
+        }
+        for (key, value) in self.decrypt_entries(table_a, entries_a)? {
+            batch.insert(table_b, &key, &self.encrypt(table_b, &key, &value)?);
+        }
+        Ok(batch)
+    }
+
+    fn decrypt_optional(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        value
+            .map(|value| self.decrypt(table_name, key, &value))
+            .transpose()
+    }
+
+    /// Decrypts the value that a write replaced. The write went through, so
+    /// a failure is reported as [`UndecryptableOldValue`].
+    fn decrypt_replaced(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        match self.decrypt(table_name, key, &value) {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(UndecryptableOldValue { stored: value }.into()),
+        }
+    }
+
+    fn decrypt_entries(
+        &self,
+        table_name: &str,
+        entries: Vec<(String, Vec<u8>)>,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.decrypt(table_name, &key, &value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+/// Authenticates the table name and the key with the value, the table name
+/// prefixed by its length so that no two pairs give the same bytes.
+fn aad(table_name: &str, key: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(8 + table_name.len() + key.len());
+    aad.extend_from_slice(&(table_name.len() as u64).to_le_bytes());
+    aad.extend_from_slice(table_name.as_bytes());
+    aad.extend_from_slice(key.as_bytes());
+    aad
+}
@@ -0,0 +1,185 @@
+use core::fmt;
+
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[derive(Debug)]
+pub enum CompareAndSwapError {
+    /// The stored value did not match the expected one. Holds the value
+    /// found in the database.
+    Mismatch {
+        current: Option<Vec<u8>>,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for CompareAndSwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareAndSwapError::Mismatch { .. } => {
+                write!(f, "current value does not match the expected value")
+            }
+            CompareAndSwapError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompareAndSwapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompareAndSwapError::Mismatch { .. } => None,
+            CompareAndSwapError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for CompareAndSwapError {
+    fn from(e: io::Error) -> Self {
+        CompareAndSwapError::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CompareAndSwapError> for io::Error {
+    fn from(e: CompareAndSwapError) -> Self {
+        match e {
+            CompareAndSwapError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
+
+/// An operation that a backend cannot perform. Converts into an
+/// `io::Error` of kind `Unsupported`, from which it can be recovered with
+/// [`Unsupported::from_io_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsupported {
+    pub backend: &'static str,
+    pub operation: &'static str,
+}
+
+impl Unsupported {
+    /// Returns the `Unsupported` error wrapped by `e`, if any.
+    #[cfg(feature = "std")]
+    pub fn from_io_error(e: &io::Error) -> Option<&Unsupported> {
+        e.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not supported by {}", self.operation, self.backend)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Unsupported {}
+
+#[cfg(feature = "std")]
+impl From<Unsupported> for io::Error {
+    fn from(e: Unsupported) -> Self {
+        io::Error::new(io::ErrorKind::Unsupported, e)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+const UNSUPPORTED_MESSAGE: &str = "operation not supported by this backend";
+
+#[cfg(not(feature = "std"))]
+impl From<Unsupported> for io::Error {
+    fn from(_: Unsupported) -> Self {
+        io::Error::new(io::ErrorKind::Other, UNSUPPORTED_MESSAGE)
+    }
+}
+
+/// Whether `e` was converted from an [`Unsupported`].
+pub(crate) fn is_unsupported(e: &io::Error) -> bool {
+    #[cfg(feature = "std")]
+    {
+        e.kind() == io::ErrorKind::Unsupported
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        e.get_ref() == Some(&UNSUPPORTED_MESSAGE)
+    }
+}
+
+impl From<Unsupported> for CompareAndSwapError {
+    fn from(e: Unsupported) -> Self {
+        CompareAndSwapError::Io(e.into())
+    }
+}
+
+/// A write rejected by a [`ReadOnlyDB`](crate::readonly::ReadOnlyDB).
+/// Converts into an `io::Error` of kind `PermissionDenied`, from which it
+/// can be recovered with [`ReadOnly::from_io_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOnly {
+    pub operation: &'static str,
+}
+
+impl ReadOnly {
+    /// Returns the `ReadOnly` error wrapped by `e`, if any.
+    #[cfg(feature = "std")]
+    pub fn from_io_error(e: &io::Error) -> Option<&ReadOnly> {
+        e.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for ReadOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not allowed on a read-only database",
+            self.operation
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReadOnly {}
+
+#[cfg(feature = "std")]
+impl From<ReadOnly> for io::Error {
+    fn from(e: ReadOnly) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, e)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl From<ReadOnly> for io::Error {
+    fn from(_: ReadOnly) -> Self {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "operation not allowed on a read-only database",
+        )
+    }
+}
+
+impl From<ReadOnly> for CompareAndSwapError {
+    fn from(e: ReadOnly) -> Self {
+        CompareAndSwapError::Io(e.into())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unsupported_round_trip() {
+        let unsupported = Unsupported {
+            backend: "test",
+            operation: "compare_and_swap",
+        };
+        let e = io::Error::from(unsupported);
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(Unsupported::from_io_error(&e), Some(&unsupported));
+        assert_eq!(e.to_string(), "compare_and_swap is not supported by test");
+
+        let e = io::Error::new(io::ErrorKind::Unsupported, "other");
+        assert_eq!(Unsupported::from_io_error(&e), None);
+    }
+}
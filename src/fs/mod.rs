@@ -0,0 +1,449 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{
+    kvdb::clamp_range,
+    probe::{check_engine, Engine},
+    BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch,
+};
+
+/// Stores each table as a directory under `root` and each key as a file in
+/// its table directory.
+///
+/// Table names and keys are percent-encoded into file names, leaving ASCII
+/// letters, digits, `-`, `_` and non-leading `.` untouched. Names are
+/// therefore subject to the file system's length limit and, on
+/// case-insensitive file systems, keys differing only by case collide.
+///
+/// `swap_tables` moves the directories with three renames. A crash in
+/// between leaves the first table under a temporary name, and the swap is
+/// completed the next time the database is opened.
+#[derive(Debug)]
+pub struct FsDB {
+    root: PathBuf,
+    lock: RwLock<()>,
+}
+
+const SWAP_PREFIX: &str = ".swap-";
+
+impl FsDB {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        check_engine(path, Engine::Fs)?;
+        fs::create_dir_all(path)?;
+        finish_swaps(path)?;
+
+        Ok(Self {
+            root: path.to_path_buf(),
+            lock: RwLock::new(()),
+        })
+    }
+
+    /// Copies the database into a new directory at `path`, which must not
+    /// exist yet, while writes are blocked. Files are hard-linked where the
+    /// file system allows it: values are replaced by renaming a new file over
+    /// the old one and never modified in place, so the copies stay
+    /// independent.
+    pub fn fork(&self, path: &Path) -> io::Result<FsDB> {
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the fork destination already exists",
+            ));
+        }
+        {
+            let _guard = self.lock.read().unwrap();
+            link_or_copy_dir(&self.root, path)?;
+        }
+
+        FsDB::open(path)
+    }
+
+    fn table_path(&self, table_name: &str) -> PathBuf {
+        self.root.join(encode_name(table_name))
+    }
+
+    fn key_path(&self, table_name: &str, key: &str) -> PathBuf {
+        self.table_path(table_name).join(encode_name(key))
+    }
+
+    fn write_value(&self, table_name: &str, key: &str, value: &[u8]) -> io::Result<()> {
+        let table_path = self.table_path(table_name);
+        fs::create_dir_all(&table_path)?;
+        let encoded_key = encode_name(key);
+        // Files starting with a dot are never keys, so a crash can only leave
+        // an ignored temporary file behind.
+        let temp_path = table_path.join(format!(".tmp-{}", encoded_key));
+        fs::write(&temp_path, value)?;
+        fs::rename(&temp_path, table_path.join(encoded_key))
+    }
+}
+
+impl crate::meta::Backend for FsDB {
+    const NAME: &'static str = "fs";
+}
+
+impl KeyValueDB for FsDB {
+    fn insert(&self, table_name: &str, key: &str, value: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let _guard = self.lock.write().unwrap();
+        let old_value = read_optional(&self.key_path(table_name, key))?;
+        self.write_value(table_name, key, value)?;
+
+        Ok(old_value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let _guard = self.lock.read().unwrap();
+        read_optional(&self.key_path(table_name, key))
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let _guard = self.lock.read().unwrap();
+        let mut file = match fs::File::open(self.key_path(table_name, key)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let range = clamp_range(range, file.metadata()?.len());
+        file.seek(SeekFrom::Start(range.start as u64))?;
+        let mut value = vec![0; range.len()];
+        file.read_exact(&mut value)?;
+
+        Ok(Some(value))
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let _guard = self.lock.write().unwrap();
+        let path = self.key_path(table_name, key);
+        let old_value = read_optional(&path)?;
+        if old_value.is_some() {
+            fs::remove_file(&path)?;
+        }
+
+        Ok(old_value)
+    }
+
+    fn iter(&self, table_name: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let _guard = self.lock.read().unwrap();
+        let entries = match fs::read_dir(self.table_path(table_name)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut result = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(key) = decode_file_name(&entry.file_name())? else {
+                continue;
+            };
+            result.push((key, fs::read(entry.path())?));
+        }
+        Ok(result)
+    }
+
+    fn table_names(&self) -> io::Result<Vec<String>> {
+        let _guard = self.lock.read().unwrap();
+        let mut result = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(table_name) = decode_file_name(&entry.file_name())? {
+                result.push(table_name);
+            }
+        }
+        Ok(result)
+    }
+
+    fn create_table(&self, table_name: &str) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        fs::create_dir_all(self.table_path(table_name))
+    }
+
+    fn len(&self, table_name: &str) -> io::Result<u64> {
+        let _guard = self.lock.read().unwrap();
+        let entries = match fs::read_dir(self.table_path(table_name)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut len = 0;
+        for entry in entries {
+            if decode_file_name(&entry?.file_name())?.is_some() {
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+
+    fn delete_table(&self, table_name: &str) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        match fs::remove_dir_all(self.table_path(table_name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> io::Result<()> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let _guard = self.lock.write().unwrap();
+        let path_a = self.table_path(table_a);
+        let path_b = self.table_path(table_b);
+        let temp_path = self.root.join(swap_file_name(table_a, table_b));
+        rename_optional(&path_a, &temp_path)?;
+        rename_optional(&path_b, &path_a)?;
+        rename_optional(&temp_path, &path_b)
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> io::Result<bool> {
+        let _guard = self.lock.write().unwrap();
+        match fs::create_dir(self.table_path(table_name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        for (key, value) in init() {
+            self.write_value(table_name, &key, &value)?;
+        }
+        Ok(true)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let _guard = self.lock.write().unwrap();
+        let path = self.key_path(table_name, key);
+        let current = read_optional(&path)?;
+        if current.as_deref() != expected {
+            return Err(CompareAndSwapError::Mismatch { current });
+        }
+        match new {
+            Some(value) => self.write_value(table_name, key, value)?,
+            None if current.is_some() => fs::remove_file(&path)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        for op in batch {
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => self.write_value(&table_name, &key, &value)?,
+                BatchOp::Remove { table_name, key } => {
+                    match fs::remove_file(self.key_path(&table_name, &key)) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recreates `src` at `dst`, skipping the files that are neither tables nor
+/// keys.
+fn link_or_copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if decode_file_name(&entry.file_name())?.is_none() {
+            continue;
+        }
+        let dst = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_or_copy_dir(&entry.path(), &dst)?;
+        } else if fs::hard_link(entry.path(), &dst).is_err() {
+            fs::copy(entry.path(), &dst)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Names the directory holding the table `table_a` while it is swapped with
+/// `table_b`. `+` never occurs in encoded names, so it separates them.
+fn swap_file_name(table_a: &str, table_b: &str) -> String {
+    format!(
+        "{SWAP_PREFIX}{}+{}",
+        encode_name(table_a),
+        encode_name(table_b)
+    )
+}
+
+/// Completes the swaps that a crash interrupted, found by the directories
+/// named by [`swap_file_name`]. The first table was moved there, so the
+/// swap is only missing the move of the second table into the place of the
+/// first, if it did not happen yet, and of the moved table into the place
+/// of the second.
+fn finish_swaps(root: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some((table_a, table_b)) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(SWAP_PREFIX))
+            .and_then(|tables| tables.split_once('+'))
+        else {
+            continue;
+        };
+        let path_a = root.join(table_a);
+        let path_b = root.join(table_b);
+        if !path_a.exists() {
+            rename_optional(&path_b, &path_a)?;
+        }
+        fs::rename(entry.path(), &path_b)?;
+    }
+    Ok(())
+}
+
+fn rename_optional(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn encode_name(name: &str) -> String {
+    if name.is_empty() {
+        return "%".to_string();
+    }
+    let mut encoded = String::with_capacity(name.len());
+    for (i, byte) in name.bytes().enumerate() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(byte as char),
+            b'.' if i > 0 => encoded.push('.'),
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+/// Returns `None` for names that cannot belong to a table or key, such as
+/// temporary files.
+fn decode_file_name(name: &std::ffi::OsStr) -> io::Result<Option<String>> {
+    let Some(name) = name.to_str() else {
+        return Ok(None);
+    };
+    if name.starts_with('.') {
+        return Ok(None);
+    }
+    if name == "%" {
+        return Ok(Some(String::new()));
+    }
+
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid file name in database: {}", name),
+        )
+    };
+    let mut decoded = Vec::with_capacity(name.len());
+    let mut bytes = name.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [
+                bytes.next().ok_or_else(invalid)?,
+                bytes.next().ok_or_else(invalid)?,
+            ];
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).map(Some).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn name_encoding_round_trips() {
+        for name in ["", ".", "..", "a/b", "key.json", "é", "%41", ".hidden"] {
+            let encoded = encode_name(name);
+            assert!(!encoded.starts_with('.'));
+            assert!(!encoded.contains('/'));
+            assert_eq!(
+                decode_file_name(encoded.as_ref()).unwrap().as_deref(),
+                Some(name)
+            );
+        }
+    }
+
+    #[test]
+    fn interrupted_swaps_are_finished_on_open() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("db");
+        let db = FsDB::open(&root).unwrap();
+        db.insert("a", "key", b"a").unwrap();
+        db.insert("b", "key", b"b").unwrap();
+        db.insert("c", "key", b"c").unwrap();
+        drop(db);
+
+        // Interrupted after moving `a` away.
+        fs::rename(root.join("a"), root.join(swap_file_name("a", "b"))).unwrap();
+        // Interrupted after also moving `missing` into the place of `c`, which
+        // is a no-op.
+        fs::rename(root.join("c"), root.join(swap_file_name("c", "missing"))).unwrap();
+
+        let db = FsDB::open(&root).unwrap();
+        assert_eq!(db.get("a", "key").unwrap(), Some(b"b".to_vec()));
+        assert_eq!(db.get("b", "key").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.get("c", "key").unwrap(), None);
+        assert_eq!(db.get("missing", "key").unwrap(), Some(b"c".to_vec()));
+        let mut table_names = db.table_names().unwrap();
+        table_names.sort();
+        assert_eq!(table_names, vec!["a", "b", "missing"]);
+        assert!(!fs::read_dir(&root).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SWAP_PREFIX)));
+
+        // Interrupted after moving `b` into the place of `a` too.
+        fs::rename(root.join("a"), root.join(swap_file_name("a", "b"))).unwrap();
+        fs::rename(root.join("b"), root.join("a")).unwrap();
+        let db = FsDB::open(&root).unwrap();
+        assert_eq!(db.get("a", "key").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.get("b", "key").unwrap(), Some(b"b".to_vec()));
+    }
+}
@@ -1,22 +1,116 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io;
-use std::sync::RwLock;
+use std::mem;
+use std::ops::Bound;
+use std::sync::{Mutex, RwLock};
 
-use crate::KeyValueDB;
+pub use crate::lru::CacheLimits;
+use crate::lru::Lru;
+use crate::{BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
 
+type Table = BTreeMap<String, Vec<u8>>;
+
+/// Tables are kept in ordered maps, so listings are sorted by key and prefix
+/// and range scans only visit the matching entries.
 #[derive(Debug, Default)]
 pub struct InMemoryDB {
-    map: RwLock<HashMap<String, HashMap<String, Vec<u8>>>>,
+    map: RwLock<BTreeMap<String, Table>>,
+    /// Set by `with_limits`.
+    eviction: Option<(CacheLimits, Mutex<Lru>)>,
 }
 
 impl InMemoryDB {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a database that keeps at most `limits` entries and bytes of
+    /// keys and values, evicting the least recently used entries on writes.
+    /// Reads through `get` count as uses.
+    pub fn with_limits(limits: CacheLimits) -> Self {
         Self {
-            map: RwLock::new(HashMap::new()),
+            map: RwLock::default(),
+            eviction: Some((limits, Mutex::default())),
+        }
+    }
+
+    /// Returns an estimate of the heap memory held by the stored data, in
+    /// bytes: the allocated capacity of every table name, key and value plus
+    /// the size of their handles. Tree and eviction bookkeeping is not
+    /// included.
+    pub fn memory_usage(&self) -> usize {
+        let map = self.map.read().unwrap();
+        map.iter()
+            .map(|(table_name, table)| {
+                let entries = table
+                    .iter()
+                    .map(|(key, value)| {
+                        mem::size_of::<(String, Vec<u8>)>() + key.capacity() + value.capacity()
+                    })
+                    .sum::<usize>();
+                mem::size_of::<String>() + table_name.capacity() + entries
+            })
+            .sum()
+    }
+
+    /// Inserts an entry and evicts the ones that no longer fit the limits.
+    fn insert_entry(
+        &self,
+        map: &mut BTreeMap<String, Table>,
+        table_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        let size = key.len() + value.len();
+        let old_value = map
+            .entry(table_name.to_owned())
+            .or_default()
+            .insert(key.to_owned(), value);
+        if let Some((limits, lru)) = &self.eviction {
+            let evicted =
+                lru.lock()
+                    .unwrap()
+                    .insert((table_name.to_owned(), key.to_owned()), size, *limits);
+            for (table_name, key) in evicted {
+                if let Some(table) = map.get_mut(&table_name) {
+                    table.remove(&key);
+                }
+            }
+        }
+        old_value
+    }
+
+    fn remove_entry(
+        &self,
+        map: &mut BTreeMap<String, Table>,
+        table_name: &str,
+        key: &str,
+    ) -> Option<Vec<u8>> {
+        if let Some((_, lru)) = &self.eviction {
+            lru.lock()
+                .unwrap()
+                .remove(&(table_name.to_owned(), key.to_owned()));
+        }
+        map.get_mut(table_name).and_then(|table| table.remove(key))
+    }
+
+    fn forget_table(&self, table_name: &str) {
+        if let Some((_, lru)) = &self.eviction {
+            lru.lock().unwrap().remove_table(table_name);
         }
     }
 }
 
+fn collect<'a>(entries: impl Iterator<Item = (&'a String, &'a Vec<u8>)>) -> Vec<(String, Vec<u8>)> {
+    entries
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect()
+}
+
+impl crate::meta::Backend for InMemoryDB {
+    const NAME: &'static str = "in-memory";
+}
+
 impl KeyValueDB for InMemoryDB {
     fn insert(
         &self,
@@ -24,32 +118,29 @@ impl KeyValueDB for InMemoryDB {
         key: &str,
         value: &[u8],
     ) -> Result<Option<Vec<u8>>, io::Error> {
-        Ok(self
-            .map
-            .write()
-            .unwrap()
-            .entry(table_name.to_owned())
-            .or_default()
-            .insert(key.to_owned(), value.to_owned()))
+        let mut map = self.map.write().unwrap();
+        Ok(self.insert_entry(&mut map, table_name, key, value.to_owned()))
     }
 
     fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
-        Ok(self
+        let value = self
             .map
             .read()
             .unwrap()
             .get(table_name)
             .and_then(|map| map.get(key))
-            .cloned())
+            .cloned();
+        if let (Some(_), Some((_, lru))) = (&value, &self.eviction) {
+            lru.lock()
+                .unwrap()
+                .touch(&(table_name.to_owned(), key.to_owned()));
+        }
+        Ok(value)
     }
 
     fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
-        Ok(self
-            .map
-            .write()
-            .unwrap()
-            .get_mut(table_name)
-            .and_then(|map| map.remove(key)))
+        let mut map = self.map.write().unwrap();
+        Ok(self.remove_entry(&mut map, table_name, key))
     }
 
     fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
@@ -58,11 +149,7 @@ impl KeyValueDB for InMemoryDB {
             .read()
             .unwrap()
             .get(table_name)
-            .map(|map| {
-                map.iter()
-                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
-                    .collect()
-            })
+            .map(|map| collect(map.iter()))
             .unwrap_or_default())
     }
 
@@ -70,8 +157,19 @@ impl KeyValueDB for InMemoryDB {
         Ok(self.map.read().unwrap().keys().cloned().collect())
     }
 
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.map
+            .write()
+            .unwrap()
+            .entry(table_name.to_owned())
+            .or_default();
+        Ok(())
+    }
+
     fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
-        self.map.write().unwrap().remove(table_name);
+        let mut map = self.map.write().unwrap();
+        map.remove(table_name);
+        self.forget_table(table_name);
         Ok(())
     }
 
@@ -86,10 +184,30 @@ impl KeyValueDB for InMemoryDB {
             .unwrap()
             .get(table_name)
             .map(|map| {
-                map.iter()
-                    .filter(|(key, _)| key.starts_with(prefix))
-                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
-                    .collect()
+                collect(
+                    map.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                        .take_while(|(key, _)| key.starts_with(prefix)),
+                )
+            })
+            .unwrap_or_default())
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        if start_key >= end_key {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .map
+            .read()
+            .unwrap()
+            .get(table_name)
+            .map(|map| {
+                collect(map.range::<str, _>((Bound::Included(start_key), Bound::Excluded(end_key))))
             })
             .unwrap_or_default())
     }
@@ -124,8 +242,101 @@ impl KeyValueDB for InMemoryDB {
             .unwrap_or_default())
     }
 
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        Ok(self
+            .map
+            .read()
+            .unwrap()
+            .get(table_name)
+            .map(|map| map.len() as u64)
+            .unwrap_or_default())
+    }
+
     fn clear(&self) -> Result<(), io::Error> {
-        self.map.write().unwrap().clear();
+        let mut map = self.map.write().unwrap();
+        map.clear();
+        if let Some((_, lru)) = &self.eviction {
+            *lru.lock().unwrap() = Lru::default();
+        }
+        Ok(())
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        let mut map = self.map.write().unwrap();
+        let entries_a = map.remove(table_a);
+        let entries_b = map.remove(table_b);
+        self.forget_table(table_a);
+        self.forget_table(table_b);
+        // Swapped entries count as just written.
+        for (table_name, entries) in [(table_a, entries_b), (table_b, entries_a)] {
+            let Some(entries) = entries else {
+                continue;
+            };
+            map.insert(table_name.to_owned(), Table::new());
+            for (key, value) in entries {
+                self.insert_entry(&mut map, table_name, &key, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        let mut map = self.map.write().unwrap();
+        if map.contains_key(table_name) {
+            return Ok(false);
+        }
+        map.insert(table_name.to_owned(), Table::new());
+        for (key, value) in init() {
+            self.insert_entry(&mut map, table_name, &key, value);
+        }
+        Ok(true)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let mut map = self.map.write().unwrap();
+        let current = map.get(table_name).and_then(|map| map.get(key));
+        if current.map(|value| value.as_slice()) != expected {
+            return Err(CompareAndSwapError::Mismatch {
+                current: current.cloned(),
+            });
+        }
+        match new {
+            Some(value) => {
+                self.insert_entry(&mut map, table_name, key, value.to_owned());
+            }
+            None => {
+                self.remove_entry(&mut map, table_name, key);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        let mut map = self.map.write().unwrap();
+        for op in batch {
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => {
+                    self.insert_entry(&mut map, &table_name, &key, value);
+                }
+                BatchOp::Remove { table_name, key } => {
+                    self.remove_entry(&mut map, &table_name, &key);
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -0,0 +1,817 @@
+use alloc::{collections::BTreeMap, format};
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{encode_key, BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// The prefix of the tables holding the indexes. The index `name` of table
+/// `table` is stored in `__keyvalue_index::name::table`.
+pub const INDEX_TABLE_PREFIX: &str = "__keyvalue_index::";
+
+/// Returns the keys under which a value is found in an index. A value may
+/// have any number of them.
+pub type IndexExtractor = fn(&[u8]) -> Vec<String>;
+
+#[derive(Debug, Clone, Default)]
+struct Indexes(BTreeMap<String, Vec<(String, IndexExtractor)>>);
+
+type Fetched = BTreeMap<(String, String), Option<Vec<u8>>>;
+
+impl Indexes {
+    fn add(&mut self, table_name: &str, index_name: &str, extractor: IndexExtractor) {
+        assert!(
+            !index_name.contains("::"),
+            "index names cannot contain `::`"
+        );
+        let indexes = self.0.entry(table_name.into()).or_default();
+        indexes.retain(|(name, _)| name != index_name);
+        indexes.push((index_name.into(), extractor));
+    }
+
+    fn is_indexed(&self, table_name: &str) -> bool {
+        self.0.contains_key(table_name)
+    }
+
+    fn extractor(&self, table_name: &str, index_name: &str) -> Result<IndexExtractor, io::Error> {
+        self.0
+            .get(table_name)
+            .and_then(|indexes| indexes.iter().find(|(name, _)| name == index_name))
+            .map(|(_, extractor)| *extractor)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unknown index"))
+    }
+
+    fn index_tables(&self, table_name: &str) -> Vec<String> {
+        self.0.get(table_name).map_or_else(Vec::new, |indexes| {
+            indexes
+                .iter()
+                .map(|(index_name, _)| index_table(table_name, index_name))
+                .collect()
+        })
+    }
+
+    /// The index entries of `value`, as pairs of index table and entry key.
+    fn entries(&self, table_name: &str, key: &str, value: Option<&[u8]>) -> Vec<(String, String)> {
+        let (Some(indexes), Some(value)) = (self.0.get(table_name), value) else {
+            return Vec::new();
+        };
+        let mut entries = Vec::new();
+        for (index_name, extractor) in indexes {
+            let index_table = index_table(table_name, index_name);
+            for index_key in extractor(value) {
+                entries.push((index_table.clone(), entry_key(&index_key, key)));
+            }
+        }
+        entries
+    }
+
+    /// The index entries to remove once `key` goes from `old` to `new`.
+    fn stale(
+        &self,
+        table_name: &str,
+        key: &str,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Vec<(String, String)> {
+        let new_entries = self.entries(table_name, key, new);
+        self.entries(table_name, key, old)
+            .into_iter()
+            .filter(|entry| !new_entries.contains(entry))
+            .collect()
+    }
+
+    /// Adds to `batch` the index updates for `key` going from `old` to `new`.
+    fn update(
+        &self,
+        batch: &mut WriteBatch,
+        table_name: &str,
+        key: &str,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) {
+        for (index_table, entry_key) in self.stale(table_name, key, old, new) {
+            batch.remove(&index_table, &entry_key);
+        }
+        for (index_table, entry_key) in self.entries(table_name, key, new) {
+            batch.insert(&index_table, &entry_key, b"");
+        }
+    }
+
+    /// The keys of indexed tables written by `batch`, whose current values
+    /// are needed to update the indexes.
+    fn touched(&self, batch: &WriteBatch) -> Vec<(String, String)> {
+        let mut touched = Vec::new();
+        for op in batch.ops() {
+            let touched_key = (op.table_name().into(), op.key().into());
+            if self.is_indexed(op.table_name()) && !touched.contains(&touched_key) {
+                touched.push(touched_key);
+            }
+        }
+        touched
+    }
+
+    /// Extends `batch` with its index updates, given the values of the keys
+    /// returned by `touched` before the batch.
+    fn indexed_batch(&self, batch: WriteBatch, mut values: Fetched) -> WriteBatch {
+        let mut indexed = WriteBatch::new();
+        for op in batch {
+            let table_name = op.table_name().into();
+            let key = op.key().into();
+            let new = match &op {
+                BatchOp::Insert { value, .. } => Some(value.clone()),
+                BatchOp::Remove { .. } => None,
+            };
+            if let Some(old) = values.get_mut(&(table_name, key)) {
+                self.update(
+                    &mut indexed,
+                    op.table_name(),
+                    op.key(),
+                    old.as_deref(),
+                    new.as_deref(),
+                );
+                *old = new;
+            }
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => indexed.insert(&table_name, &key, &value),
+                BatchOp::Remove { table_name, key } => indexed.remove(&table_name, &key),
+            };
+        }
+        indexed
+    }
+
+    /// Adds to `batch` the writes turning `table_name` from `old` into `new`,
+    /// with their index updates.
+    fn replace(
+        &self,
+        batch: &mut WriteBatch,
+        table_name: &str,
+        old: &BTreeMap<String, Vec<u8>>,
+        new: &BTreeMap<String, Vec<u8>>,
+    ) {
+        for (key, value) in old {
+            if !new.contains_key(key) {
+                batch.remove(table_name, key);
+                self.update(batch, table_name, key, Some(value), None);
+            }
+        }
+        for (key, value) in new {
+            batch.insert(table_name, key, value);
+            self.update(
+                batch,
+                table_name,
+                key,
+                old.get(key).map(Vec::as_slice),
+                Some(value),
+            );
+        }
+    }
+
+    /// The batch filling the empty `table_name` with `entries`.
+    fn seed_batch(&self, table_name: &str, entries: Vec<(String, Vec<u8>)>) -> WriteBatch {
+        let mut batch = WriteBatch::new();
+        self.replace(
+            &mut batch,
+            table_name,
+            &BTreeMap::new(),
+            &entries.into_iter().collect(),
+        );
+        batch
+    }
+
+    /// The batch swapping the entries of `table_a` and `table_b`, given their
+    /// current ones.
+    fn swap_batch(
+        &self,
+        table_a: &str,
+        entries_a: Vec<(String, Vec<u8>)>,
+        table_b: &str,
+        entries_b: Vec<(String, Vec<u8>)>,
+    ) -> WriteBatch {
+        let entries_a = entries_a.into_iter().collect();
+        let entries_b = entries_b.into_iter().collect();
+        let mut batch = WriteBatch::new();
+        self.replace(&mut batch, table_a, &entries_a, &entries_b);
+        self.replace(&mut batch, table_b, &entries_b, &entries_a);
+        batch
+    }
+
+    fn prefix(index_key: &str) -> String {
+        format!("{}:", encode_key(index_key.as_bytes()))
+    }
+}
+
+fn index_table(table_name: &str, index_name: &str) -> String {
+    format!("{INDEX_TABLE_PREFIX}{index_name}::{table_name}")
+}
+
+/// Index entries are stored as `index_key:key`, with the index key
+/// hex-encoded so that it cannot contain the separator.
+fn entry_key(index_key: &str, key: &str) -> String {
+    format!("{}{}", Indexes::prefix(index_key), key)
+}
+
+fn without_index_tables(table_names: Vec<String>) -> Vec<String> {
+    table_names
+        .into_iter()
+        .filter(|table_name| !table_name.starts_with(INDEX_TABLE_PREFIX))
+        .collect()
+}
+
+/// Maintains secondary indexes over the values of the wrapped database, so
+/// entries can be looked up by something other than their key with
+/// `get_by_index`. Each index of a table is registered with an extractor
+/// computing the index keys of a value, and is kept in a table of its own.
+///
+/// Every write to an indexed table is applied together with its index
+/// updates in a single `apply_batch`, so it is atomic on the backends that
+/// apply batches atomically. Writes to the same key from several handles
+/// are not isolated from each other and may leave stale index entries;
+/// `get_by_index` checks entries against the current values, so stale ones
+/// are never returned. Writes made to the wrapped database directly are not
+/// indexed: call `rebuild_index` after registering an index on a table that
+/// already has entries.
+///
+/// `swap_tables` on an indexed table rewrites both tables and their indexes
+/// in a single `apply_batch` instead of swapping the tables. On an indexed
+/// table, `ensure_table_with` writes the seeded entries and their index
+/// entries in a single `apply_batch`, but like the default implementation it
+/// checks whether the table exists beforehand, so concurrent callers may
+/// both seed it.
+#[derive(Debug)]
+pub struct SecondaryIndexDB<T> {
+    inner: T,
+    indexes: Indexes,
+}
+
+impl<T> SecondaryIndexDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            indexes: Indexes::default(),
+        }
+    }
+
+    /// Registers the index `index_name` of `table_name`, replacing the one
+    /// with the same name.
+    ///
+    /// # Panics
+    ///
+    /// If `index_name` contains `::`.
+    pub fn with_index(
+        mut self,
+        table_name: &str,
+        index_name: &str,
+        extractor: IndexExtractor,
+    ) -> Self {
+        self.indexes.add(table_name, index_name, extractor);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: KeyValueDB> SecondaryIndexDB<T> {
+    /// Returns the entries of `table_name` that have `index_key` in the index
+    /// `index_name`, in the order of their keys.
+    #[allow(clippy::type_complexity)]
+    pub fn get_by_index(
+        &self,
+        table_name: &str,
+        index_name: &str,
+        index_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let extractor = self.indexes.extractor(table_name, index_name)?;
+        let prefix = Indexes::prefix(index_key);
+        let mut result = Vec::new();
+        for (entry_key, _) in self
+            .inner
+            .iter_from_prefix(&index_table(table_name, index_name), &prefix)?
+        {
+            let key = &entry_key[prefix.len()..];
+            if let Some(value) = self.inner.get(table_name, key)? {
+                if extractor(&value).iter().any(|k| k == index_key) {
+                    result.push((key.into(), value));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Recomputes the index `index_name` of `table_name` from its entries.
+    pub fn rebuild_index(&self, table_name: &str, index_name: &str) -> Result<(), io::Error> {
+        let extractor = self.indexes.extractor(table_name, index_name)?;
+        let index_table = index_table(table_name, index_name);
+        self.inner.delete_table(&index_table)?;
+        let mut batch = WriteBatch::new();
+        for (key, value) in self.inner.iter(table_name)? {
+            for index_key in extractor(&value) {
+                batch.insert(&index_table, &entry_key(&index_key, &key), b"");
+            }
+        }
+        self.inner.apply_batch(batch)
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for SecondaryIndexDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.insert(table_name, key, value);
+        }
+        let old_value = self.inner.get(table_name, key)?;
+        let mut batch = WriteBatch::new();
+        batch.insert(table_name, key, value);
+        self.indexes.update(
+            &mut batch,
+            table_name,
+            key,
+            old_value.as_deref(),
+            Some(value),
+        );
+        self.inner.apply_batch(batch)?;
+        Ok(old_value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.remove(table_name, key);
+        }
+        let old_value = self.inner.get(table_name, key)?;
+        if old_value.is_some() {
+            let mut batch = WriteBatch::new();
+            batch.remove(table_name, key);
+            self.indexes
+                .update(&mut batch, table_name, key, old_value.as_deref(), None);
+            self.inner.apply_batch(batch)?;
+        }
+        Ok(old_value)
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(without_index_tables(self.inner.table_names()?))
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name)?;
+        for index_table in self.indexes.index_tables(table_name) {
+            self.inner.delete_table(&index_table)?;
+        }
+        Ok(())
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_range(table_name, start_key, end_key)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name)
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear()
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if !self.indexes.is_indexed(table_a) && !self.indexes.is_indexed(table_b) {
+            return self.inner.swap_tables(table_a, table_b);
+        }
+        let entries_a = self.inner.iter(table_a)?;
+        let entries_b = self.inner.iter(table_b)?;
+        self.inner.apply_batch(
+            self.indexes
+                .swap_batch(table_a, entries_a, table_b, entries_b),
+        )
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.ensure_table_with(table_name, init);
+        }
+        if self
+            .inner
+            .table_names()?
+            .iter()
+            .any(|name| name == table_name)
+        {
+            return Ok(false);
+        }
+        self.inner.create_table(table_name)?;
+        self.inner
+            .apply_batch(self.indexes.seed_batch(table_name, init()))?;
+        Ok(true)
+    }
+
+    /// The new index entries are added before the swap and the old ones
+    /// removed after it, so a failure in between leaves stale entries only.
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.compare_and_swap(table_name, key, expected, new);
+        }
+        let mut added = WriteBatch::new();
+        self.indexes.update(&mut added, table_name, key, None, new);
+        self.inner.apply_batch(added)?;
+        self.inner
+            .compare_and_swap(table_name, key, expected, new)?;
+        let mut removed = WriteBatch::new();
+        for (index_table, entry_key) in self.indexes.stale(table_name, key, expected, new) {
+            removed.remove(&index_table, &entry_key);
+        }
+        self.inner.apply_batch(removed)?;
+        Ok(())
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        let mut values = Fetched::new();
+        for (table_name, key) in self.indexes.touched(&batch) {
+            let value = self.inner.get(&table_name, &key)?;
+            values.insert((table_name, key), value);
+        }
+        self.inner
+            .apply_batch(self.indexes.indexed_batch(batch, values))
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`SecondaryIndexDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncSecondaryIndexDB<T> {
+    inner: T,
+    indexes: Indexes,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncSecondaryIndexDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            indexes: Indexes::default(),
+        }
+    }
+
+    /// Registers the index `index_name` of `table_name`, replacing the one
+    /// with the same name.
+    ///
+    /// # Panics
+    ///
+    /// If `index_name` contains `::`.
+    pub fn with_index(
+        mut self,
+        table_name: &str,
+        index_name: &str,
+        extractor: IndexExtractor,
+    ) -> Self {
+        self.indexes.add(table_name, index_name, extractor);
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: AsyncKeyValueDB> AsyncSecondaryIndexDB<T> {
+    #[allow(clippy::type_complexity)]
+    pub async fn get_by_index(
+        &self,
+        table_name: &str,
+        index_name: &str,
+        index_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let extractor = self.indexes.extractor(table_name, index_name)?;
+        let prefix = Indexes::prefix(index_key);
+        let mut result = Vec::new();
+        for (entry_key, _) in self
+            .inner
+            .iter_from_prefix(&index_table(table_name, index_name), &prefix)
+            .await?
+        {
+            let key = &entry_key[prefix.len()..];
+            if let Some(value) = self.inner.get(table_name, key).await? {
+                if extractor(&value).iter().any(|k| k == index_key) {
+                    result.push((key.into(), value));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub async fn rebuild_index(&self, table_name: &str, index_name: &str) -> Result<(), io::Error> {
+        let extractor = self.indexes.extractor(table_name, index_name)?;
+        let index_table = index_table(table_name, index_name);
+        self.inner.delete_table(&index_table).await?;
+        let mut batch = WriteBatch::new();
+        for (key, value) in self.inner.iter(table_name).await? {
+            for index_key in extractor(&value) {
+                batch.insert(&index_table, &entry_key(&index_key, &key), b"");
+            }
+        }
+        self.inner.apply_batch(batch).await
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncSecondaryIndexDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.insert(table_name, key, value).await;
+        }
+        let old_value = self.inner.get(table_name, key).await?;
+        let mut batch = WriteBatch::new();
+        batch.insert(table_name, key, value);
+        self.indexes.update(
+            &mut batch,
+            table_name,
+            key,
+            old_value.as_deref(),
+            Some(value),
+        );
+        self.inner.apply_batch(batch).await?;
+        Ok(old_value)
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key).await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.remove(table_name, key).await;
+        }
+        let old_value = self.inner.get(table_name, key).await?;
+        if old_value.is_some() {
+            let mut batch = WriteBatch::new();
+            batch.remove(table_name, key);
+            self.indexes
+                .update(&mut batch, table_name, key, old_value.as_deref(), None);
+            self.inner.apply_batch(batch).await?;
+        }
+        Ok(old_value)
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(without_index_tables(self.inner.table_names().await?))
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name).await?;
+        for index_table in self.indexes.index_tables(table_name) {
+            self.inner.delete_table(&index_table).await?;
+        }
+        Ok(())
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix).await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if !self.indexes.is_indexed(table_a) && !self.indexes.is_indexed(table_b) {
+            return self.inner.swap_tables(table_a, table_b).await;
+        }
+        let entries_a = self.inner.iter(table_a).await?;
+        let entries_b = self.inner.iter(table_b).await?;
+        self.inner
+            .apply_batch(
+                self.indexes
+                    .swap_batch(table_a, entries_a, table_b, entries_b),
+            )
+            .await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        if !self.indexes.is_indexed(table_name) {
+            return self.inner.ensure_table_with(table_name, init).await;
+        }
+        if self
+            .inner
+            .table_names()
+            .await?
+            .iter()
+            .any(|name| name == table_name)
+        {
+            return Ok(false);
+        }
+        self.inner.create_table(table_name).await?;
+        self.inner
+            .apply_batch(self.indexes.seed_batch(table_name, init()))
+            .await?;
+        Ok(true)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        if !self.indexes.is_indexed(table_name) {
+            return self
+                .inner
+                .compare_and_swap(table_name, key, expected, new)
+                .await;
+        }
+        let mut added = WriteBatch::new();
+        self.indexes.update(&mut added, table_name, key, None, new);
+        self.inner.apply_batch(added).await?;
+        self.inner
+            .compare_and_swap(table_name, key, expected, new)
+            .await?;
+        let mut removed = WriteBatch::new();
+        for (index_table, entry_key) in self.indexes.stale(table_name, key, expected, new) {
+            removed.remove(&index_table, &entry_key);
+        }
+        self.inner.apply_batch(removed).await?;
+        Ok(())
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range).await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        let mut values = Fetched::new();
+        for (table_name, key) in self.indexes.touched(&batch) {
+            let value = self.inner.get(&table_name, &key).await?;
+            values.insert((table_name, key), value);
+        }
+        self.inner
+            .apply_batch(self.indexes.indexed_batch(batch, values))
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
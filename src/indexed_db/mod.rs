@@ -1,17 +1,29 @@
-use std::{io, sync::atomic::AtomicU32};
+use std::{
+    collections::HashSet,
+    io,
+    ops::Bound,
+    sync::{atomic::AtomicU32, Mutex as StdMutex},
+};
 
 use async_trait::async_trait;
-use futures::lock::Mutex;
+use futures::lock::{Mutex, MutexGuard};
 use indexed_db::{Database, Factory};
-use js_sys::{wasm_bindgen::JsValue, Uint8Array};
+use js_sys::{wasm_bindgen::JsValue, JsString, Uint8Array};
 
-use crate::AsyncKeyValueDB;
+use crate::{AsyncKeyValueDB, CompareAndSwapError};
 
+/// A database stored in IndexedDB, with an object store per table.
+///
+/// Batches are applied one transaction per operation, so `apply_batch` and
+/// the `swap_tables` built on it are not atomic: readers may see them
+/// partially applied, and a failed operation leaves them so.
 #[derive(Debug)]
 pub struct IndexedDB {
     name: String,
     version: AtomicU32,
     inner: Mutex<Database<()>>,
+    /// Object stores waiting to be created by the next upgrade.
+    pending_stores: StdMutex<HashSet<String>>,
 }
 
 // Safety: It is safe to implement Send and Sync for IndexedDB because
@@ -31,8 +43,141 @@ impl IndexedDB {
             name: db_name.to_string(),
             version: AtomicU32::new(db.version()),
             inner: Mutex::new(db),
+            pending_stores: StdMutex::new(HashSet::new()),
         })
     }
+
+    /// Opens the database and creates the object stores of `table_names`
+    /// that do not exist yet, in a single upgrade. Writing to these tables
+    /// then never has to reopen the database.
+    pub async fn open_with_tables(db_name: &str, table_names: &[&str]) -> io::Result<Self> {
+        let db = Self::open(db_name).await?;
+        {
+            let mut inner = db.inner.lock().await;
+            let existing = inner.object_store_names();
+            let missing = table_names
+                .iter()
+                .filter(|table_name| !existing.iter().any(|n| n == *table_name))
+                .map(|table_name| table_name.to_string())
+                .collect::<HashSet<_>>();
+            if !missing.is_empty() {
+                db.create_object_stores(&mut inner, missing).await?;
+            }
+        }
+
+        Ok(db)
+    }
+
+    /// Locks the database after making sure the object store of
+    /// `table_name` exists. Stores requested while another caller holds the
+    /// lock are queued and created together by the next upgrade, so
+    /// concurrent writes to new tables share a single version bump.
+    async fn lock_with_store(&self, table_name: &str) -> io::Result<MutexGuard<'_, Database<()>>> {
+        self.pending_stores
+            .lock()
+            .unwrap()
+            .insert(table_name.to_string());
+
+        let mut db = self.inner.lock().await;
+
+        let existing = db.object_store_names();
+        let mut missing = self
+            .pending_stores
+            .lock()
+            .unwrap()
+            .drain()
+            .collect::<HashSet<_>>();
+        // A failed upgrade of another caller may have dropped our request.
+        missing.insert(table_name.to_string());
+        missing.retain(|table_name| !existing.contains(table_name));
+        if !missing.is_empty() {
+            self.create_object_stores(&mut db, missing).await?;
+        }
+
+        Ok(db)
+    }
+
+    /// Reads the entries of `table_name` whose key is in `range`, or all of
+    /// them, with one request for the keys and one for the values. Both come
+    /// back in key order from the same transaction, so they can be zipped.
+    async fn get_entries(
+        &self,
+        table_name: &str,
+        range: Option<(Bound<JsValue>, Bound<JsValue>)>,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let db = self.inner.lock().await;
+
+        let table_name = table_name.to_string();
+        let entries = match db
+            .transaction(&[&table_name])
+            .run(move |tx| async move {
+                let table = tx.object_store(&table_name)?;
+                let (keys, values) = match range {
+                    Some(range) => (
+                        table.get_all_keys_in(range.clone(), None).await?,
+                        table.get_all_in(range, None).await?,
+                    ),
+                    None => (table.get_all_keys(None).await?, table.get_all(None).await?),
+                };
+                let entries = keys
+                    .into_iter()
+                    .zip(values)
+                    .map(|(key, value)| {
+                        (
+                            key.as_string().unwrap_or_default(),
+                            Uint8Array::from(value).to_vec(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok::<_, indexed_db::Error<()>>(entries)
+            })
+            .await
+            .map_err(indexed_db_error_to_io_error)
+        {
+            Ok(entries) => entries,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    return Ok(Vec::new());
+                } else {
+                    return Err(e);
+                }
+            }
+        };
+
+        Ok(entries)
+    }
+
+    async fn create_object_stores(
+        &self,
+        db: &mut Database<()>,
+        table_names: HashSet<String>,
+    ) -> io::Result<()> {
+        db.close();
+
+        let new_version = self
+            .version
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+            + 1;
+
+        *db = Factory::get()
+            .map_err(indexed_db_error_to_io_error)?
+            .open(&self.name, new_version, move |evt| async move {
+                let db = evt.database();
+                for table_name in &table_names {
+                    db.build_object_store(table_name).create()?;
+                }
+                Ok(())
+            })
+            .await
+            .map_err(indexed_db_error_to_io_error)?;
+
+        Ok(())
+    }
+}
+
+impl crate::meta::Backend for IndexedDB {
+    const NAME: &'static str = "indexed-db";
 }
 
 #[async_trait(?Send)]
@@ -45,27 +190,7 @@ impl AsyncKeyValueDB for IndexedDB {
     ) -> Result<Option<Vec<u8>>, io::Error> {
         let old_value = self.get(table_name, key).await?;
 
-        let mut db = self.inner.lock().await;
-
-        if !db.object_store_names().into_iter().any(|n| n == table_name) {
-            db.close();
-
-            let table_name_str = table_name.to_string();
-            let new_version = self
-                .version
-                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
-                + 1;
-
-            *db = Factory::get()
-                .map_err(indexed_db_error_to_io_error)?
-                .open(&self.name, new_version, move |evt| async move {
-                    let db = evt.database();
-                    db.build_object_store(&table_name_str).create()?;
-                    Ok(())
-                })
-                .await
-                .map_err(indexed_db_error_to_io_error)?;
-        }
+        let db = self.lock_with_store(table_name).await?;
 
         let table_name = table_name.to_string();
         let key = key.to_string();
@@ -118,26 +243,7 @@ impl AsyncKeyValueDB for IndexedDB {
 
     async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
         if let Some(old_value) = self.get(table_name, key).await? {
-            let mut db = self.inner.lock().await;
-
-            if !db.object_store_names().into_iter().any(|n| n == table_name) {
-                db.close();
-
-                let table_name_str = table_name.to_string();
-                let new_version = self
-                    .version
-                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
-                    + 1;
-                *db = Factory::get()
-                    .map_err(indexed_db_error_to_io_error)?
-                    .open(&self.name, new_version, move |evt| async move {
-                        let db = evt.database();
-                        db.build_object_store(&table_name_str).create()?;
-                        Ok(())
-                    })
-                    .await
-                    .map_err(indexed_db_error_to_io_error)?;
-            }
+            let db = self.lock_with_store(table_name).await?;
 
             let table_name = table_name.to_string();
             let key = key.to_string();
@@ -166,38 +272,31 @@ impl AsyncKeyValueDB for IndexedDB {
     }
 
     async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
-        let db = self.inner.lock().await;
-
-        let table_name = table_name.to_string();
-        let values = match db
-            .transaction(&[&table_name])
-            .run(move |tx| async move {
-                let table = tx.object_store(&table_name)?;
-                let mut key_values = Vec::new();
-                for key in table.get_all_keys(None).await? {
-                    if let Some(value) = table.get(&key).await? {
-                        let key = key.as_string().unwrap_or_default();
-                        let value = Uint8Array::from(value).to_vec();
-                        key_values.push((key, value));
-                    }
-                }
+        self.get_entries(table_name, None).await
+    }
 
-                Ok::<_, indexed_db::Error<()>>(key_values)
-            })
-            .await
-            .map_err(indexed_db_error_to_io_error)
-        {
-            Ok(values) => values,
-            Err(e) => {
-                if e.kind() == io::ErrorKind::NotFound {
-                    return Ok(Vec::new());
-                } else {
-                    return Err(e);
-                }
-            }
-        };
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.get_entries(table_name, prefix_range(prefix)).await
+    }
 
-        Ok(values)
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        if start_key >= end_key {
+            return Ok(Vec::new());
+        }
+        let range = (
+            Bound::Included(JsValue::from(start_key)),
+            Bound::Excluded(JsValue::from(end_key)),
+        );
+        self.get_entries(table_name, Some(range)).await
     }
 
     async fn table_names(&self) -> Result<Vec<String>, io::Error> {
@@ -205,6 +304,12 @@ impl AsyncKeyValueDB for IndexedDB {
         Ok(db.object_store_names())
     }
 
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.lock_with_store(table_name).await?;
+
+        Ok(())
+    }
+
     async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
         let mut db = self.inner.lock().await;
 
@@ -289,6 +394,34 @@ impl AsyncKeyValueDB for IndexedDB {
         Ok(keys)
     }
 
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        let db = self.inner.lock().await;
+
+        let table_name = table_name.to_string();
+        let len = match db
+            .transaction(&[&table_name])
+            .run(move |tx| async move {
+                let table = tx.object_store(&table_name)?;
+                let len = table.count().await?;
+
+                Ok::<_, indexed_db::Error<()>>(len)
+            })
+            .await
+            .map_err(indexed_db_error_to_io_error)
+        {
+            Ok(len) => len,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    return Ok(0);
+                } else {
+                    return Err(e);
+                }
+            }
+        };
+
+        Ok(len as u64)
+    }
+
     async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
         let db = self.inner.lock().await;
 
@@ -320,6 +453,54 @@ impl AsyncKeyValueDB for IndexedDB {
         Ok(values)
     }
 
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let mut db = self.inner.lock().await;
+
+        if !db.object_store_names().into_iter().any(|n| n == table_name) {
+            if expected.is_some() {
+                return Err(CompareAndSwapError::Mismatch { current: None });
+            }
+            if new.is_none() {
+                return Ok(());
+            }
+            self.create_object_stores(&mut db, HashSet::from([table_name.to_string()]))
+                .await?;
+        }
+
+        let table_name = table_name.to_string();
+        let key = key.to_string();
+        let expected = expected.map(|v| v.to_vec());
+        let new = new.map(|v| v.to_vec());
+        db.transaction(&[&table_name])
+            .rw()
+            .run(move |tx| async move {
+                let table = tx.object_store(&table_name)?;
+                let key = JsValue::from(key);
+                let current = table.get(&key).await?.map(|v| Uint8Array::from(v).to_vec());
+                if current != expected {
+                    return Ok(Err(current));
+                }
+                match new {
+                    Some(value) => {
+                        table
+                            .put_kv(&key, &Uint8Array::from(value.as_ref()).into())
+                            .await?;
+                    }
+                    None => table.delete(&key).await?,
+                }
+                Ok::<_, indexed_db::Error<()>>(Ok(()))
+            })
+            .await
+            .map_err(indexed_db_error_to_io_error)?
+            .map_err(|current| CompareAndSwapError::Mismatch { current })
+    }
+
     async fn clear(&self) -> io::Result<()> {
         let mut db = self.inner.lock().await;
         db.close();
@@ -343,6 +524,28 @@ impl AsyncKeyValueDB for IndexedDB {
     }
 }
 
+/// The key range holding exactly the keys that start with `prefix`.
+/// IndexedDB orders strings by UTF-16 code units, so the exclusive upper bound
+/// is the prefix with its last code unit below `0xFFFF` incremented.
+fn prefix_range(prefix: &str) -> Option<(Bound<JsValue>, Bound<JsValue>)> {
+    if prefix.is_empty() {
+        return None;
+    }
+    let mut end = prefix.encode_utf16().collect::<Vec<_>>();
+    while end.last() == Some(&u16::MAX) {
+        end.pop();
+    }
+    let start = Bound::Included(JsValue::from(prefix));
+    let Some(last) = end.last_mut() else {
+        return Some((start, Bound::Unbounded));
+    };
+    *last += 1;
+    Some((
+        start,
+        Bound::Excluded(JsString::from_char_code(&end).into()),
+    ))
+}
+
 fn indexed_db_error_to_io_error(e: indexed_db::Error<()>) -> io::Error {
     match e {
         indexed_db::Error::AlreadyExists => {
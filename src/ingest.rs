@@ -0,0 +1,77 @@
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use futures::{Stream, StreamExt};
+
+use crate::{AsyncKeyValueDB, WriteBatch};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop at the first failed write and return its error.
+    #[default]
+    FailFast,
+    /// Count failed writes and keep consuming the stream.
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestReport {
+    pub written: u64,
+    pub skipped: u64,
+}
+
+pub struct IngestOptions<'a> {
+    /// Number of items written together with `apply_batch`.
+    pub batch_size: usize,
+    /// With [`ErrorPolicy::Skip`], every item of a failed batch is skipped.
+    pub error_policy: ErrorPolicy,
+    /// Called after every written batch with the running totals.
+    pub progress: Option<&'a mut (dyn FnMut(&IngestReport) + Send)>,
+}
+
+impl Default for IngestOptions<'_> {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            error_policy: ErrorPolicy::default(),
+            progress: None,
+        }
+    }
+}
+
+/// Writes every item of `stream` into `table_name`, in batches of
+/// `options.batch_size` items. The next batch is only pulled once the
+/// previous one was written, so a fast source is throttled to the speed of
+/// the backend.
+pub async fn ingest<S>(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    stream: S,
+    mut options: IngestOptions<'_>,
+) -> Result<IngestReport, io::Error>
+where
+    S: Stream<Item = (String, Vec<u8>)>,
+{
+    let mut chunks = core::pin::pin!(stream.chunks(options.batch_size.max(1)));
+    let mut report = IngestReport::default();
+
+    while let Some(items) = chunks.next().await {
+        let mut batch = WriteBatch::new();
+        for (key, value) in &items {
+            batch.insert(table_name, key, value);
+        }
+        match db.apply_batch(batch).await {
+            Ok(()) => report.written += items.len() as u64,
+            Err(e) => match options.error_policy {
+                ErrorPolicy::FailFast => return Err(e),
+                ErrorPolicy::Skip => report.skipped += items.len() as u64,
+            },
+        }
+        if let Some(progress) = options.progress.as_mut() {
+            progress(&report);
+        }
+    }
+
+    Ok(report)
+}
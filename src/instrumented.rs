@@ -0,0 +1,537 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    ops::Range,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// Upper bounds of the latency histogram buckets. A last bucket counts the
+/// calls slower than all of them.
+pub const LATENCY_BUCKETS: [Duration; 6] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+/// What an `InstrumentedDB` recorded for one operation on one table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationStats {
+    /// Empty for the operations that do not target a single table:
+    /// `table_names`, `clear`, `apply_batch`, `flush` and `compact`.
+    /// `swap_tables` is recorded under its first table.
+    pub table_name: String,
+    /// The name of the `KeyValueDB` method.
+    pub operation: &'static str,
+    pub calls: u64,
+    /// Calls that returned an error, including compare-and-swap mismatches.
+    pub errors: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+    /// Calls per bucket of [`LATENCY_BUCKETS`], plus the slower ones.
+    pub latency_histogram: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+/// Counts the calls, errors and latency of every operation, per table, and
+/// reports them with `stats`.
+#[derive(Debug)]
+pub struct InstrumentedDB<T> {
+    inner: T,
+    recorder: Recorder,
+}
+
+impl<T> InstrumentedDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            recorder: Recorder::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Returns a snapshot of the recorded operations, sorted by table and
+    /// operation.
+    pub fn stats(&self) -> Vec<OperationStats> {
+        self.recorder.stats()
+    }
+
+    pub fn reset(&self) {
+        self.recorder.reset()
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for InstrumentedDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder.call(table_name, "insert", || {
+            self.inner.insert(table_name, key, value)
+        })
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .call(table_name, "get", || self.inner.get(table_name, key))
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .call(table_name, "remove", || self.inner.remove(table_name, key))
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder
+            .call(table_name, "iter", || self.inner.iter(table_name))
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.recorder
+            .call("", "table_names", || self.inner.table_names())
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.recorder.call(table_name, "create_table", || {
+            self.inner.create_table(table_name)
+        })
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.recorder.call(table_name, "delete_table", || {
+            self.inner.delete_table(table_name)
+        })
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder.call(table_name, "iter_from_prefix", || {
+            self.inner.iter_from_prefix(table_name, prefix)
+        })
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder.call(table_name, "iter_from_range", || {
+            self.inner.iter_from_range(table_name, start_key, end_key)
+        })
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.recorder.call(table_name, "contains_key", || {
+            self.inner.contains_key(table_name, key)
+        })
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.recorder
+            .call(table_name, "keys", || self.inner.keys(table_name))
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.recorder
+            .call(table_name, "values", || self.inner.values(table_name))
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.recorder
+            .call(table_name, "len", || self.inner.len(table_name))
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.recorder
+            .call(table_name, "is_empty", || self.inner.is_empty(table_name))
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.recorder.call("", "clear", || self.inner.clear())
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.recorder.call(table_a, "swap_tables", || {
+            self.inner.swap_tables(table_a, table_b)
+        })
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.recorder.call(table_name, "ensure_table_with", || {
+            self.inner.ensure_table_with(table_name, init)
+        })
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.recorder.call(table_name, "compare_and_swap", || {
+            self.inner.compare_and_swap(table_name, key, expected, new)
+        })
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.recorder.call(table_name, "checksum", || {
+            self.inner.checksum(table_name, key)
+        })
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder.call(table_name, "get_range", || {
+            self.inner.get_range(table_name, key, range)
+        })
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.recorder
+            .call("", "apply_batch", || self.inner.apply_batch(batch))
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.recorder.call("", "flush", || self.inner.flush())
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.recorder.call("", "compact", || self.inner.compact())
+    }
+}
+
+/// Async counterpart of [`InstrumentedDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncInstrumentedDB<T> {
+    inner: T,
+    recorder: Recorder,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncInstrumentedDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            recorder: Recorder::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Returns a snapshot of the recorded operations, sorted by table and
+    /// operation.
+    pub fn stats(&self) -> Vec<OperationStats> {
+        self.recorder.stats()
+    }
+
+    pub fn reset(&self) {
+        self.recorder.reset()
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncInstrumentedDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "insert",
+                self.inner.insert(table_name, key, value),
+            )
+            .await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .time(table_name, "get", self.inner.get(table_name, key))
+            .await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .time(table_name, "remove", self.inner.remove(table_name, key))
+            .await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder
+            .time(table_name, "iter", self.inner.iter(table_name))
+            .await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.recorder
+            .time("", "table_names", self.inner.table_names())
+            .await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "create_table",
+                self.inner.create_table(table_name),
+            )
+            .await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "delete_table",
+                self.inner.delete_table(table_name),
+            )
+            .await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "iter_from_prefix",
+                self.inner.iter_from_prefix(table_name, prefix),
+            )
+            .await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "iter_from_range",
+                self.inner.iter_from_range(table_name, start_key, end_key),
+            )
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "contains_key",
+                self.inner.contains_key(table_name, key),
+            )
+            .await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.recorder
+            .time(table_name, "keys", self.inner.keys(table_name))
+            .await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.recorder
+            .time(table_name, "values", self.inner.values(table_name))
+            .await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.recorder
+            .time(table_name, "len", self.inner.len(table_name))
+            .await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.recorder
+            .time(table_name, "is_empty", self.inner.is_empty(table_name))
+            .await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.recorder.time("", "clear", self.inner.clear()).await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.recorder
+            .time(
+                table_a,
+                "swap_tables",
+                self.inner.swap_tables(table_a, table_b),
+            )
+            .await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "ensure_table_with",
+                self.inner.ensure_table_with(table_name, init),
+            )
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.recorder
+            .time(
+                table_name,
+                "compare_and_swap",
+                self.inner.compare_and_swap(table_name, key, expected, new),
+            )
+            .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.recorder
+            .time(table_name, "checksum", self.inner.checksum(table_name, key))
+            .await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.recorder
+            .time(
+                table_name,
+                "get_range",
+                self.inner.get_range(table_name, key, range),
+            )
+            .await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.recorder
+            .time("", "apply_batch", self.inner.apply_batch(batch))
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.recorder.time("", "flush", self.inner.flush()).await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.recorder
+            .time("", "compact", self.inner.compact())
+            .await
+    }
+}
+
+#[derive(Debug, Default)]
+struct Recorder {
+    stats: Mutex<BTreeMap<(String, &'static str), OperationStats>>,
+}
+
+impl Recorder {
+    fn call<R, E>(
+        &self,
+        table_name: &str,
+        operation: &'static str,
+        f: impl FnOnce() -> Result<R, E>,
+    ) -> Result<R, E> {
+        let start = Instant::now();
+        let result = f();
+        self.record(table_name, operation, start.elapsed(), result.is_ok());
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn time<R, E>(
+        &self,
+        table_name: &str,
+        operation: &'static str,
+        future: impl core::future::Future<Output = Result<R, E>>,
+    ) -> Result<R, E> {
+        let start = Instant::now();
+        let result = future.await;
+        self.record(table_name, operation, start.elapsed(), result.is_ok());
+        result
+    }
+
+    fn record(&self, table_name: &str, operation: &'static str, latency: Duration, ok: bool) {
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats
+            .entry((table_name.to_owned(), operation))
+            .or_insert_with(|| OperationStats {
+                table_name: table_name.to_owned(),
+                operation,
+                ..Default::default()
+            });
+        entry.calls += 1;
+        if !ok {
+            entry.errors += 1;
+        }
+        entry.total_latency += latency;
+        entry.max_latency = entry.max_latency.max(latency);
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        entry.latency_histogram[bucket] += 1;
+    }
+
+    fn stats(&self) -> Vec<OperationStats> {
+        self.stats.lock().unwrap().values().cloned().collect()
+    }
+
+    fn reset(&self) {
+        self.stats.lock().unwrap().clear();
+    }
+}
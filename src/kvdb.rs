@@ -1,7 +1,31 @@
-use crate::io;
+use core::ops::Range;
+
+use crate::{
+    error::is_unsupported,
+    io,
+    meta::{seeding_key, META_TABLE},
+    BatchOp, CompareAndSwapError, Unsupported, WriteBatch,
+};
 #[cfg(not(feature = "std"))]
 use alloc::{string::String, vec::Vec};
 
+use xxhash_rust::xxh3::xxh3_64;
+
+/// A database of tables mapping string keys to byte values.
+///
+/// Handles are shared between threads. The single-key primitives `insert`,
+/// `get`, `remove` and `compare_and_swap` are atomic within one handle: a
+/// concurrent reader sees a value either before or after a write, never
+/// partially written, and calls on one table never affect another. The
+/// compound methods (`delete_table`, `clear`, `swap_tables`, `apply_batch`,
+/// `ensure_table_with`, ...) are only atomic where a backend overrides them
+/// to be; their default implementations are sequences of single-key calls
+/// that other calls may interleave with. Separate calls are not isolated
+/// from each other either, so updates that depend on a read should use
+/// `compare_and_swap`, and updates of several keys `apply_batch` on the
+/// backends that apply it atomically.
+/// `testsuite::concurrency`, under the `test` feature, checks these
+/// guarantees for a backend.
 pub trait KeyValueDB: Send + Sync {
     fn insert(
         &self,
@@ -15,6 +39,11 @@ pub trait KeyValueDB: Send + Sync {
     fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error>;
     fn table_names(&self) -> Result<Vec<String>, io::Error>;
 
+    /// Creates `table_name` if it does not exist yet. Backends whose tables
+    /// only exist through their keys have nothing to create.
+    fn create_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Ok(())
+    }
     fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
         for (key, _) in self.iter(table_name)? {
             self.remove(table_name, &key)?;
@@ -35,6 +64,21 @@ pub trait KeyValueDB: Send + Sync {
         }
         Ok(result)
     }
+    #[allow(clippy::type_complexity)]
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let mut result = Vec::new();
+        for (key, value) in self.iter(table_name)? {
+            if start_key <= key.as_str() && key.as_str() < end_key {
+                result.push((key, value));
+            }
+        }
+        Ok(result)
+    }
     fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
         Ok(self.get(table_name, key)?.is_some())
     }
@@ -52,12 +96,237 @@ pub trait KeyValueDB: Send + Sync {
         }
         Ok(values)
     }
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        Ok(self.keys(table_name)?.len() as u64)
+    }
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        Ok(self.len(table_name)? == 0)
+    }
     fn clear(&self) -> Result<(), io::Error> {
         for table_name in self.table_names()? {
             self.delete_table(&table_name)?;
         }
         Ok(())
     }
+    /// Swaps the entries of `table_a` and `table_b`.
+    ///
+    /// The default implementation reads both tables and writes the swapped
+    /// entries with a single `apply_batch`, then removes a table whose
+    /// counterpart did not exist. Nothing is removed before the new entries
+    /// are written, but the swap is only as atomic as the backend's
+    /// `apply_batch`: where that applies one operation at a time, readers may
+    /// see a mix of both tables and a failure may leave the swap partially
+    /// applied.
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let table_names = self.table_names()?;
+        let entries_a = self.iter(table_a)?;
+        let entries_b = self.iter(table_b)?;
+        self.apply_batch(WriteBatch::swap_tables(
+            table_a, &entries_a, table_b, &entries_b,
+        ))?;
+        for (table_name, other) in [(table_a, table_b), (table_b, table_a)] {
+            if !table_names.iter().any(|name| name == other) {
+                self.delete_table(table_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates `table_name` filled with the entries returned by `init`, unless
+    /// the table already exists. Returns whether the table was created.
+    ///
+    /// The default implementation claims the seeding with `compare_and_swap`
+    /// on a marker key in [`META_TABLE`](crate::meta::META_TABLE), so only one
+    /// of concurrent callers runs `init`, and removes the marker once the
+    /// table is created and seeded. A crash in between leaves the marker
+    /// behind, and later calls then report the table as existing. On backends
+    /// without `compare_and_swap` concurrent callers may both run `init`, and
+    /// on those whose tables only exist through their keys a table seeded
+    /// with no entries still does not exist afterwards. Backends override it
+    /// to make the check and the seeding atomic where they can.
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        if self.table_names()?.iter().any(|name| name == table_name) {
+            return Ok(false);
+        }
+        let marker = seeding_key(table_name);
+        let claimed = match self.compare_and_swap(META_TABLE, &marker, None, Some(&[])) {
+            Ok(()) => true,
+            Err(CompareAndSwapError::Mismatch { .. }) => return Ok(false),
+            Err(CompareAndSwapError::Io(e)) if is_unsupported(&e) => false,
+            Err(CompareAndSwapError::Io(e)) => return Err(e),
+        };
+        // Another caller may have seeded the table and released its claim
+        // since the first check.
+        let seeded = (|| {
+            if self.table_names()?.iter().any(|name| name == table_name) {
+                return Ok(false);
+            }
+            self.create_table(table_name)?;
+            for (key, value) in init() {
+                self.insert(table_name, &key, &value)?;
+            }
+            Ok(true)
+        })();
+        if claimed {
+            self.remove(META_TABLE, &marker)?;
+        }
+        seeded
+    }
+    /// Atomically replaces the value of `key` with `new` if its current value
+    /// is `expected`. `None` stands for a missing key on both sides.
+    fn compare_and_swap(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        Err(Unsupported {
+            backend: core::any::type_name::<Self>(),
+            operation: "compare_and_swap",
+        }
+        .into())
+    }
+    /// Returns the 64-bit XXH3 hash of the value of `key`, so entries can be
+    /// compared across databases without transferring their values.
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        Ok(self.get(table_name, key)?.map(|value| xxh3_64(&value)))
+    }
+    /// Returns the bytes of the value of `key` in `range`, which is clamped
+    /// to the length of the value. The file system and S3 backends only read
+    /// the requested bytes; by default the whole value is read.
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        Ok(self
+            .get(table_name, key)?
+            .map(|value| value[clamp_range(range, value.len() as u64)].to_vec()))
+    }
+    /// Applies the operations of `batch` in order. In-memory and redb apply
+    /// the whole batch atomically, the file system backend isolates it from
+    /// concurrent calls on the same `FsDB` but may leave it partially applied
+    /// on a crash, local storage rolls back a batch that fails midway but may
+    /// also leave it partially applied if the page is closed, and the
+    /// remaining backends apply it one operation at a time.
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        for op in batch {
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => {
+                    self.insert(&table_name, &key, &value)?;
+                }
+                BatchOp::Remove { table_name, key } => {
+                    self.remove(&table_name, &key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Persists the writes that the backend has not made durable yet.
+    /// Backends that write through do nothing.
+    fn flush(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+    /// Reclaims the space left behind by removed entries, where the backend
+    /// supports it.
+    fn compact(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Returns a handle bound to `table_name`.
+    fn table(&self, table_name: &str) -> TableHandle<'_, Self>
+    where
+        Self: Sized,
+    {
+        TableHandle::new(self, table_name)
+    }
+}
+
+/// A handle bound to a single table of a [`KeyValueDB`].
+///
+/// It only holds the table name and forwards to the database, so calls cost
+/// the same as the database's own methods.
+#[derive(Debug)]
+pub struct TableHandle<'a, D: ?Sized> {
+    db: &'a D,
+    name: String,
+}
+
+impl<'a, D: KeyValueDB + ?Sized> TableHandle<'a, D> {
+    pub fn new(db: &'a D, table_name: &str) -> Self {
+        Self {
+            db,
+            name: table_name.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn insert(&self, key: &str, value: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.insert(&self.name, key, value)
+    }
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.get(&self.name, key)
+    }
+    pub fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.db.remove(&self.name, key)
+    }
+    pub fn iter(&self) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db.iter(&self.name)
+    }
+    pub fn iter_from_prefix(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db.iter_from_prefix(&self.name, prefix)
+    }
+    pub fn iter_from_range(
+        &self,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.db.iter_from_range(&self.name, start_key, end_key)
+    }
+    pub fn contains_key(&self, key: &str) -> Result<bool, io::Error> {
+        self.db.contains_key(&self.name, key)
+    }
+    pub fn keys(&self) -> Result<Vec<String>, io::Error> {
+        self.db.keys(&self.name)
+    }
+    pub fn values(&self) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.db.values(&self.name)
+    }
+    pub fn len(&self) -> Result<u64, io::Error> {
+        self.db.len(&self.name)
+    }
+    pub fn is_empty(&self) -> Result<bool, io::Error> {
+        self.db.is_empty(&self.name)
+    }
+    pub fn create(&self) -> Result<(), io::Error> {
+        self.db.create_table(&self.name)
+    }
+    pub fn delete(&self) -> Result<(), io::Error> {
+        self.db.delete_table(&self.name)
+    }
+}
+
+/// Clamps `range` to a value of `len` bytes, as an index into it.
+pub(crate) fn clamp_range(range: Range<u64>, len: u64) -> Range<usize> {
+    let start = range.start.min(len);
+    let end = range.end.clamp(start, len);
+    start as usize..end as usize
 }
 
 #[cfg(test)]
@@ -9,11 +9,62 @@ use std::io;
 
 #[cfg(feature = "async")]
 mod async_kvdb;
+mod batch;
+mod bytes_kvdb;
+mod diff;
+mod error;
 mod kvdb;
+#[cfg(all(feature = "std", any(feature = "async", feature = "in-memory")))]
+mod lru;
+#[cfg(feature = "async")]
+mod native_async_kvdb;
+mod numeric;
 
 #[cfg(feature = "async")]
 pub use async_kvdb::*;
+pub use batch::*;
+pub use bytes_kvdb::*;
+pub use diff::*;
+pub use error::*;
 pub use kvdb::*;
+#[cfg(feature = "async")]
+pub use native_async_kvdb::*;
+pub use numeric::*;
+#[cfg(feature = "std")]
+pub use probe::probe;
+
+pub mod authorized;
+#[cfg(all(feature = "async", feature = "std"))]
+pub mod cache;
+#[cfg(feature = "clock")]
+pub mod clock;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+pub mod index;
+#[cfg(feature = "async")]
+pub mod ingest;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub mod instrumented;
+pub mod meta;
+#[cfg(feature = "async")]
+pub mod migrate;
+pub mod normalize;
+#[cfg(all(feature = "async", feature = "std"))]
+pub mod observable;
+pub mod outbox;
+#[cfg(feature = "std")]
+pub mod probe;
+pub mod readonly;
+pub mod scoped;
+pub mod sequence;
+#[cfg(feature = "std")]
+pub mod shadow;
+pub mod snapshot;
+pub mod stats;
+#[cfg(feature = "tracing")]
+pub mod traced;
+pub mod ulid;
+pub mod update;
 
 #[cfg(feature = "in-memory")]
 pub mod in_memory;
@@ -21,6 +72,9 @@ pub mod in_memory;
 #[cfg(feature = "redb")]
 pub mod redb;
 
+#[cfg(all(feature = "fs", not(target_arch = "wasm32")))]
+pub mod fs;
+
 #[cfg(feature = "aws-s3")]
 pub mod aws_s3;
 
@@ -29,3 +83,10 @@ pub mod local_storage;
 
 #[cfg(all(feature = "indexed-db", target_arch = "wasm32"))]
 pub mod indexed_db;
+
+#[cfg(feature = "test")]
+pub mod conformance;
+#[cfg(all(feature = "test", not(target_arch = "wasm32")))]
+pub mod crash;
+#[cfg(feature = "test")]
+pub mod testsuite;
@@ -2,8 +2,13 @@ use std::{collections::HashSet, io};
 
 use gloo_storage::{errors::StorageError, LocalStorage, Storage};
 
-use crate::KeyValueDB;
+use crate::{BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
 
+/// A database stored in the browser's local storage, with a key per entry.
+///
+/// `apply_batch`, and the `swap_tables` built on it, run without yielding and
+/// restore the previous values if a write fails, so readers never see them
+/// partially applied.
 #[derive(Debug)]
 pub struct LocalStorageDB {
     name: String,
@@ -17,6 +22,10 @@ impl LocalStorageDB {
     }
 }
 
+impl crate::meta::Backend for LocalStorageDB {
+    const NAME: &'static str = "local-storage";
+}
+
 impl KeyValueDB for LocalStorageDB {
     fn insert(&self, table_name: &str, key: &str, value: &[u8]) -> io::Result<Option<Vec<u8>>> {
         let old_value = self.get(table_name, key)?;
@@ -135,6 +144,80 @@ impl KeyValueDB for LocalStorageDB {
         Ok(())
     }
 
+    // Local storage is only accessed from the current thread, so nothing can
+    // interleave between the read and the write below.
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let current = self.get(table_name, key)?;
+        if current.as_deref() != expected {
+            return Err(CompareAndSwapError::Mismatch { current });
+        }
+        let full_key = format!("{}/{}/{}", self.name, table_name, key);
+        match new {
+            Some(value) => {
+                LocalStorage::set(full_key, value).map_err(storage_error_to_io_error)?;
+            }
+            None => LocalStorage::delete(full_key),
+        }
+        Ok(())
+    }
+
+    // Nothing else runs on this thread while the batch is applied, so readers
+    // see either none or all of it. If a write fails, e.g. because the quota
+    // is exceeded, the previous values are restored.
+    fn apply_batch(&self, batch: WriteBatch) -> io::Result<()> {
+        let local_storage = LocalStorage::raw();
+
+        let mut previous = Vec::new();
+        let mut result = Ok(());
+        for op in batch {
+            let (full_key, value) = match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => (format!("{}/{}/{}", self.name, table_name, key), Some(value)),
+                BatchOp::Remove { table_name, key } => {
+                    (format!("{}/{}/{}", self.name, table_name, key), None)
+                }
+            };
+            match local_storage.get_item(&full_key) {
+                Ok(old_value) => previous.push((full_key.clone(), old_value)),
+                Err(e) => {
+                    result = Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Failed to get value for key {}: {:?}", full_key, e),
+                    ));
+                    break;
+                }
+            }
+            match value {
+                Some(value) => {
+                    if let Err(e) = LocalStorage::set(full_key, value) {
+                        result = Err(storage_error_to_io_error(e));
+                        break;
+                    }
+                }
+                None => LocalStorage::delete(full_key),
+            }
+        }
+
+        if result.is_err() {
+            for (full_key, old_value) in previous.into_iter().rev() {
+                let _ = match old_value {
+                    Some(old_value) => local_storage.set_item(&full_key, &old_value),
+                    None => local_storage.remove_item(&full_key),
+                };
+            }
+        }
+        result
+    }
+
     fn clear(&self) -> io::Result<()> {
         LocalStorage::clear();
 
@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Bounds on the number and size of the entries kept by a cache or by a
+/// bounded `InMemoryDB`. `None` means unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheLimits {
+    pub max_entries: Option<usize>,
+    /// Total size of the kept keys and values.
+    pub max_bytes: Option<usize>,
+}
+
+pub(crate) type CacheKey = (String, String);
+
+#[derive(Debug, Default)]
+pub(crate) struct Lru {
+    /// Cached entries with their last use and size.
+    entries: HashMap<CacheKey, (u64, usize)>,
+    /// Cached entries by last use, oldest first.
+    order: BTreeMap<u64, CacheKey>,
+    bytes: usize,
+    clock: u64,
+    /// Reads that missed and may fill the entry once they get the value,
+    /// unless the entry is written meanwhile.
+    #[cfg(feature = "async")]
+    fills: HashMap<CacheKey, u64>,
+}
+
+impl Lru {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn touch(&mut self, cache_key: &CacheKey) {
+        let tick = self.tick();
+        if let Some((last_use, _)) = self.entries.get_mut(cache_key) {
+            let cache_key = self.order.remove(last_use).unwrap();
+            *last_use = tick;
+            self.order.insert(tick, cache_key);
+        }
+    }
+
+    /// Records `cache_key` as the most recently used entry and returns the
+    /// entries that must be evicted to stay within `limits`.
+    pub(crate) fn insert(
+        &mut self,
+        cache_key: CacheKey,
+        size: usize,
+        limits: CacheLimits,
+    ) -> Vec<CacheKey> {
+        self.remove(&cache_key);
+        let tick = self.tick();
+        self.entries.insert(cache_key.clone(), (tick, size));
+        self.order.insert(tick, cache_key);
+        self.bytes += size;
+
+        let mut evicted = Vec::new();
+        while limits
+            .max_entries
+            .is_some_and(|max| self.entries.len() > max)
+            || limits.max_bytes.is_some_and(|max| self.bytes > max)
+        {
+            let Some((_, cache_key)) = self.order.pop_first() else {
+                break;
+            };
+            let (_, size) = self.entries.remove(&cache_key).unwrap();
+            self.bytes -= size;
+            evicted.push(cache_key);
+        }
+        evicted
+    }
+
+    pub(crate) fn remove(&mut self, cache_key: &CacheKey) {
+        if let Some((last_use, size)) = self.entries.remove(cache_key) {
+            self.order.remove(&last_use);
+            self.bytes -= size;
+        }
+    }
+
+    /// Starts a read of `cache_key` that missed, to be completed with
+    /// `finish_fill` once the value is read.
+    #[cfg(feature = "async")]
+    pub(crate) fn begin_fill(&mut self, cache_key: CacheKey) -> u64 {
+        let fill = self.tick();
+        self.fills.insert(cache_key, fill);
+        fill
+    }
+
+    /// Returns whether the read `fill` of `cache_key` may fill the entry,
+    /// i.e. whether no write cancelled it since it began.
+    #[cfg(feature = "async")]
+    pub(crate) fn finish_fill(&mut self, cache_key: &CacheKey, fill: u64) -> bool {
+        if self.fills.get(cache_key) == Some(&fill) {
+            self.fills.remove(cache_key);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Prevents the reads of `cache_key` in progress from filling it with
+    /// the value they read, which a write is about to make stale.
+    #[cfg(feature = "async")]
+    pub(crate) fn cancel_fills(&mut self, cache_key: &CacheKey) {
+        self.fills.remove(cache_key);
+    }
+
+    pub(crate) fn remove_table(&mut self, table_name: &str) {
+        #[cfg(feature = "async")]
+        self.fills.retain(|(table, _), _| table != table_name);
+        let cache_keys = self
+            .entries
+            .keys()
+            .filter(|(table, _)| table == table_name)
+            .cloned()
+            .collect::<Vec<_>>();
+        for cache_key in cache_keys {
+            self.remove(&cache_key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cache_key(key: &str) -> CacheKey {
+        ("table".to_string(), key.to_string())
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let limits = CacheLimits {
+            max_entries: Some(2),
+            max_bytes: Some(10),
+        };
+        let mut lru = Lru::default();
+        assert!(lru.insert(cache_key("a"), 2, limits).is_empty());
+        assert!(lru.insert(cache_key("b"), 2, limits).is_empty());
+        lru.touch(&cache_key("a"));
+        assert_eq!(lru.insert(cache_key("c"), 2, limits), vec![cache_key("b")]);
+        assert_eq!(
+            lru.insert(cache_key("d"), 9, limits),
+            vec![cache_key("a"), cache_key("c")]
+        );
+        assert_eq!(lru.bytes, 9);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB};
+
+pub const META_TABLE: &str = "__keyvalue_meta";
+pub const LAYOUT_VERSION: u32 = 1;
+
+const INFO_KEY: &str = "info";
+/// The prefix of the keys marking a table that `ensure_table_with` is
+/// seeding.
+const SEEDING_KEY_PREFIX: &str = "seeding::";
+
+/// A storage backend, as opposed to a wrapper around one, named in the
+/// metadata it records.
+pub trait Backend {
+    const NAME: &'static str;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbInfo {
+    pub layout_version: u32,
+    pub crate_version: String,
+    pub backend: String,
+    /// When the metadata was first written, in milliseconds since the Unix
+    /// epoch, or 0 on targets without a clock.
+    pub created_at: u64,
+}
+
+impl DbInfo {
+    fn current<D: Backend>() -> Self {
+        Self {
+            layout_version: LAYOUT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            backend: D::NAME.to_owned(),
+            created_at: now_ms(),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut value = Vec::new();
+        value.extend_from_slice(&self.layout_version.to_le_bytes());
+        value.extend_from_slice(&self.created_at.to_le_bytes());
+        value.extend_from_slice(&(self.crate_version.len() as u32).to_le_bytes());
+        value.extend_from_slice(self.crate_version.as_bytes());
+        value.extend_from_slice(self.backend.as_bytes());
+        value
+    }
+
+    fn decode(value: Option<Vec<u8>>) -> Result<Option<Self>, io::Error> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid database metadata");
+        let (layout_version, rest) = value.split_at_checked(4).ok_or_else(invalid)?;
+        let (created_at, rest) = rest.split_at_checked(8).ok_or_else(invalid)?;
+        let (crate_version_len, rest) = rest.split_at_checked(4).ok_or_else(invalid)?;
+        let crate_version_len = u32::from_le_bytes(crate_version_len.try_into().unwrap());
+        let (crate_version, backend) = rest
+            .split_at_checked(crate_version_len as usize)
+            .ok_or_else(invalid)?;
+
+        Ok(Some(Self {
+            layout_version: u32::from_le_bytes(layout_version.try_into().unwrap()),
+            crate_version: String::from_utf8(crate_version.to_vec()).map_err(|_| invalid())?,
+            backend: String::from_utf8(backend.to_vec()).map_err(|_| invalid())?,
+            created_at: u64::from_le_bytes(created_at.try_into().unwrap()),
+        }))
+    }
+}
+
+pub fn db_info(db: &dyn KeyValueDB) -> Result<Option<DbInfo>, io::Error> {
+    DbInfo::decode(db.get(META_TABLE, INFO_KEY)?)
+}
+
+/// Returns the stored metadata, writing the current one first if the
+/// database has none yet. The record is a single value written with
+/// `compare_and_swap`, so concurrent callers all get the one that was stored
+/// first.
+pub fn init_db_info<D: KeyValueDB + Backend>(db: &D) -> Result<DbInfo, io::Error> {
+    let info = DbInfo::current::<D>();
+    let written = db.compare_and_swap(META_TABLE, INFO_KEY, None, Some(&info.encode()));
+    stored(info, written)
+}
+
+#[cfg(feature = "async")]
+pub async fn db_info_async(db: &dyn AsyncKeyValueDB) -> Result<Option<DbInfo>, io::Error> {
+    DbInfo::decode(db.get(META_TABLE, INFO_KEY).await?)
+}
+
+#[cfg(feature = "async")]
+pub async fn init_db_info_async<D: AsyncKeyValueDB + Backend>(db: &D) -> Result<DbInfo, io::Error> {
+    let info = DbInfo::current::<D>();
+    let written = db
+        .compare_and_swap(META_TABLE, INFO_KEY, None, Some(&info.encode()))
+        .await;
+    stored(info, written)
+}
+
+/// The metadata stored by the first writer, given the outcome of writing
+/// `info` if there was none.
+/// The key in [`META_TABLE`] claimed while `table_name` is being seeded.
+pub(crate) fn seeding_key(table_name: &str) -> String {
+    alloc::format!("{SEEDING_KEY_PREFIX}{table_name}")
+}
+
+fn stored(info: DbInfo, written: Result<(), CompareAndSwapError>) -> Result<DbInfo, io::Error> {
+    match written {
+        Ok(()) => Ok(info),
+        Err(CompareAndSwapError::Mismatch { current }) => DbInfo::decode(current)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "database metadata was removed")),
+        Err(CompareAndSwapError::Io(e)) => Err(e),
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(all(
+    target_arch = "wasm32",
+    any(feature = "local-storage", feature = "indexed-db")
+))]
+fn now_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+#[cfg(not(any(
+    all(feature = "std", not(target_arch = "wasm32")),
+    all(
+        target_arch = "wasm32",
+        any(feature = "local-storage", feature = "indexed-db")
+    )
+)))]
+fn now_ms() -> u64 {
+    0
+}
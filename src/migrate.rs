@@ -0,0 +1,87 @@
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use crate::{AsyncKeyValueDB, WriteBatch};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MigrateReport {
+    pub tables: u64,
+    pub entries: u64,
+}
+
+#[allow(clippy::type_complexity)]
+pub struct MigrateOptions<'a> {
+    /// Number of entries written together to the destination with
+    /// `apply_batch`.
+    pub batch_size: usize,
+    /// Only entries for which this returns `true`, given the table name and
+    /// the key, are copied.
+    pub filter: Option<&'a (dyn Fn(&str, &str) -> bool + Sync)>,
+    /// Called after every written batch with the running totals.
+    pub progress: Option<&'a mut (dyn FnMut(&MigrateReport) + Send)>,
+}
+
+impl Default for MigrateOptions<'_> {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            filter: None,
+            progress: None,
+        }
+    }
+}
+
+/// Copies every table of `src` into `dst`, overwriting existing keys.
+pub async fn copy_all(
+    src: &dyn AsyncKeyValueDB,
+    dst: &dyn AsyncKeyValueDB,
+    mut options: MigrateOptions<'_>,
+) -> Result<MigrateReport, io::Error> {
+    let mut report = MigrateReport::default();
+    for table_name in src.table_names().await? {
+        copy_entries(src, dst, &table_name, &mut options, &mut report).await?;
+    }
+    Ok(report)
+}
+
+pub async fn copy_table(
+    src: &dyn AsyncKeyValueDB,
+    dst: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    mut options: MigrateOptions<'_>,
+) -> Result<MigrateReport, io::Error> {
+    let mut report = MigrateReport::default();
+    copy_entries(src, dst, table_name, &mut options, &mut report).await?;
+    Ok(report)
+}
+
+async fn copy_entries(
+    src: &dyn AsyncKeyValueDB,
+    dst: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    options: &mut MigrateOptions<'_>,
+    report: &mut MigrateReport,
+) -> Result<(), io::Error> {
+    let entries = src
+        .iter(table_name)
+        .await?
+        .into_iter()
+        .filter(|(key, _)| options.filter.is_none_or(|filter| filter(table_name, key)))
+        .collect::<Vec<(String, Vec<u8>)>>();
+
+    for chunk in entries.chunks(options.batch_size.max(1)) {
+        let mut batch = WriteBatch::new();
+        for (key, value) in chunk {
+            batch.insert(table_name, key, value);
+        }
+        dst.apply_batch(batch).await?;
+        report.entries += chunk.len() as u64;
+        if let Some(progress) = options.progress.as_mut() {
+            progress(report);
+        }
+    }
+    report.tables += 1;
+
+    Ok(())
+}
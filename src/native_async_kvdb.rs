@@ -0,0 +1,463 @@
+use core::{future::Future, ops::Range};
+
+use crate::{io, BatchOp, CompareAndSwapError, Unsupported, WriteBatch};
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use async_trait::async_trait;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    kvdb::{clamp_range, KeyValueDB},
+    AsyncKeyValueDB,
+};
+
+/// [`AsyncKeyValueDB`] with native async methods, whose futures are returned
+/// by value instead of boxed, so calls do not allocate. Every
+/// [`KeyValueDB`] implements it.
+///
+/// It cannot be used as a trait object: wrap an implementor in
+/// [`BoxedAsyncDB`] to get a `dyn AsyncKeyValueDB`. The futures must be
+/// `Send`, so the wasm backends only implement [`AsyncKeyValueDB`].
+pub trait NativeAsyncKeyValueDB: Send + Sync {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, io::Error>> + Send;
+    fn get(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, io::Error>> + Send;
+    fn remove(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, io::Error>> + Send;
+    #[allow(clippy::type_complexity)]
+    fn iter(
+        &self,
+        table_name: &str,
+    ) -> impl Future<Output = Result<Vec<(String, Vec<u8>)>, io::Error>> + Send;
+    fn table_names(&self) -> impl Future<Output = Result<Vec<String>, io::Error>> + Send;
+
+    fn create_table(
+        &self,
+        _table_name: &str,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async { Ok(()) }
+    }
+    fn delete_table(&self, table_name: &str) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            for (key, _) in self.iter(table_name).await? {
+                self.remove(table_name, &key).await?;
+            }
+            Ok(())
+        }
+    }
+    #[allow(clippy::type_complexity)]
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> impl Future<Output = Result<Vec<(String, Vec<u8>)>, io::Error>> + Send {
+        async move {
+            let mut entries = self.iter(table_name).await?;
+            entries.retain(|(key, _)| key.starts_with(prefix));
+            Ok(entries)
+        }
+    }
+    #[allow(clippy::type_complexity)]
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> impl Future<Output = Result<Vec<(String, Vec<u8>)>, io::Error>> + Send {
+        async move {
+            let mut entries = self.iter(table_name).await?;
+            entries.retain(|(key, _)| start_key <= key.as_str() && key.as_str() < end_key);
+            Ok(entries)
+        }
+    }
+    fn contains_key(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<bool, io::Error>> + Send {
+        async move { Ok(self.get(table_name, key).await?.is_some()) }
+    }
+    fn keys(
+        &self,
+        table_name: &str,
+    ) -> impl Future<Output = Result<Vec<String>, io::Error>> + Send {
+        async move {
+            let entries = self.iter(table_name).await?;
+            Ok(entries.into_iter().map(|(key, _)| key).collect())
+        }
+    }
+    fn values(
+        &self,
+        table_name: &str,
+    ) -> impl Future<Output = Result<Vec<Vec<u8>>, io::Error>> + Send {
+        async move {
+            let entries = self.iter(table_name).await?;
+            Ok(entries.into_iter().map(|(_, value)| value).collect())
+        }
+    }
+    fn len(&self, table_name: &str) -> impl Future<Output = Result<u64, io::Error>> + Send {
+        async move { Ok(self.keys(table_name).await?.len() as u64) }
+    }
+    fn is_empty(&self, table_name: &str) -> impl Future<Output = Result<bool, io::Error>> + Send {
+        async move { Ok(self.len(table_name).await? == 0) }
+    }
+    fn clear(&self) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            for table_name in self.table_names().await? {
+                self.delete_table(&table_name).await?;
+            }
+            Ok(())
+        }
+    }
+    fn swap_tables(
+        &self,
+        table_a: &str,
+        table_b: &str,
+    ) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            if table_a == table_b {
+                return Ok(());
+            }
+            let entries_a = self.iter(table_a).await?;
+            let entries_b = self.iter(table_b).await?;
+            self.delete_table(table_a).await?;
+            self.delete_table(table_b).await?;
+            for (key, value) in entries_b {
+                self.insert(table_a, &key, &value).await?;
+            }
+            for (key, value) in entries_a {
+                self.insert(table_b, &key, &value).await?;
+            }
+            Ok(())
+        }
+    }
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> impl Future<Output = Result<bool, io::Error>> + Send {
+        async move {
+            if self
+                .table_names()
+                .await?
+                .iter()
+                .any(|name| name == table_name)
+            {
+                return Ok(false);
+            }
+            for (key, value) in init() {
+                self.insert(table_name, &key, &value).await?;
+            }
+            Ok(true)
+        }
+    }
+    fn compare_and_swap(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _new: Option<&[u8]>,
+    ) -> impl Future<Output = Result<(), CompareAndSwapError>> + Send {
+        async {
+            Err(Unsupported {
+                backend: core::any::type_name::<Self>(),
+                operation: "compare_and_swap",
+            }
+            .into())
+        }
+    }
+    fn checksum(
+        &self,
+        table_name: &str,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<u64>, io::Error>> + Send {
+        async move {
+            Ok(self
+                .get(table_name, key)
+                .await?
+                .map(|value| xxh3_64(&value)))
+        }
+    }
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, io::Error>> + Send {
+        async move {
+            Ok(self
+                .get(table_name, key)
+                .await?
+                .map(|value| value[clamp_range(range, value.len() as u64)].to_vec()))
+        }
+    }
+    fn apply_batch(&self, batch: WriteBatch) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async move {
+            for op in batch {
+                match op {
+                    BatchOp::Insert {
+                        table_name,
+                        key,
+                        value,
+                    } => {
+                        self.insert(&table_name, &key, &value).await?;
+                    }
+                    BatchOp::Remove { table_name, key } => {
+                        self.remove(&table_name, &key).await?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+    fn flush(&self) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async { Ok(()) }
+    }
+    fn compact(&self) -> impl Future<Output = Result<(), io::Error>> + Send {
+        async { Ok(()) }
+    }
+}
+
+impl<T: KeyValueDB + ?Sized> NativeAsyncKeyValueDB for T {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::insert(self, table_name, key, value)
+    }
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get(self, table_name, key)
+    }
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::remove(self, table_name, key)
+    }
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        KeyValueDB::iter(self, table_name)
+    }
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        KeyValueDB::table_names(self)
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        KeyValueDB::create_table(self, table_name)
+    }
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        KeyValueDB::delete_table(self, table_name)
+    }
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        KeyValueDB::iter_from_prefix(self, table_name, prefix)
+    }
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        KeyValueDB::iter_from_range(self, table_name, start_key, end_key)
+    }
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        KeyValueDB::contains_key(self, table_name, key)
+    }
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        KeyValueDB::keys(self, table_name)
+    }
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        KeyValueDB::values(self, table_name)
+    }
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        KeyValueDB::len(self, table_name)
+    }
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        KeyValueDB::is_empty(self, table_name)
+    }
+    async fn clear(&self) -> Result<(), io::Error> {
+        KeyValueDB::clear(self)
+    }
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        KeyValueDB::swap_tables(self, table_a, table_b)
+    }
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        KeyValueDB::ensure_table_with(self, table_name, init)
+    }
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        KeyValueDB::compare_and_swap(self, table_name, key, expected, new)
+    }
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        KeyValueDB::checksum(self, table_name, key)
+    }
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        KeyValueDB::get_range(self, table_name, key, range)
+    }
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        KeyValueDB::apply_batch(self, batch)
+    }
+    async fn flush(&self) -> Result<(), io::Error> {
+        KeyValueDB::flush(self)
+    }
+    async fn compact(&self) -> Result<(), io::Error> {
+        KeyValueDB::compact(self)
+    }
+}
+
+/// Exposes a [`NativeAsyncKeyValueDB`] as an [`AsyncKeyValueDB`], boxing its
+/// futures, so it can be used as a `dyn AsyncKeyValueDB` and with the async
+/// wrappers.
+#[derive(Debug)]
+pub struct BoxedAsyncDB<T> {
+    inner: T,
+}
+
+impl<T> BoxedAsyncDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: NativeAsyncKeyValueDB> AsyncKeyValueDB for BoxedAsyncDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.insert(table_name, key, value).await
+    }
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key).await
+    }
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.remove(table_name, key).await
+    }
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name).await
+    }
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names().await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name).await
+    }
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name).await
+    }
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix).await
+    }
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key).await
+    }
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name).await
+    }
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear().await
+    }
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.inner.swap_tables(table_a, table_b).await
+    }
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.inner.ensure_table_with(table_name, init).await
+    }
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.inner
+            .compare_and_swap(table_name, key, expected, new)
+            .await
+    }
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key).await
+    }
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range).await
+    }
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner.apply_batch(batch).await
+    }
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
@@ -0,0 +1,413 @@
+use alloc::borrow::Cow;
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// Maps every key to the form it is stored under. Keys with the same
+/// normalized form address the same entry.
+pub trait KeyNormalizer: Send + Sync {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str>;
+}
+
+impl<F: Fn(&str) -> String + Send + Sync> KeyNormalizer for F {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        Cow::Owned(self(key))
+    }
+}
+
+/// Case-insensitive keys, stored in lowercase.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lowercase;
+
+impl KeyNormalizer for Lowercase {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        if key.chars().any(char::is_uppercase) {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+}
+
+/// Normalizes keys, prefixes and range bounds before handing them to the
+/// wrapped database. Keys are returned in their normalized form; table names
+/// are left untouched.
+///
+/// Range bounds are normalized like keys, so `iter_from_range` is only
+/// meaningful if the normalizer preserves the order of keys.
+#[derive(Debug)]
+pub struct KeyNormalizingDB<T, N = Lowercase> {
+    inner: T,
+    normalizer: N,
+}
+
+impl<T, N: KeyNormalizer> KeyNormalizingDB<T, N> {
+    pub fn new(inner: T, normalizer: N) -> Self {
+        Self { inner, normalizer }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: KeyValueDB, N: KeyNormalizer> KeyValueDB for KeyNormalizingDB<T, N> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .insert(table_name, &self.normalizer.normalize(key), value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, &self.normalizer.normalize(key))
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .remove(table_name, &self.normalizer.normalize(key))
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names()
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name)
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_prefix(table_name, &self.normalizer.normalize(prefix))
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_range(
+            table_name,
+            &self.normalizer.normalize(start_key),
+            &self.normalizer.normalize(end_key),
+        )
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner
+            .contains_key(table_name, &self.normalizer.normalize(key))
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name)
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear()
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.inner.swap_tables(table_a, table_b)
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.inner.ensure_table_with(table_name, &mut || {
+            normalize_entries(&self.normalizer, init())
+        })
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.inner
+            .compare_and_swap(table_name, &self.normalizer.normalize(key), expected, new)
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner
+            .checksum(table_name, &self.normalizer.normalize(key))
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .get_range(table_name, &self.normalizer.normalize(key), range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner
+            .apply_batch(normalize_batch(&self.normalizer, batch))
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`KeyNormalizingDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncKeyNormalizingDB<T, N = Lowercase> {
+    inner: T,
+    normalizer: N,
+}
+
+#[cfg(feature = "async")]
+impl<T, N: KeyNormalizer> AsyncKeyNormalizingDB<T, N> {
+    pub fn new(inner: T, normalizer: N) -> Self {
+        Self { inner, normalizer }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB, N: KeyNormalizer> AsyncKeyValueDB for AsyncKeyNormalizingDB<T, N> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .insert(table_name, &self.normalizer.normalize(key), value)
+            .await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .get(table_name, &self.normalizer.normalize(key))
+            .await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .remove(table_name, &self.normalizer.normalize(key))
+            .await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names().await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(table_name).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_prefix(table_name, &self.normalizer.normalize(prefix))
+            .await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(
+                table_name,
+                &self.normalizer.normalize(start_key),
+                &self.normalizer.normalize(end_key),
+            )
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner
+            .contains_key(table_name, &self.normalizer.normalize(key))
+            .await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.inner.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.inner.swap_tables(table_a, table_b).await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.inner
+            .ensure_table_with(table_name, &mut || {
+                normalize_entries(&self.normalizer, init())
+            })
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.inner
+            .compare_and_swap(table_name, &self.normalizer.normalize(key), expected, new)
+            .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner
+            .checksum(table_name, &self.normalizer.normalize(key))
+            .await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .get_range(table_name, &self.normalizer.normalize(key), range)
+            .await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner
+            .apply_batch(normalize_batch(&self.normalizer, batch))
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
+
+fn normalize_batch(normalizer: &impl KeyNormalizer, batch: WriteBatch) -> WriteBatch {
+    batch
+        .into_iter()
+        .map(|op| match op {
+            BatchOp::Insert {
+                table_name,
+                key,
+                value,
+            } => BatchOp::Insert {
+                table_name,
+                key: normalizer.normalize(&key).into_owned(),
+                value,
+            },
+            BatchOp::Remove { table_name, key } => BatchOp::Remove {
+                table_name,
+                key: normalizer.normalize(&key).into_owned(),
+            },
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn normalize_entries(
+    normalizer: &impl KeyNormalizer,
+    entries: Vec<(String, Vec<u8>)>,
+) -> Vec<(String, Vec<u8>)> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (normalizer.normalize(&key).into_owned(), value))
+        .collect()
+}
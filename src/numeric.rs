@@ -0,0 +1,145 @@
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::{update::run_update_async, AsyncKeyValueDB};
+use crate::{
+    update::{run_update, update_error, DEFAULT_MAX_ATTEMPTS},
+    KeyValueDB,
+};
+
+fn decode(value: Option<&[u8]>) -> Result<Option<[u8; 8]>, io::Error> {
+    value
+        .map(|value| {
+            <[u8; 8]>::try_from(value).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "numeric value is not 8 bytes")
+            })
+        })
+        .transpose()
+}
+
+fn add(current: Option<&[u8]>, delta: u64) -> Result<u64, io::Error> {
+    decode(current)?
+        .map_or(0, u64::from_le_bytes)
+        .checked_add(delta)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "counter overflow"))
+}
+
+/// Typed access to numeric values, implemented for every [`KeyValueDB`].
+///
+/// Numbers are stored as 8 bytes in little-endian order: `i64` in two's
+/// complement and `f64` as its IEEE 754 bits. Sequences and
+/// [`increment_u64`](KeyValueDBNumeric::increment_u64) use the same encoding,
+/// so their values can be read with `get_u64`. Reading a value of another
+/// length fails with `InvalidData`.
+pub trait KeyValueDBNumeric {
+    fn get_u64(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error>;
+    fn put_u64(&self, table_name: &str, key: &str, value: u64) -> Result<(), io::Error>;
+    fn get_i64(&self, table_name: &str, key: &str) -> Result<Option<i64>, io::Error>;
+    fn put_i64(&self, table_name: &str, key: &str, value: i64) -> Result<(), io::Error>;
+    fn get_f64(&self, table_name: &str, key: &str) -> Result<Option<f64>, io::Error>;
+    fn put_f64(&self, table_name: &str, key: &str, value: f64) -> Result<(), io::Error>;
+    /// Atomically adds `delta` to the counter at `key`, starting from 0 if it
+    /// is missing, and returns the new value. Fails on overflow, and if the
+    /// counter changed during each of [`DEFAULT_MAX_ATTEMPTS`] attempts.
+    fn increment_u64(&self, table_name: &str, key: &str, delta: u64) -> Result<u64, io::Error>;
+}
+
+impl<T: KeyValueDB + ?Sized> KeyValueDBNumeric for T {
+    fn get_u64(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        Ok(decode(self.get(table_name, key)?.as_deref())?.map(u64::from_le_bytes))
+    }
+    fn put_u64(&self, table_name: &str, key: &str, value: u64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes())?;
+        Ok(())
+    }
+    fn get_i64(&self, table_name: &str, key: &str) -> Result<Option<i64>, io::Error> {
+        Ok(decode(self.get(table_name, key)?.as_deref())?.map(i64::from_le_bytes))
+    }
+    fn put_i64(&self, table_name: &str, key: &str, value: i64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes())?;
+        Ok(())
+    }
+    fn get_f64(&self, table_name: &str, key: &str) -> Result<Option<f64>, io::Error> {
+        Ok(decode(self.get(table_name, key)?.as_deref())?.map(f64::from_le_bytes))
+    }
+    fn put_f64(&self, table_name: &str, key: &str, value: f64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes())?;
+        Ok(())
+    }
+    fn increment_u64(&self, table_name: &str, key: &str, delta: u64) -> Result<u64, io::Error> {
+        let mut new = 0;
+        run_update(self, table_name, key, DEFAULT_MAX_ATTEMPTS, |current| {
+            new = add(current, delta)?;
+            Ok(Some(new.to_le_bytes().to_vec()))
+        })
+        .map_err(update_error)?;
+        Ok(new)
+    }
+}
+
+/// Async counterpart of [`KeyValueDBNumeric`], implemented for every
+/// [`AsyncKeyValueDB`].
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+pub trait AsyncKeyValueDBNumeric {
+    async fn get_u64(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error>;
+    async fn put_u64(&self, table_name: &str, key: &str, value: u64) -> Result<(), io::Error>;
+    async fn get_i64(&self, table_name: &str, key: &str) -> Result<Option<i64>, io::Error>;
+    async fn put_i64(&self, table_name: &str, key: &str, value: i64) -> Result<(), io::Error>;
+    async fn get_f64(&self, table_name: &str, key: &str) -> Result<Option<f64>, io::Error>;
+    async fn put_f64(&self, table_name: &str, key: &str, value: f64) -> Result<(), io::Error>;
+    async fn increment_u64(
+        &self,
+        table_name: &str,
+        key: &str,
+        delta: u64,
+    ) -> Result<u64, io::Error>;
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB + ?Sized> AsyncKeyValueDBNumeric for T {
+    async fn get_u64(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        Ok(decode(self.get(table_name, key).await?.as_deref())?.map(u64::from_le_bytes))
+    }
+    async fn put_u64(&self, table_name: &str, key: &str, value: u64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes()).await?;
+        Ok(())
+    }
+    async fn get_i64(&self, table_name: &str, key: &str) -> Result<Option<i64>, io::Error> {
+        Ok(decode(self.get(table_name, key).await?.as_deref())?.map(i64::from_le_bytes))
+    }
+    async fn put_i64(&self, table_name: &str, key: &str, value: i64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes()).await?;
+        Ok(())
+    }
+    async fn get_f64(&self, table_name: &str, key: &str) -> Result<Option<f64>, io::Error> {
+        Ok(decode(self.get(table_name, key).await?.as_deref())?.map(f64::from_le_bytes))
+    }
+    async fn put_f64(&self, table_name: &str, key: &str, value: f64) -> Result<(), io::Error> {
+        self.insert(table_name, key, &value.to_le_bytes()).await?;
+        Ok(())
+    }
+    async fn increment_u64(
+        &self,
+        table_name: &str,
+        key: &str,
+        delta: u64,
+    ) -> Result<u64, io::Error> {
+        let mut new = 0;
+        run_update_async(self, table_name, key, DEFAULT_MAX_ATTEMPTS, |current| {
+            new = add(current, delta)?;
+            Ok(Some(new.to_le_bytes().to_vec()))
+        })
+        .await
+        .map_err(update_error)?;
+        Ok(new)
+    }
+}
@@ -0,0 +1,635 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    lock::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard},
+};
+
+use crate::{AsyncKeyValueDB, BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    Insert {
+        table_name: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    Remove {
+        table_name: String,
+        key: String,
+    },
+    DeleteTable {
+        table_name: String,
+    },
+}
+
+impl ChangeEvent {
+    pub fn table_name(&self) -> &str {
+        match self {
+            ChangeEvent::Insert { table_name, .. }
+            | ChangeEvent::Remove { table_name, .. }
+            | ChangeEvent::DeleteTable { table_name } => table_name,
+        }
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            ChangeEvent::Insert { key, .. } | ChangeEvent::Remove { key, .. } => Some(key),
+            ChangeEvent::DeleteTable { .. } => None,
+        }
+    }
+}
+
+/// Publishes a `ChangeEvent` to the matching watchers after every write that
+/// succeeded on the wrapped database. Writes made to the wrapped database
+/// directly are not observed.
+///
+/// Writes hold a lock on the tables they touch until their events are
+/// published, so the watchers of a table receive its events in the order the
+/// writes were applied. `clear` and `swap_tables` are reported as the
+/// deletion of every table they affect, followed by the insertion of the
+/// entries a swapped table now holds, and `ensure_table_with` as the
+/// insertion of the entries it seeded. Removes applied through `apply_batch`
+/// are reported even if the key was missing.
+#[derive(Debug)]
+pub struct ObservableDB<T> {
+    inner: T,
+    watchers: Watchers,
+    locks: TableLocks<Mutex<()>>,
+}
+
+impl<T> ObservableDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            watchers: Watchers::default(),
+            locks: TableLocks::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a stream of the changes made to the keys of `table_name`
+    /// starting with `prefix`. Deleting the table is reported to every
+    /// watcher of that table. The stream is unbounded, so events pile up
+    /// until they are consumed; dropping it unsubscribes.
+    pub fn watch(&self, table_name: &str, prefix: &str) -> UnboundedReceiver<ChangeEvent> {
+        self.watchers.watch(table_name, prefix)
+    }
+
+    /// Runs `write` while holding the locks of `table_names`.
+    fn locked<R>(&self, table_names: &[&str], write: impl FnOnce() -> R) -> R {
+        let locks = self.locks.get(table_names);
+        let _guards = locks
+            .iter()
+            .map(|lock| lock.lock().unwrap())
+            .collect::<Vec<_>>();
+        write()
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for ObservableDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.locked(&[table_name], || {
+            let old_value = self.inner.insert(table_name, key, value)?;
+            self.watchers.inserted(table_name, key, value);
+            Ok(old_value)
+        })
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.locked(&[table_name], || {
+            let old_value = self.inner.remove(table_name, key)?;
+            if old_value.is_some() {
+                self.watchers.removed(table_name, key);
+            }
+            Ok(old_value)
+        })
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names()
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.locked(&[table_name], || {
+            self.inner.delete_table(table_name)?;
+            self.watchers.table_deleted(table_name);
+            Ok(())
+        })
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_range(table_name, start_key, end_key)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name)
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.locked(&[table_name], || {
+            self.inner
+                .compare_and_swap(table_name, key, expected, new)?;
+            self.watchers.swapped(table_name, key, expected, new);
+            Ok(())
+        })
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.locked(&batch_tables(&batch), || {
+            self.inner.apply_batch(batch.clone())?;
+            self.watchers.batch_applied(&batch);
+            Ok(())
+        })
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        let table_names = self.inner.table_names()?;
+        let tables = table_names.iter().map(String::as_str).collect::<Vec<_>>();
+        self.locked(&tables, || {
+            self.inner.clear()?;
+            for table_name in &table_names {
+                self.watchers.table_deleted(table_name);
+            }
+            Ok(())
+        })
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.locked(&[table_a, table_b], || {
+            let entries_a = self.inner.iter(table_a)?;
+            let entries_b = self.inner.iter(table_b)?;
+            self.inner.swap_tables(table_a, table_b)?;
+            self.watchers.table_replaced(table_a, entries_b);
+            self.watchers.table_replaced(table_b, entries_a);
+            Ok(())
+        })
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.locked(&[table_name], || {
+            let mut seeded = Vec::new();
+            let created = self.inner.ensure_table_with(table_name, &mut || {
+                let entries = init();
+                seeded.clone_from(&entries);
+                entries
+            })?;
+            if created {
+                self.watchers.entries_inserted(table_name, seeded);
+            }
+            Ok(created)
+        })
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`ObservableDB`].
+#[derive(Debug)]
+pub struct AsyncObservableDB<T> {
+    inner: T,
+    watchers: Watchers,
+    locks: TableLocks<AsyncMutex<()>>,
+}
+
+impl<T> AsyncObservableDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            watchers: Watchers::default(),
+            locks: TableLocks::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// See [`ObservableDB::watch`].
+    pub fn watch(&self, table_name: &str, prefix: &str) -> UnboundedReceiver<ChangeEvent> {
+        self.watchers.watch(table_name, prefix)
+    }
+}
+
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncObservableDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        let locks = self.locks.get(&[table_name]);
+        let _guards = lock_all(&locks).await;
+        let old_value = self.inner.insert(table_name, key, value).await?;
+        self.watchers.inserted(table_name, key, value);
+        Ok(old_value)
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key).await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let locks = self.locks.get(&[table_name]);
+        let _guards = lock_all(&locks).await;
+        let old_value = self.inner.remove(table_name, key).await?;
+        if old_value.is_some() {
+            self.watchers.removed(table_name, key);
+        }
+        Ok(old_value)
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names().await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        let locks = self.locks.get(&[table_name]);
+        let _guards = lock_all(&locks).await;
+        self.inner.delete_table(table_name).await?;
+        self.watchers.table_deleted(table_name);
+        Ok(())
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix).await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let locks = self.locks.get(&[table_name]);
+        let _guards = lock_all(&locks).await;
+        self.inner
+            .compare_and_swap(table_name, key, expected, new)
+            .await?;
+        self.watchers.swapped(table_name, key, expected, new);
+        Ok(())
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range).await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        let locks = self.locks.get(&batch_tables(&batch));
+        let _guards = lock_all(&locks).await;
+        self.inner.apply_batch(batch.clone()).await?;
+        self.watchers.batch_applied(&batch);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        let table_names = self.inner.table_names().await?;
+        let tables = table_names.iter().map(String::as_str).collect::<Vec<_>>();
+        let locks = self.locks.get(&tables);
+        let _guards = lock_all(&locks).await;
+        self.inner.clear().await?;
+        for table_name in &table_names {
+            self.watchers.table_deleted(table_name);
+        }
+        Ok(())
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        let locks = self.locks.get(&[table_a, table_b]);
+        let _guards = lock_all(&locks).await;
+        let entries_a = self.inner.iter(table_a).await?;
+        let entries_b = self.inner.iter(table_b).await?;
+        self.inner.swap_tables(table_a, table_b).await?;
+        self.watchers.table_replaced(table_a, entries_b);
+        self.watchers.table_replaced(table_b, entries_a);
+        Ok(())
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        let locks = self.locks.get(&[table_name]);
+        let _guards = lock_all(&locks).await;
+        let mut seeded = Vec::new();
+        let created = self
+            .inner
+            .ensure_table_with(table_name, &mut || {
+                let entries = init();
+                seeded.clone_from(&entries);
+                entries
+            })
+            .await?;
+        if created {
+            self.watchers.entries_inserted(table_name, seeded);
+        }
+        Ok(created)
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
+
+#[derive(Debug)]
+struct Watcher {
+    table_name: String,
+    prefix: String,
+    sender: UnboundedSender<ChangeEvent>,
+}
+
+#[derive(Debug, Default)]
+struct Watchers(Mutex<Vec<Watcher>>);
+
+impl Watchers {
+    fn watch(&self, table_name: &str, prefix: &str) -> UnboundedReceiver<ChangeEvent> {
+        let (sender, receiver) = unbounded();
+        self.0.lock().unwrap().push(Watcher {
+            table_name: table_name.to_string(),
+            prefix: prefix.to_string(),
+            sender,
+        });
+        receiver
+    }
+
+    fn inserted(&self, table_name: &str, key: &str, value: &[u8]) {
+        self.publish(ChangeEvent::Insert {
+            table_name: table_name.to_string(),
+            key: key.to_string(),
+            value: value.to_vec(),
+        });
+    }
+
+    fn removed(&self, table_name: &str, key: &str) {
+        self.publish(ChangeEvent::Remove {
+            table_name: table_name.to_string(),
+            key: key.to_string(),
+        });
+    }
+
+    fn table_deleted(&self, table_name: &str) {
+        self.publish(ChangeEvent::DeleteTable {
+            table_name: table_name.to_string(),
+        });
+    }
+
+    fn entries_inserted(&self, table_name: &str, entries: Vec<(String, Vec<u8>)>) {
+        for (key, value) in entries {
+            self.publish(ChangeEvent::Insert {
+                table_name: table_name.to_string(),
+                key,
+                value,
+            });
+        }
+    }
+
+    /// Reports that `table_name` now holds `entries` and nothing else.
+    fn table_replaced(&self, table_name: &str, entries: Vec<(String, Vec<u8>)>) {
+        self.table_deleted(table_name);
+        self.entries_inserted(table_name, entries);
+    }
+
+    fn swapped(&self, table_name: &str, key: &str, expected: Option<&[u8]>, new: Option<&[u8]>) {
+        match (expected, new) {
+            (_, Some(value)) => self.inserted(table_name, key, value),
+            (Some(_), None) => self.removed(table_name, key),
+            (None, None) => {}
+        }
+    }
+
+    fn batch_applied(&self, batch: &WriteBatch) {
+        for op in batch.ops() {
+            match op {
+                BatchOp::Insert {
+                    table_name,
+                    key,
+                    value,
+                } => self.inserted(table_name, key, value),
+                BatchOp::Remove { table_name, key } => self.removed(table_name, key),
+            }
+        }
+    }
+
+    fn publish(&self, event: ChangeEvent) {
+        self.0.lock().unwrap().retain(|watcher| {
+            if watcher.table_name != event.table_name()
+                || !event
+                    .key()
+                    .is_none_or(|key| key.starts_with(&watcher.prefix))
+            {
+                return !watcher.sender.is_closed();
+            }
+            watcher.sender.unbounded_send(event.clone()).is_ok()
+        });
+    }
+}
+
+/// One lock per table, held by a write until its events are published.
+#[derive(Debug)]
+struct TableLocks<L>(Mutex<HashMap<String, Arc<L>>>);
+
+impl<L> Default for TableLocks<L> {
+    fn default() -> Self {
+        Self(Mutex::default())
+    }
+}
+
+impl<L: Default> TableLocks<L> {
+    /// Returns the locks of `table_names`, ordered by table name so that
+    /// writes touching several tables take them in the same order. Locks no
+    /// write holds any more are dropped first, so the map only grows with the
+    /// tables being written.
+    fn get(&self, table_names: &[&str]) -> Vec<Arc<L>> {
+        let mut locks = self.0.lock().unwrap();
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        table_names
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|table_name| locks.entry(table_name.to_string()).or_default().clone())
+            .collect()
+    }
+}
+
+async fn lock_all(locks: &[Arc<AsyncMutex<()>>]) -> Vec<AsyncMutexGuard<'_, ()>> {
+    let mut guards = Vec::with_capacity(locks.len());
+    for lock in locks {
+        guards.push(lock.lock().await);
+    }
+    guards
+}
+
+fn batch_tables(batch: &WriteBatch) -> Vec<&str> {
+    batch
+        .ops()
+        .iter()
+        .map(|op| match op {
+            BatchOp::Insert { table_name, .. } | BatchOp::Remove { table_name, .. } => {
+                table_name.as_str()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unused_table_locks_are_dropped() {
+        let locks = TableLocks::<Mutex<()>>::default();
+        let held = locks.get(&["a"]);
+        drop(locks.get(&["b", "c"]));
+
+        let again = locks.get(&["a"]);
+        assert!(Arc::ptr_eq(&held[0], &again[0]));
+        assert_eq!(
+            locks.0.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec!["a"]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+const HEADER_LEN: usize = 8;
+
+/// A message taken from the outbox by `lease`. It stays invisible to other
+/// consumers until `leased_until`, and is removed by `mark_published`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxMessage {
+    pub id: String,
+    pub payload: Vec<u8>,
+    pub leased_until: u64,
+    record: Vec<u8>,
+}
+
+/// Transactional outbox kept in a table of its own. Messages are added to
+/// the `WriteBatch` that carries the data changes, so they are stored if and
+/// only if the changes are, on backends that apply batches atomically.
+/// Consumers lease messages, publish them and then mark them as published;
+/// a message whose lease expires before that is handed out again, so
+/// delivery is at least once and consumers should be idempotent.
+///
+/// Leases rely on `compare_and_swap`, and messages are leased in the order
+/// of their ids. Times are opaque `u64`s chosen by the caller, e.g.
+/// milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outbox {
+    table_name: String,
+}
+
+impl Outbox {
+    pub fn new(table_name: &str) -> Self {
+        Self {
+            table_name: table_name.into(),
+        }
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Adds the message `id` to `batch`. Reusing the id of a pending message
+    /// replaces it.
+    pub fn enqueue(&self, batch: &mut WriteBatch, id: &str, payload: &[u8]) {
+        batch.insert(&self.table_name, id, &encode(0, payload));
+    }
+
+    /// Leases up to `max` messages that are not leased at `now`, until
+    /// `now + lease_for`.
+    pub fn lease(
+        &self,
+        db: &dyn KeyValueDB,
+        max: usize,
+        now: u64,
+        lease_for: u64,
+    ) -> Result<Vec<OutboxMessage>, io::Error> {
+        let mut messages = Vec::new();
+        for (id, record) in self.available(db.iter(&self.table_name)?, now)? {
+            if messages.len() == max {
+                break;
+            }
+            let message = leased(id, &record, now.saturating_add(lease_for))?;
+            match db.compare_and_swap(
+                &self.table_name,
+                &message.id,
+                Some(&record),
+                Some(&message.record),
+            ) {
+                Ok(()) => messages.push(message),
+                Err(CompareAndSwapError::Mismatch { .. }) => {}
+                Err(CompareAndSwapError::Io(e)) => return Err(e),
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Removes `message` from the outbox. Returns `false` if its lease expired
+    /// and the message was leased again or removed in the meantime.
+    pub fn mark_published(
+        &self,
+        db: &dyn KeyValueDB,
+        message: &OutboxMessage,
+    ) -> Result<bool, io::Error> {
+        match db.compare_and_swap(&self.table_name, &message.id, Some(&message.record), None) {
+            Ok(()) => Ok(true),
+            Err(CompareAndSwapError::Mismatch { .. }) => Ok(false),
+            Err(CompareAndSwapError::Io(e)) => Err(e),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn lease_async(
+        &self,
+        db: &dyn AsyncKeyValueDB,
+        max: usize,
+        now: u64,
+        lease_for: u64,
+    ) -> Result<Vec<OutboxMessage>, io::Error> {
+        let mut messages = Vec::new();
+        for (id, record) in self.available(db.iter(&self.table_name).await?, now)? {
+            if messages.len() == max {
+                break;
+            }
+            let message = leased(id, &record, now.saturating_add(lease_for))?;
+            match db
+                .compare_and_swap(
+                    &self.table_name,
+                    &message.id,
+                    Some(&record),
+                    Some(&message.record),
+                )
+                .await
+            {
+                Ok(()) => messages.push(message),
+                Err(CompareAndSwapError::Mismatch { .. }) => {}
+                Err(CompareAndSwapError::Io(e)) => return Err(e),
+            }
+        }
+        Ok(messages)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn mark_published_async(
+        &self,
+        db: &dyn AsyncKeyValueDB,
+        message: &OutboxMessage,
+    ) -> Result<bool, io::Error> {
+        match db
+            .compare_and_swap(&self.table_name, &message.id, Some(&message.record), None)
+            .await
+        {
+            Ok(()) => Ok(true),
+            Err(CompareAndSwapError::Mismatch { .. }) => Ok(false),
+            Err(CompareAndSwapError::Io(e)) => Err(e),
+        }
+    }
+
+    /// Returns the records whose lease ended by `now`, sorted by id.
+    #[allow(clippy::type_complexity)]
+    fn available(
+        &self,
+        mut entries: Vec<(String, Vec<u8>)>,
+        now: u64,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let mut available = Vec::new();
+        for (id, record) in entries {
+            if decode(&record)?.0 <= now {
+                available.push((id, record));
+            }
+        }
+        Ok(available)
+    }
+}
+
+fn leased(id: String, record: &[u8], leased_until: u64) -> Result<OutboxMessage, io::Error> {
+    let (_, payload) = decode(record)?;
+    Ok(OutboxMessage {
+        id,
+        payload: payload.to_vec(),
+        leased_until,
+        record: encode(leased_until, payload),
+    })
+}
+
+fn encode(leased_until: u64, payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.extend_from_slice(&leased_until.to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+fn decode(record: &[u8]) -> Result<(u64, &[u8]), io::Error> {
+    if record.len() < HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid outbox record",
+        ));
+    }
+    let (header, payload) = record.split_at(HEADER_LEN);
+    let leased_until = u64::from_le_bytes(header.try_into().unwrap());
+    Ok((leased_until, payload))
+}
@@ -0,0 +1,148 @@
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+const REDB_MAGIC: &[u8] = &[b'r', b'e', b'd', b'b', 0x1A, 0x0A, 0xA9, 0x0D, 0x0A];
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+const SNAPSHOT_MAGIC: &[u8] = b"KVSNAP\0\0";
+const FJALL_MAGIC: &[u8] = b"FJL";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Engine {
+    Redb,
+    Fs,
+    Snapshot,
+    Sqlite,
+    RocksDb,
+    Fjall,
+    /// An empty file, or one whose format is not recognized.
+    Unknown,
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Engine::Redb => "a redb database",
+            Engine::Fs => "a file system database",
+            Engine::Snapshot => "a keyvalue snapshot",
+            Engine::Sqlite => "a SQLite database",
+            Engine::RocksDb => "a RocksDB directory",
+            Engine::Fjall => "a Fjall keyspace",
+            Engine::Unknown => "an unrecognized file",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReport {
+    pub engine: Engine,
+    /// The version of the engine's own format, when it is stored where it
+    /// can be read without opening the database.
+    pub format_version: Option<u32>,
+    /// The layout version recorded by [`crate::meta::init_db_info`], for the
+    /// backends this build can open. redb databases are only read if they are
+    /// already open in this process, since opening one takes an exclusive lock
+    /// on the file and may write to it to repair it.
+    pub layout_version: Option<u32>,
+}
+
+impl ProbeReport {
+    /// Fails with an error naming the detected engine if it is neither
+    /// `expected` nor unknown.
+    pub fn expect(&self, expected: Engine) -> io::Result<()> {
+        if self.engine == expected || self.engine == Engine::Unknown {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("this is {}, but it was opened as {}", self.engine, expected),
+        ))
+    }
+}
+
+/// Inspects the file or directory at `path` and reports which engine created
+/// it. Only the layout version requires opening the database.
+pub fn probe(path: &Path) -> io::Result<ProbeReport> {
+    let (engine, format_version) = detect(path)?;
+    let layout_version = match engine {
+        Engine::Fs => fs_layout_version(path),
+        Engine::Redb => redb_layout_version(path),
+        _ => None,
+    };
+
+    Ok(ProbeReport {
+        engine,
+        format_version,
+        layout_version,
+    })
+}
+
+/// Fails if `path` exists and was created by another engine than `expected`.
+#[cfg(any(feature = "fs", feature = "redb"))]
+pub(crate) fn check_engine(path: &Path, expected: Engine) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let (engine, format_version) = detect(path)?;
+    ProbeReport {
+        engine,
+        format_version,
+        layout_version: None,
+    }
+    .expect(expected)
+}
+
+fn detect(path: &Path) -> io::Result<(Engine, Option<u32>)> {
+    if path.is_dir() {
+        if path.join("CURRENT").is_file() {
+            return Ok((Engine::RocksDb, None));
+        }
+        if let Some(version) = fs::read(path.join("version"))
+            .ok()
+            .and_then(|marker| marker.strip_prefix(FJALL_MAGIC).map(<[u8]>::to_vec))
+        {
+            return Ok((Engine::Fjall, version.first().copied().map(u32::from)));
+        }
+        return Ok((Engine::Fs, None));
+    }
+
+    let mut header = Vec::with_capacity(SQLITE_MAGIC.len());
+    fs::File::open(path)?
+        .take(SQLITE_MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+
+    if header.starts_with(REDB_MAGIC) {
+        Ok((Engine::Redb, None))
+    } else if header.starts_with(SQLITE_MAGIC) {
+        Ok((Engine::Sqlite, None))
+    } else if header.starts_with(SNAPSHOT_MAGIC) {
+        let version = header.get(SNAPSHOT_MAGIC.len()).copied().map(u32::from);
+        Ok((Engine::Snapshot, version))
+    } else {
+        Ok((Engine::Unknown, None))
+    }
+}
+
+#[cfg(feature = "fs")]
+fn fs_layout_version(path: &Path) -> Option<u32> {
+    let db = crate::fs::FsDB::open(path).ok()?;
+    Some(crate::meta::db_info(&db).ok()??.layout_version)
+}
+
+#[cfg(not(feature = "fs"))]
+fn fs_layout_version(_path: &Path) -> Option<u32> {
+    None
+}
+
+#[cfg(feature = "redb")]
+fn redb_layout_version(path: &Path) -> Option<u32> {
+    let db = crate::redb::RedbDB::open_in_process(path)?;
+    Some(crate::meta::db_info(&db).ok()??.layout_version)
+}
+
+#[cfg(not(feature = "redb"))]
+fn redb_layout_version(_path: &Path) -> Option<u32> {
+    None
+}
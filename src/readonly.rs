@@ -0,0 +1,336 @@
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, ReadOnly, WriteBatch};
+
+/// Forwards reads to the wrapped database and rejects every write with a
+/// [`ReadOnly`] error, so a handle can be given to code that must not
+/// modify the database. `flush` is forwarded, `compact` is rejected.
+#[derive(Debug)]
+pub struct ReadOnlyDB<T> {
+    inner: T,
+}
+
+impl<T> ReadOnlyDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for ReadOnlyDB<T> {
+    fn insert(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        Err(rejected("insert"))
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key)
+    }
+
+    fn remove(&self, _table_name: &str, _key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        Err(rejected("remove"))
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names()
+    }
+
+    fn create_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Err(rejected("create_table"))
+    }
+
+    fn delete_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Err(rejected("delete_table"))
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_range(table_name, start_key, end_key)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        Err(rejected("clear"))
+    }
+
+    fn swap_tables(&self, _table_a: &str, _table_b: &str) -> Result<(), io::Error> {
+        Err(rejected("swap_tables"))
+    }
+
+    /// Succeeds without calling `init` if the table exists already.
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        _init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        if self
+            .inner
+            .table_names()?
+            .iter()
+            .any(|name| name == table_name)
+        {
+            return Ok(false);
+        }
+        Err(rejected("ensure_table_with"))
+    }
+
+    fn compare_and_swap(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        Err(read_only("compare_and_swap").into())
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range)
+    }
+
+    fn apply_batch(&self, _batch: WriteBatch) -> Result<(), io::Error> {
+        Err(rejected("apply_batch"))
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        Err(rejected("compact"))
+    }
+}
+
+/// Async counterpart of [`ReadOnlyDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncReadOnlyDB<T> {
+    inner: T,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncReadOnlyDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncReadOnlyDB<T> {
+    async fn insert(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        Err(rejected("insert"))
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(table_name, key).await
+    }
+
+    async fn remove(&self, _table_name: &str, _key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        Err(rejected("remove"))
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(table_name).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.inner.table_names().await
+    }
+
+    async fn create_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Err(rejected("create_table"))
+    }
+
+    async fn delete_table(&self, _table_name: &str) -> Result<(), io::Error> {
+        Err(rejected("delete_table"))
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter_from_prefix(table_name, prefix).await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(table_name, start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(table_name, key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(table_name).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        Err(rejected("clear"))
+    }
+
+    async fn swap_tables(&self, _table_a: &str, _table_b: &str) -> Result<(), io::Error> {
+        Err(rejected("swap_tables"))
+    }
+
+    /// Succeeds without calling `init` if the table exists already.
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        _init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        if self
+            .inner
+            .table_names()
+            .await?
+            .iter()
+            .any(|name| name == table_name)
+        {
+            return Ok(false);
+        }
+        Err(rejected("ensure_table_with"))
+    }
+
+    async fn compare_and_swap(
+        &self,
+        _table_name: &str,
+        _key: &str,
+        _expected: Option<&[u8]>,
+        _new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        Err(read_only("compare_and_swap").into())
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(table_name, key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(table_name, key, range).await
+    }
+
+    async fn apply_batch(&self, _batch: WriteBatch) -> Result<(), io::Error> {
+        Err(rejected("apply_batch"))
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        Err(rejected("compact"))
+    }
+}
+
+fn read_only(operation: &'static str) -> ReadOnly {
+    ReadOnly { operation }
+}
+
+fn rejected(operation: &'static str) -> io::Error {
+    read_only(operation).into()
+}
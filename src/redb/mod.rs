@@ -1,31 +1,219 @@
-use std::{io, path::Path};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, Weak},
+};
 
+pub use redb::Durability;
 use redb::{
-    CommitError, Database, DatabaseError, ReadableTable, StorageError, TableDefinition, TableError,
-    TableHandle, TransactionError,
+    CommitError, CompactionError, Database, DatabaseError, ReadTransaction, ReadableTable,
+    ReadableTableMetadata, StorageError, TableDefinition, TableError, TableHandle,
+    TransactionError, WriteTransaction,
+};
+
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    probe::{check_engine, Engine},
+    BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch,
 };
 
-use crate::KeyValueDB;
+/// The databases open in this process, by canonical path, so that opening a
+/// path twice shares the database instead of failing.
+static OPEN_DATABASES: Mutex<BTreeMap<PathBuf, Weak<RwLock<Database>>>> =
+    Mutex::new(BTreeMap::new());
 
-#[derive(Debug)]
+/// A handle to a redb database. Opening a path that is already open in this
+/// process returns a handle to the same database, as does `clone`; the
+/// database is closed when the last handle is dropped.
+#[derive(Debug, Clone)]
 pub struct RedbDB {
-    inner: Database,
+    // Compaction needs exclusive access to the database.
+    inner: Arc<RwLock<Database>>,
+    durability: Durability,
 }
 
 impl RedbDB {
     pub fn open(path: &Path) -> io::Result<Self> {
-        let inner = Database::create(path).map_err(database_error_to_io_error)?;
+        Self::builder().open(path)
+    }
 
-        Ok(Self { inner })
+    pub fn builder() -> RedbDBBuilder {
+        RedbDBBuilder::default()
     }
-}
 
-impl KeyValueDB for RedbDB {
-    fn insert(&self, table_name: &str, key: &str, value: &[u8]) -> io::Result<Option<Vec<u8>>> {
-        let write_transaction = self
+    /// Copies every table, as seen by a single read transaction, into a new
+    /// database at `path`, which must not exist yet, and opens it with the
+    /// same durability.
+    pub fn fork(&self, path: &Path) -> io::Result<RedbDB> {
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the fork destination already exists",
+            ));
+        }
+        let fork = Self::builder().durability(self.durability).open(path)?;
+
+        let read_transaction = self.begin_read()?;
+        let mut write_transaction = fork.begin_write()?;
+        write_transaction.set_durability(Durability::Immediate);
+        for handle in read_transaction
+            .list_tables()
+            .map_err(storage_error_to_io_error)?
+        {
+            let definition = TableDefinition::<&str, &[u8]>::new(handle.name());
+            let source = read_transaction
+                .open_table(definition)
+                .map_err(table_error_to_io_error)?;
+            let mut target = write_transaction
+                .open_table(definition)
+                .map_err(table_error_to_io_error)?;
+            for entry in source.iter().map_err(storage_error_to_io_error)? {
+                let (key, value) = entry.map_err(storage_error_to_io_error)?;
+                target
+                    .insert(key.value(), value.value())
+                    .map_err(storage_error_to_io_error)?;
+            }
+        }
+        write_transaction
+            .commit()
+            .map_err(commit_error_to_io_error)?;
+
+        Ok(fork)
+    }
+
+    /// Calls `f` with the value of `key` borrowed from the page cache,
+    /// without the allocation and copy made by `get`. This suits reading a
+    /// part of large values, or decoding them in place. The read
+    /// transaction stays open while `f` runs, so `f` should be short.
+    pub fn get_with<R>(
+        &self,
+        table_name: &str,
+        key: &str,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> io::Result<Option<R>> {
+        let read_transaction = self.begin_read()?;
+        let table =
+            match read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name)) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(table_error_to_io_error(e)),
+            };
+        let value = table.get(key).map_err(storage_error_to_io_error)?;
+
+        Ok(value.map(|value| f(value.value())))
+    }
+
+    /// Returns a handle to the database at `path` if it is already open in
+    /// this process, without opening the file.
+    pub(crate) fn open_in_process(path: &Path) -> Option<RedbDB> {
+        let path = path.canonicalize().ok()?;
+        let inner = OPEN_DATABASES.lock().unwrap().get(&path)?.upgrade()?;
+        Some(RedbDB {
+            inner,
+            durability: Durability::Immediate,
+        })
+    }
+
+    fn begin_read(&self) -> io::Result<ReadTransaction> {
+        self.inner
+            .read()
+            .unwrap()
+            .begin_read()
+            .map_err(transaction_error_to_io_error)
+    }
+
+    fn begin_write(&self) -> io::Result<WriteTransaction> {
+        let mut write_transaction = self
             .inner
+            .read()
+            .unwrap()
             .begin_write()
             .map_err(transaction_error_to_io_error)?;
+        write_transaction.set_durability(self.durability);
+
+        Ok(write_transaction)
+    }
+}
+
+/// Settings for opening a `RedbDB`.
+#[derive(Debug, Clone, Copy)]
+pub struct RedbDBBuilder {
+    cache_size: Option<usize>,
+    durability: Durability,
+}
+
+impl Default for RedbDBBuilder {
+    fn default() -> Self {
+        Self {
+            cache_size: None,
+            durability: Durability::Immediate,
+        }
+    }
+}
+
+impl RedbDBBuilder {
+    /// Sets the size of the page cache in bytes. Defaults to redb's own
+    /// default.
+    pub fn cache_size(mut self, bytes: usize) -> Self {
+        self.cache_size = Some(bytes);
+        self
+    }
+
+    /// Sets the durability of every write. With `Durability::Eventual`,
+    /// writes are persisted by a later `flush` or by a later write with a
+    /// stronger durability. Defaults to `Durability::Immediate`.
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Opens the database at `path`, creating it if needed. If it is
+    /// already open in this process, the returned handle shares it, and
+    /// the cache size of this builder is ignored.
+    pub fn open(self, path: &Path) -> io::Result<RedbDB> {
+        check_engine(path, Engine::Redb)?;
+
+        // The registry stays locked while the database is created, so
+        // concurrent opens of a new path do not race.
+        let mut open_databases = OPEN_DATABASES.lock().unwrap();
+        open_databases.retain(|_, database| database.strong_count() > 0);
+        if let Some(inner) = path
+            .canonicalize()
+            .ok()
+            .and_then(|path| open_databases.get(&path))
+            .and_then(Weak::upgrade)
+        {
+            return Ok(RedbDB {
+                inner,
+                durability: self.durability,
+            });
+        }
+
+        let mut builder = Database::builder();
+        if let Some(cache_size) = self.cache_size {
+            builder.set_cache_size(cache_size);
+        }
+        let inner = Arc::new(RwLock::new(
+            builder.create(path).map_err(database_error_to_io_error)?,
+        ));
+        open_databases.insert(path.canonicalize()?, Arc::downgrade(&inner));
+
+        Ok(RedbDB {
+            inner,
+            durability: self.durability,
+        })
+    }
+}
+
+impl crate::meta::Backend for RedbDB {
+    const NAME: &'static str = "redb";
+}
+
+impl KeyValueDB for RedbDB {
+    fn insert(&self, table_name: &str, key: &str, value: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let write_transaction = self.begin_write()?;
         let old_value = {
             let mut table = write_transaction
                 .open_table(TableDefinition::<&str, &[u8]>::new(table_name))
@@ -45,10 +233,7 @@ impl KeyValueDB for RedbDB {
     }
 
     fn get(&self, table_name: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
-        let read_transaction = self
-            .inner
-            .begin_read()
-            .map_err(transaction_error_to_io_error)?;
+        let read_transaction = self.begin_read()?;
         let value = {
             let table_res =
                 read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name));
@@ -71,10 +256,7 @@ impl KeyValueDB for RedbDB {
     }
 
     fn remove(&self, table_name: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
-        let write_transaction = self
-            .inner
-            .begin_write()
-            .map_err(transaction_error_to_io_error)?;
+        let write_transaction = self.begin_write()?;
         let old_value = {
             let table_res =
                 write_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name));
@@ -109,10 +291,7 @@ impl KeyValueDB for RedbDB {
     }
 
     fn iter(&self, table_name: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
-        let read_transaction = self
-            .inner
-            .begin_read()
-            .map_err(transaction_error_to_io_error)?;
+        let read_transaction = self.begin_read()?;
         let table_res =
             read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name));
         let table = match table_res {
@@ -130,11 +309,177 @@ impl KeyValueDB for RedbDB {
         Ok(result)
     }
 
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> io::Result<Vec<(String, Vec<u8>)>> {
+        if start_key >= end_key {
+            return Ok(Vec::new());
+        }
+        let read_transaction = self.begin_read()?;
+        let table_res =
+            read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name));
+        let table = match table_res {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => {
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(table_error_to_io_error(e)),
+        };
+        let mut result = Vec::new();
+        for item in table
+            .range(start_key..end_key)
+            .map_err(storage_error_to_io_error)?
+        {
+            let (key, value) = item.map_err(storage_error_to_io_error)?;
+            result.push((key.value().to_string(), value.value().to_vec()));
+        }
+        Ok(result)
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> io::Result<bool> {
+        let write_transaction = self.begin_write()?;
+        let exists = write_transaction
+            .list_tables()
+            .map_err(storage_error_to_io_error)?
+            .any(|table| table.name() == table_name);
+        if exists {
+            write_transaction
+                .abort()
+                .map_err(storage_error_to_io_error)?;
+            return Ok(false);
+        }
+        {
+            let mut table = write_transaction
+                .open_table(TableDefinition::<&str, &[u8]>::new(table_name))
+                .map_err(table_error_to_io_error)?;
+            for (key, value) in init() {
+                table
+                    .insert(key.as_str(), value.as_slice())
+                    .map_err(storage_error_to_io_error)?;
+            }
+        }
+        write_transaction
+            .commit()
+            .map_err(commit_error_to_io_error)?;
+
+        Ok(true)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        let write_transaction = self.begin_write()?;
+        let (current, changed) = {
+            let mut table = write_transaction
+                .open_table(TableDefinition::<&str, &[u8]>::new(table_name))
+                .map_err(table_error_to_io_error)?;
+            let current = table
+                .get(key)
+                .map_err(storage_error_to_io_error)?
+                .map(|v| v.value().to_vec());
+            if current.as_deref() != expected {
+                (Some(current), false)
+            } else {
+                match new {
+                    Some(value) => {
+                        table
+                            .insert(key, value)
+                            .map_err(storage_error_to_io_error)?;
+                    }
+                    None => {
+                        table.remove(key).map_err(storage_error_to_io_error)?;
+                    }
+                }
+                (None, new.is_some() || expected.is_some())
+            }
+        };
+
+        if changed {
+            write_transaction
+                .commit()
+                .map_err(commit_error_to_io_error)?;
+        } else {
+            write_transaction
+                .abort()
+                .map_err(storage_error_to_io_error)?;
+        }
+
+        match current {
+            Some(current) => Err(CompareAndSwapError::Mismatch { current }),
+            None => Ok(()),
+        }
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> io::Result<Option<u64>> {
+        let read_transaction = self.begin_read()?;
+        let table =
+            match read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name)) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(table_error_to_io_error(e)),
+            };
+
+        Ok(table
+            .get(key)
+            .map_err(storage_error_to_io_error)?
+            .map(|v| xxh3_64(v.value())))
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> io::Result<()> {
+        let write_transaction = self.begin_write()?;
+        for op in batch {
+            let mut table = write_transaction
+                .open_table(TableDefinition::<&str, &[u8]>::new(op.table_name()))
+                .map_err(table_error_to_io_error)?;
+            match &op {
+                BatchOp::Insert { key, value, .. } => {
+                    table
+                        .insert(key.as_str(), value.as_slice())
+                        .map_err(storage_error_to_io_error)?;
+                }
+                BatchOp::Remove { key, .. } => {
+                    table
+                        .remove(key.as_str())
+                        .map_err(storage_error_to_io_error)?;
+                }
+            }
+        }
+        write_transaction
+            .commit()
+            .map_err(commit_error_to_io_error)?;
+
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut write_transaction = self.begin_write()?;
+        write_transaction.set_durability(Durability::Immediate);
+        write_transaction.commit().map_err(commit_error_to_io_error)
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        self.inner
+            .write()
+            .unwrap()
+            .compact()
+            .map_err(compaction_error_to_io_error)?;
+
+        Ok(())
+    }
+
     fn table_names(&self) -> Result<Vec<String>, io::Error> {
-        let read_transaction = self
-            .inner
-            .begin_read()
-            .map_err(transaction_error_to_io_error)?;
+        let read_transaction = self.begin_read()?;
         let mut result = Vec::new();
         let tables_res = read_transaction.list_tables();
         match tables_res {
@@ -151,11 +496,29 @@ impl KeyValueDB for RedbDB {
         Ok(result)
     }
 
+    fn create_table(&self, table_name: &str) -> io::Result<()> {
+        let write_transaction = self.begin_write()?;
+        write_transaction
+            .open_table(TableDefinition::<&str, &[u8]>::new(table_name))
+            .map_err(table_error_to_io_error)?;
+        write_transaction
+            .commit()
+            .map_err(commit_error_to_io_error)?;
+
+        Ok(())
+    }
+
+    fn len(&self, table_name: &str) -> io::Result<u64> {
+        let read_transaction = self.begin_read()?;
+        match read_transaction.open_table(TableDefinition::<&str, &[u8]>::new(table_name)) {
+            Ok(table) => table.len().map_err(storage_error_to_io_error),
+            Err(TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(e) => Err(table_error_to_io_error(e)),
+        }
+    }
+
     fn delete_table(&self, table_name: &str) -> io::Result<()> {
-        let write_transaction = self
-            .inner
-            .begin_write()
-            .map_err(transaction_error_to_io_error)?;
+        let write_transaction = self.begin_write()?;
         write_transaction
             .delete_table(TableDefinition::<&str, &[u8]>::new(table_name))
             .map_err(table_error_to_io_error)?;
@@ -165,6 +528,59 @@ impl KeyValueDB for RedbDB {
 
         Ok(())
     }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> io::Result<()> {
+        if table_a == table_b {
+            return Ok(());
+        }
+        let write_transaction = self.begin_write()?;
+        let existing = write_transaction
+            .list_tables()
+            .map_err(storage_error_to_io_error)?
+            .map(|table| table.name().to_string())
+            .collect::<Vec<_>>();
+        let mut entries = Vec::new();
+        for table_name in [table_a, table_b] {
+            let definition = TableDefinition::<&str, &[u8]>::new(table_name);
+            if !existing.iter().any(|name| name == table_name) {
+                entries.push(None);
+                continue;
+            }
+            let mut table_entries = Vec::new();
+            {
+                let table = write_transaction
+                    .open_table(definition)
+                    .map_err(table_error_to_io_error)?;
+                for item in table.iter().map_err(storage_error_to_io_error)? {
+                    let (key, value) = item.map_err(storage_error_to_io_error)?;
+                    table_entries.push((key.value().to_string(), value.value().to_vec()));
+                }
+            }
+            write_transaction
+                .delete_table(definition)
+                .map_err(table_error_to_io_error)?;
+            entries.push(Some(table_entries));
+        }
+        let entries_b = entries.pop().flatten();
+        let entries_a = entries.pop().flatten();
+        for (table_name, table_entries) in [(table_a, entries_b), (table_b, entries_a)] {
+            if let Some(table_entries) = table_entries {
+                let mut table = write_transaction
+                    .open_table(TableDefinition::<&str, &[u8]>::new(table_name))
+                    .map_err(table_error_to_io_error)?;
+                for (key, value) in table_entries {
+                    table
+                        .insert(key.as_str(), value.as_slice())
+                        .map_err(storage_error_to_io_error)?;
+                }
+            }
+        }
+        write_transaction
+            .commit()
+            .map_err(commit_error_to_io_error)?;
+
+        Ok(())
+    }
 }
 
 fn storage_error_to_io_error(e: StorageError) -> io::Error {
@@ -251,6 +667,17 @@ fn table_error_to_io_error(e: TableError) -> io::Error {
     }
 }
 
+fn compaction_error_to_io_error(e: CompactionError) -> io::Error {
+    match e {
+        CompactionError::Storage(e) => storage_error_to_io_error(e),
+        CompactionError::TransactionInProgress => io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "Database cannot be compacted while a transaction is in progress",
+        ),
+        e => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
 fn commit_error_to_io_error(e: CommitError) -> io::Error {
     match e {
         CommitError::Storage(e) => storage_error_to_io_error(e),
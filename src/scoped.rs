@@ -0,0 +1,427 @@
+use core::ops::Range;
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+use alloc::format;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{BatchOp, CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// Separates the namespace from the table name in the wrapped database.
+pub const SEPARATOR: &str = "::";
+
+/// A view of the tables of one namespace. Every table `name` is stored as
+/// `namespace::name` in the wrapped database, so components sharing it cannot
+/// see or overwrite each other's tables. Keys are left untouched.
+#[derive(Debug)]
+pub struct ScopedDB<T> {
+    inner: T,
+    prefix: String,
+}
+
+impl<T> ScopedDB<T> {
+    /// # Panics
+    ///
+    /// If `namespace` contains [`SEPARATOR`].
+    pub fn new(inner: T, namespace: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix(namespace),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.prefix[..self.prefix.len() - SEPARATOR.len()]
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn scoped(&self, table_name: &str) -> String {
+        format!("{}{}", self.prefix, table_name)
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for ScopedDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.insert(&self.scoped(table_name), key, value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(&self.scoped(table_name), key)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.remove(&self.scoped(table_name), key)
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(&self.scoped(table_name))
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(strip_prefix(&self.prefix, self.inner.table_names()?))
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(&self.scoped(table_name))
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(&self.scoped(table_name))
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_prefix(&self.scoped(table_name), prefix)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(&self.scoped(table_name), start_key, end_key)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(&self.scoped(table_name), key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(&self.scoped(table_name))
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(&self.scoped(table_name))
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(&self.scoped(table_name))
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(&self.scoped(table_name))
+    }
+
+    /// Only deletes the tables of this namespace.
+    fn clear(&self) -> Result<(), io::Error> {
+        for table_name in KeyValueDB::table_names(self)? {
+            KeyValueDB::delete_table(self, &table_name)?;
+        }
+        Ok(())
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.inner
+            .swap_tables(&self.scoped(table_a), &self.scoped(table_b))
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.inner.ensure_table_with(&self.scoped(table_name), init)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.inner
+            .compare_and_swap(&self.scoped(table_name), key, expected, new)
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(&self.scoped(table_name), key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get_range(&self.scoped(table_name), key, range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner.apply_batch(scope_batch(&self.prefix, batch))
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact()
+    }
+}
+
+/// Async counterpart of [`ScopedDB`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncScopedDB<T> {
+    inner: T,
+    prefix: String,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncScopedDB<T> {
+    /// # Panics
+    ///
+    /// If `namespace` contains [`SEPARATOR`].
+    pub fn new(inner: T, namespace: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix(namespace),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.prefix[..self.prefix.len() - SEPARATOR.len()]
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn scoped(&self, table_name: &str) -> String {
+        format!("{}{}", self.prefix, table_name)
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncScopedDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .insert(&self.scoped(table_name), key, value)
+            .await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(&self.scoped(table_name), key).await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.remove(&self.scoped(table_name), key).await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner.iter(&self.scoped(table_name)).await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        Ok(strip_prefix(&self.prefix, self.inner.table_names().await?))
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.create_table(&self.scoped(table_name)).await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.inner.delete_table(&self.scoped(table_name)).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_prefix(&self.scoped(table_name), prefix)
+            .await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        self.inner
+            .iter_from_range(&self.scoped(table_name), start_key, end_key)
+            .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        self.inner.contains_key(&self.scoped(table_name), key).await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        self.inner.keys(&self.scoped(table_name)).await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.inner.values(&self.scoped(table_name)).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.inner.len(&self.scoped(table_name)).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.inner.is_empty(&self.scoped(table_name)).await
+    }
+
+    /// Only deletes the tables of this namespace.
+    async fn clear(&self) -> Result<(), io::Error> {
+        for table_name in self.table_names().await? {
+            self.delete_table(&table_name).await?;
+        }
+        Ok(())
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.inner
+            .swap_tables(&self.scoped(table_a), &self.scoped(table_b))
+            .await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.inner
+            .ensure_table_with(&self.scoped(table_name), init)
+            .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.inner
+            .compare_and_swap(&self.scoped(table_name), key, expected, new)
+            .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.inner.checksum(&self.scoped(table_name), key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner
+            .get_range(&self.scoped(table_name), key, range)
+            .await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.inner
+            .apply_batch(scope_batch(&self.prefix, batch))
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.inner.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.inner.compact().await
+    }
+}
+
+/// Returns the namespaces that have at least one table in `db`.
+pub fn list_namespaces(db: &dyn KeyValueDB) -> Result<Vec<String>, io::Error> {
+    Ok(namespaces(db.table_names()?))
+}
+
+#[cfg(feature = "async")]
+pub async fn list_namespaces_async(db: &dyn AsyncKeyValueDB) -> Result<Vec<String>, io::Error> {
+    Ok(namespaces(db.table_names().await?))
+}
+
+fn prefix(namespace: &str) -> String {
+    assert!(
+        !namespace.contains(SEPARATOR),
+        "namespace {namespace:?} contains {SEPARATOR:?}"
+    );
+    format!("{}{}", namespace, SEPARATOR)
+}
+
+fn strip_prefix(prefix: &str, table_names: Vec<String>) -> Vec<String> {
+    table_names
+        .into_iter()
+        .filter_map(|table_name| table_name.strip_prefix(prefix).map(String::from))
+        .collect()
+}
+
+fn namespaces(table_names: Vec<String>) -> Vec<String> {
+    let mut namespaces = table_names
+        .iter()
+        .filter_map(|table_name| table_name.split_once(SEPARATOR))
+        .map(|(namespace, _)| String::from(namespace))
+        .collect::<Vec<_>>();
+    namespaces.sort_unstable();
+    namespaces.dedup();
+    namespaces
+}
+
+fn scope_batch(prefix: &str, batch: WriteBatch) -> WriteBatch {
+    batch
+        .into_iter()
+        .map(|op| match op {
+            BatchOp::Insert {
+                table_name,
+                key,
+                value,
+            } => BatchOp::Insert {
+                table_name: format!("{}{}", prefix, table_name),
+                key,
+                value,
+            },
+            BatchOp::Remove { table_name, key } => BatchOp::Remove {
+                table_name: format!("{}{}", prefix, table_name),
+                key,
+            },
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
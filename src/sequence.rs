@@ -0,0 +1,140 @@
+use core::ops::Range;
+
+use crate::io;
+
+#[cfg(feature = "async")]
+use crate::{update::run_update_async, AsyncKeyValueDB};
+use crate::{
+    update::{run_update, update_error, DEFAULT_MAX_ATTEMPTS},
+    KeyValueDB,
+};
+
+/// The table holding the last value handed out by every sequence.
+pub const SEQUENCE_TABLE: &str = "__keyvalue_sequences";
+
+/// Returns the next value of the sequence `name`, starting at 1. Values are
+/// unique across all users of `db`, as long as the backend supports
+/// `compare_and_swap`.
+pub fn next_sequence(db: &dyn KeyValueDB, name: &str) -> Result<u64, io::Error> {
+    Ok(reserve(db, name, 1)?.start)
+}
+
+/// Takes the next `count` values of the sequence `name` at once. Fails if
+/// the sequence advanced during each of [`DEFAULT_MAX_ATTEMPTS`] attempts.
+pub fn reserve(db: &dyn KeyValueDB, name: &str, count: u64) -> Result<Range<u64>, io::Error> {
+    let mut range = 0..0;
+    run_update(db, SEQUENCE_TABLE, name, DEFAULT_MAX_ATTEMPTS, |current| {
+        let (reserved, new) = advance(current, count)?;
+        range = reserved;
+        Ok(Some(new.to_vec()))
+    })
+    .map_err(update_error)?;
+    Ok(range)
+}
+
+#[cfg(feature = "async")]
+pub async fn next_sequence_async(db: &dyn AsyncKeyValueDB, name: &str) -> Result<u64, io::Error> {
+    Ok(reserve_async(db, name, 1).await?.start)
+}
+
+#[cfg(feature = "async")]
+pub async fn reserve_async(
+    db: &dyn AsyncKeyValueDB,
+    name: &str,
+    count: u64,
+) -> Result<Range<u64>, io::Error> {
+    let mut range = 0..0;
+    run_update_async(db, SEQUENCE_TABLE, name, DEFAULT_MAX_ATTEMPTS, |current| {
+        let (reserved, new) = advance(current, count)?;
+        range = reserved;
+        Ok(Some(new.to_vec()))
+    })
+    .await
+    .map_err(update_error)?;
+    Ok(range)
+}
+
+/// A sequence that reserves `cache_size` values at a time and hands them out
+/// from memory, so only one in `cache_size` calls writes to the database.
+///
+/// Values stay unique, and increase within a process, but values reserved by
+/// a `Sequence` that is dropped before using them are skipped, and values
+/// handed out by different processes interleave.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Sequence {
+    name: String,
+    cache_size: u64,
+    reserved: std::sync::Mutex<Range<u64>>,
+}
+
+#[cfg(feature = "std")]
+impl Sequence {
+    pub fn new(name: &str, cache_size: u64) -> Self {
+        Self {
+            name: name.into(),
+            cache_size: cache_size.max(1),
+            reserved: std::sync::Mutex::new(0..0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn next(&self, db: &dyn KeyValueDB) -> Result<u64, io::Error> {
+        let mut reserved = self.reserved.lock().unwrap();
+        if reserved.is_empty() {
+            *reserved = reserve(db, &self.name, self.cache_size)?;
+        }
+        Ok(reserved.next().unwrap())
+    }
+}
+
+/// Async counterpart of [`Sequence`].
+#[cfg(all(feature = "async", feature = "std"))]
+#[derive(Debug)]
+pub struct AsyncSequence {
+    name: String,
+    cache_size: u64,
+    reserved: futures::lock::Mutex<Range<u64>>,
+}
+
+#[cfg(all(feature = "async", feature = "std"))]
+impl AsyncSequence {
+    pub fn new(name: &str, cache_size: u64) -> Self {
+        Self {
+            name: name.into(),
+            cache_size: cache_size.max(1),
+            reserved: futures::lock::Mutex::new(0..0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn next(&self, db: &dyn AsyncKeyValueDB) -> Result<u64, io::Error> {
+        let mut reserved = self.reserved.lock().await;
+        if reserved.is_empty() {
+            *reserved = reserve_async(db, &self.name, self.cache_size).await?;
+        }
+        Ok(reserved.next().unwrap())
+    }
+}
+
+/// Returns the values following the stored `current` one and the new value to
+/// store.
+fn advance(current: Option<&[u8]>, count: u64) -> Result<(Range<u64>, [u8; 8]), io::Error> {
+    let last = match current {
+        Some(current) => <[u8; 8]>::try_from(current)
+            .map(u64::from_le_bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid sequence value"))?,
+        None => 0,
+    };
+    let end = last
+        .checked_add(count)
+        .filter(|end| *end < u64::MAX)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "sequence exhausted"))?;
+    Ok((last + 1..end + 1, end.to_le_bytes()))
+}
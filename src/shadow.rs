@@ -0,0 +1,549 @@
+use std::{
+    fmt, io,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// A sampled read whose result differed between the primary and the shadow
+/// database. A shadow read that failed is reported as a mismatch too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowMismatch {
+    pub operation: &'static str,
+    pub table_name: String,
+    /// The key or prefix that was read, if any.
+    pub key: Option<String>,
+}
+
+/// Serves every operation from `primary` and, for one read out of every
+/// `sample_every`, repeats the read on `shadow` and reports the reads whose
+/// results differ. Listings are compared regardless of their order.
+///
+/// Writes only go to `primary`, so `shadow` is meant to be a path to the same
+/// data that bypasses some layers, e.g. the database wrapped by a cache.
+pub struct ShadowReadDB<P, S> {
+    primary: P,
+    shadow: S,
+    sampler: Sampler,
+}
+
+impl<P, S> ShadowReadDB<P, S> {
+    /// Compares one read out of every `sample_every`, or none if it is `0`,
+    /// and calls `on_mismatch` for every difference found.
+    pub fn new(
+        primary: P,
+        shadow: S,
+        sample_every: u64,
+        on_mismatch: impl Fn(&ShadowMismatch) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            primary,
+            shadow,
+            sampler: Sampler::new(sample_every, on_mismatch),
+        }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn shadow(&self) -> &S {
+        &self.shadow
+    }
+
+    /// Returns the number of mismatches found so far.
+    pub fn mismatches(&self) -> u64 {
+        self.sampler.mismatches.load(Ordering::Relaxed)
+    }
+}
+
+impl<P: fmt::Debug, S: fmt::Debug> fmt::Debug for ShadowReadDB<P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShadowReadDB")
+            .field("primary", &self.primary)
+            .field("shadow", &self.shadow)
+            .field("sampler", &self.sampler)
+            .finish()
+    }
+}
+
+impl<P: KeyValueDB, S: KeyValueDB> KeyValueDB for ShadowReadDB<P, S> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.insert(table_name, key, value)
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let value = self.primary.get(table_name, key)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.get(table_name, key);
+            self.sampler
+                .compare("get", table_name, Some(key), &value, shadow);
+        }
+        Ok(value)
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.remove(table_name, key)
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self.primary.iter(table_name)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.iter(table_name);
+            self.sampler
+                .compare_unordered("iter", table_name, None, &entries, shadow);
+        }
+        Ok(entries)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.primary.table_names()
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.primary.create_table(table_name)
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.primary.delete_table(table_name)
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self.primary.iter_from_prefix(table_name, prefix)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.iter_from_prefix(table_name, prefix);
+            self.sampler.compare_unordered(
+                "iter_from_prefix",
+                table_name,
+                Some(prefix),
+                &entries,
+                shadow,
+            );
+        }
+        Ok(entries)
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self
+            .primary
+            .iter_from_range(table_name, start_key, end_key)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.iter_from_range(table_name, start_key, end_key);
+            self.sampler.compare_unordered(
+                "iter_from_range",
+                table_name,
+                Some(start_key),
+                &entries,
+                shadow,
+            );
+        }
+        Ok(entries)
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        let contains_key = self.primary.contains_key(table_name, key)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.contains_key(table_name, key);
+            self.sampler
+                .compare("contains_key", table_name, Some(key), &contains_key, shadow);
+        }
+        Ok(contains_key)
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        let keys = self.primary.keys(table_name)?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.keys(table_name);
+            self.sampler
+                .compare_unordered("keys", table_name, None, &keys, shadow);
+        }
+        Ok(keys)
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.primary.values(table_name)
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.primary.len(table_name)
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.primary.is_empty(table_name)
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.primary.clear()
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.primary.swap_tables(table_a, table_b)
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        self.primary.ensure_table_with(table_name, init)
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.primary
+            .compare_and_swap(table_name, key, expected, new)
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.primary.checksum(table_name, key)
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.get_range(table_name, key, range)
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.primary.apply_batch(batch)
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        self.primary.flush()
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        self.primary.compact()
+    }
+}
+
+/// Async counterpart of [`ShadowReadDB`].
+#[cfg(feature = "async")]
+pub struct AsyncShadowReadDB<P, S> {
+    primary: P,
+    shadow: S,
+    sampler: Sampler,
+}
+
+#[cfg(feature = "async")]
+impl<P, S> AsyncShadowReadDB<P, S> {
+    /// See [`ShadowReadDB::new`].
+    pub fn new(
+        primary: P,
+        shadow: S,
+        sample_every: u64,
+        on_mismatch: impl Fn(&ShadowMismatch) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            primary,
+            shadow,
+            sampler: Sampler::new(sample_every, on_mismatch),
+        }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn shadow(&self) -> &S {
+        &self.shadow
+    }
+
+    pub fn mismatches(&self) -> u64 {
+        self.sampler.mismatches.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P: fmt::Debug, S: fmt::Debug> fmt::Debug for AsyncShadowReadDB<P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncShadowReadDB")
+            .field("primary", &self.primary)
+            .field("shadow", &self.shadow)
+            .field("sampler", &self.sampler)
+            .finish()
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<P: AsyncKeyValueDB, S: AsyncKeyValueDB> AsyncKeyValueDB for AsyncShadowReadDB<P, S> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.insert(table_name, key, value).await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let value = self.primary.get(table_name, key).await?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.get(table_name, key).await;
+            self.sampler
+                .compare("get", table_name, Some(key), &value, shadow);
+        }
+        Ok(value)
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.remove(table_name, key).await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self.primary.iter(table_name).await?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.iter(table_name).await;
+            self.sampler
+                .compare_unordered("iter", table_name, None, &entries, shadow);
+        }
+        Ok(entries)
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        self.primary.table_names().await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.primary.create_table(table_name).await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        self.primary.delete_table(table_name).await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self.primary.iter_from_prefix(table_name, prefix).await?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.iter_from_prefix(table_name, prefix).await;
+            self.sampler.compare_unordered(
+                "iter_from_prefix",
+                table_name,
+                Some(prefix),
+                &entries,
+                shadow,
+            );
+        }
+        Ok(entries)
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        let entries = self
+            .primary
+            .iter_from_range(table_name, start_key, end_key)
+            .await?;
+        if self.sampler.sample() {
+            let shadow = self
+                .shadow
+                .iter_from_range(table_name, start_key, end_key)
+                .await;
+            self.sampler.compare_unordered(
+                "iter_from_range",
+                table_name,
+                Some(start_key),
+                &entries,
+                shadow,
+            );
+        }
+        Ok(entries)
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        let contains_key = self.primary.contains_key(table_name, key).await?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.contains_key(table_name, key).await;
+            self.sampler
+                .compare("contains_key", table_name, Some(key), &contains_key, shadow);
+        }
+        Ok(contains_key)
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        let keys = self.primary.keys(table_name).await?;
+        if self.sampler.sample() {
+            let shadow = self.shadow.keys(table_name).await;
+            self.sampler
+                .compare_unordered("keys", table_name, None, &keys, shadow);
+        }
+        Ok(keys)
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        self.primary.values(table_name).await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        self.primary.len(table_name).await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        self.primary.is_empty(table_name).await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        self.primary.clear().await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        self.primary.swap_tables(table_a, table_b).await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        self.primary.ensure_table_with(table_name, init).await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        self.primary
+            .compare_and_swap(table_name, key, expected, new)
+            .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        self.primary.checksum(table_name, key).await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        self.primary.get_range(table_name, key, range).await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        self.primary.apply_batch(batch).await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        self.primary.flush().await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        self.primary.compact().await
+    }
+}
+
+struct Sampler {
+    sample_every: u64,
+    reads: AtomicU64,
+    mismatches: AtomicU64,
+    on_mismatch: Box<dyn Fn(&ShadowMismatch) + Send + Sync>,
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("sample_every", &self.sample_every)
+            .field("reads", &self.reads)
+            .field("mismatches", &self.mismatches)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sampler {
+    fn new(
+        sample_every: u64,
+        on_mismatch: impl Fn(&ShadowMismatch) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            sample_every,
+            reads: AtomicU64::new(0),
+            mismatches: AtomicU64::new(0),
+            on_mismatch: Box::new(on_mismatch),
+        }
+    }
+
+    fn sample(&self) -> bool {
+        self.sample_every != 0
+            && self
+                .reads
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(self.sample_every)
+    }
+
+    fn compare<V: PartialEq>(
+        &self,
+        operation: &'static str,
+        table_name: &str,
+        key: Option<&str>,
+        primary: &V,
+        shadow: Result<V, io::Error>,
+    ) {
+        if shadow.is_ok_and(|shadow| shadow == *primary) {
+            return;
+        }
+        self.mismatches.fetch_add(1, Ordering::Relaxed);
+        (self.on_mismatch)(&ShadowMismatch {
+            operation,
+            table_name: table_name.to_string(),
+            key: key.map(str::to_string),
+        });
+    }
+
+    fn compare_unordered<V: Ord + Clone>(
+        &self,
+        operation: &'static str,
+        table_name: &str,
+        key: Option<&str>,
+        primary: &[V],
+        shadow: Result<Vec<V>, io::Error>,
+    ) {
+        let mut primary = primary.to_vec();
+        primary.sort();
+        let shadow = shadow.map(|mut shadow| {
+            shadow.sort();
+            shadow
+        });
+        self.compare(operation, table_name, key, &primary, shadow);
+    }
+}
@@ -0,0 +1,463 @@
+use alloc::format;
+
+use crate::io::{self, Read, Write};
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use crate::{meta::META_TABLE, CompareAndSwapError, KeyValueDB, KeyValueDBNumeric, WriteBatch};
+#[cfg(feature = "async")]
+use crate::{AsyncKeyValueDB, AsyncKeyValueDBNumeric};
+
+const MAGIC: &[u8; 8] = b"KVSNAP\0\0";
+const FORMAT_VERSION: u8 = 1;
+const TABLE_TAG: u8 = 1;
+const END_TAG: u8 = 0;
+const READ_CHUNK: usize = 64 * 1024;
+
+/// The prefix of the tables holding named snapshots. The snapshot `name` of
+/// table `table` is stored in `__keyvalue_snapshot::table::name`.
+pub const SNAPSHOT_TABLE_PREFIX: &str = "__keyvalue_snapshot::";
+
+const SNAPSHOT_KEY_PREFIX: &str = "snapshot::";
+/// The prefix of the keys in [`META_TABLE`] counting the snapshots taken of
+/// each table.
+const VERSION_KEY_PREFIX: &str = "snapshot_version::";
+/// The listing of a snapshot whose name is claimed but whose copy is not
+/// written yet.
+const PENDING: &[u8] = &[];
+
+/// Writes every table of `db` to `writer` and returns the number of entries
+/// written.
+///
+/// The format is the magic `KVSNAP\0\0` and a version byte, followed by one
+/// record per table and a final `0` byte. A table record is a `1` byte, the
+/// table name, the number of entries as a little-endian `u64` and then each
+/// key and value. Names and keys are prefixed by their length as a
+/// little-endian `u32`, values by their length as a little-endian `u64`.
+pub fn export_snapshot(db: &dyn KeyValueDB, mut writer: impl Write) -> Result<u64, io::Error> {
+    write_header(&mut writer)?;
+    let mut count = 0;
+    for table_name in db.table_names()? {
+        count += write_table(&mut writer, &table_name, db.iter(&table_name)?)?;
+    }
+    writer.write_all(&[END_TAG])?;
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Inserts every entry of a snapshot written by `export_snapshot` into `db`,
+/// overwriting existing keys, and returns the number of entries imported.
+pub fn import_snapshot(db: &dyn KeyValueDB, mut reader: impl Read) -> Result<u64, io::Error> {
+    read_header(&mut reader)?;
+    let mut count = 0;
+    while let Some((table_name, len)) = read_table_header(&mut reader)? {
+        for _ in 0..len {
+            let (key, value) = read_entry(&mut reader)?;
+            db.insert(&table_name, &key, &value)?;
+        }
+        count += len;
+    }
+    Ok(count)
+}
+
+#[cfg(feature = "async")]
+pub async fn export_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    mut writer: impl Write,
+) -> Result<u64, io::Error> {
+    write_header(&mut writer)?;
+    let mut count = 0;
+    for table_name in db.table_names().await? {
+        count += write_table(&mut writer, &table_name, db.iter(&table_name).await?)?;
+    }
+    writer.write_all(&[END_TAG])?;
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(feature = "async")]
+pub async fn import_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    mut reader: impl Read,
+) -> Result<u64, io::Error> {
+    read_header(&mut reader)?;
+    let mut count = 0;
+    while let Some((table_name, len)) = read_table_header(&mut reader)? {
+        for _ in 0..len {
+            let (key, value) = read_entry(&mut reader)?;
+            db.insert(&table_name, &key, &value).await?;
+        }
+        count += len;
+    }
+    Ok(count)
+}
+
+/// A copy of a table taken by [`create_named_snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedSnapshot {
+    pub name: String,
+    /// Increases with every snapshot of the table, so snapshots can be
+    /// ordered by the time they were taken. It comes from a counter of the
+    /// table incremented with `compare_and_swap`, so concurrent snapshots get
+    /// distinct versions and the versions of deleted ones are not reused.
+    pub version: u64,
+}
+
+/// Copies `table_name` into a snapshot that can be read or restored later
+/// by `name`. Snapshots are listed in [`META_TABLE`] and stored as regular
+/// tables prefixed by [`SNAPSHOT_TABLE_PREFIX`], so the copy is a full one
+/// and is included in `export_snapshot`.
+///
+/// The name is first claimed with `compare_and_swap`, so of concurrent calls
+/// with the same name only one copies the table. The copy and its listing
+/// are then written in one `apply_batch`, so they are atomic on the backends
+/// that apply batches atomically. Fails with `AlreadyExists` if the snapshot
+/// exists or is being created, and with `InvalidInput` if `name` is empty or
+/// contains `::`.
+pub fn create_named_snapshot(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<NamedSnapshot, io::Error> {
+    check_name(name)?;
+    let meta_key = meta_key(table_name, name);
+    claim(db.compare_and_swap(META_TABLE, &meta_key, None, Some(PENDING)))?;
+    let created = (|| {
+        let snapshot = NamedSnapshot {
+            name: name.into(),
+            version: KeyValueDBNumeric::increment_u64(db, META_TABLE, &version_key(table_name), 1)?,
+        };
+        // Clears what a failed attempt may have left behind.
+        db.delete_table(&snapshot_table(table_name, name))?;
+        db.apply_batch(snapshot_batch(table_name, &snapshot, db.iter(table_name)?))?;
+        Ok(snapshot)
+    })();
+    if created.is_err() {
+        // Releases the name. Failing to is not worth hiding the first error.
+        let _ = db.compare_and_swap(META_TABLE, &meta_key, Some(PENDING), None);
+    }
+    created
+}
+
+/// Returns the entries of `table_name` in the snapshot `name`.
+#[allow(clippy::type_complexity)]
+pub fn read_snapshot(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+    if !is_listed(db.get(META_TABLE, &meta_key(table_name, name))?) {
+        return Err(not_found());
+    }
+    db.iter(&snapshot_table(table_name, name))
+}
+
+/// Returns the snapshots of `table_name`, oldest first.
+pub fn list_snapshots(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+) -> Result<Vec<NamedSnapshot>, io::Error> {
+    parse_snapshots(
+        table_name,
+        db.iter_from_prefix(META_TABLE, &meta_prefix(table_name))?,
+    )
+}
+
+/// Replaces the entries of `table_name` with those of the snapshot `name`.
+/// The table is deleted first, so this is not atomic.
+pub fn restore_snapshot(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<(), io::Error> {
+    let entries = read_snapshot(db, table_name, name)?;
+    db.delete_table(table_name)?;
+    db.apply_batch(restore_batch(table_name, entries))
+}
+
+/// Deletes the snapshot `name` of `table_name`, if it exists.
+pub fn delete_snapshot(db: &dyn KeyValueDB, table_name: &str, name: &str) -> Result<(), io::Error> {
+    db.remove(META_TABLE, &meta_key(table_name, name))?;
+    db.delete_table(&snapshot_table(table_name, name))
+}
+
+#[cfg(feature = "async")]
+pub async fn create_named_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<NamedSnapshot, io::Error> {
+    check_name(name)?;
+    let meta_key = meta_key(table_name, name);
+    claim(
+        db.compare_and_swap(META_TABLE, &meta_key, None, Some(PENDING))
+            .await,
+    )?;
+    let created = async {
+        let snapshot = NamedSnapshot {
+            name: name.into(),
+            version: AsyncKeyValueDBNumeric::increment_u64(
+                db,
+                META_TABLE,
+                &version_key(table_name),
+                1,
+            )
+            .await?,
+        };
+        db.delete_table(&snapshot_table(table_name, name)).await?;
+        db.apply_batch(snapshot_batch(
+            table_name,
+            &snapshot,
+            db.iter(table_name).await?,
+        ))
+        .await?;
+        Ok(snapshot)
+    }
+    .await;
+    if created.is_err() {
+        let _ = db
+            .compare_and_swap(META_TABLE, &meta_key, Some(PENDING), None)
+            .await;
+    }
+    created
+}
+
+#[cfg(feature = "async")]
+#[allow(clippy::type_complexity)]
+pub async fn read_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+    if !is_listed(db.get(META_TABLE, &meta_key(table_name, name)).await?) {
+        return Err(not_found());
+    }
+    db.iter(&snapshot_table(table_name, name)).await
+}
+
+#[cfg(feature = "async")]
+pub async fn list_snapshots_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+) -> Result<Vec<NamedSnapshot>, io::Error> {
+    parse_snapshots(
+        table_name,
+        db.iter_from_prefix(META_TABLE, &meta_prefix(table_name))
+            .await?,
+    )
+}
+
+#[cfg(feature = "async")]
+pub async fn restore_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<(), io::Error> {
+    let entries = read_snapshot_async(db, table_name, name).await?;
+    db.delete_table(table_name).await?;
+    db.apply_batch(restore_batch(table_name, entries)).await
+}
+
+#[cfg(feature = "async")]
+pub async fn delete_snapshot_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    name: &str,
+) -> Result<(), io::Error> {
+    db.remove(META_TABLE, &meta_key(table_name, name)).await?;
+    db.delete_table(&snapshot_table(table_name, name)).await
+}
+
+fn check_name(name: &str) -> Result<(), io::Error> {
+    if name.is_empty() || name.contains("::") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "snapshot names must be non-empty and cannot contain `::`",
+        ));
+    }
+    Ok(())
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "snapshot not found")
+}
+
+fn snapshot_table(table_name: &str, name: &str) -> String {
+    format!("{SNAPSHOT_TABLE_PREFIX}{table_name}::{name}")
+}
+
+fn meta_prefix(table_name: &str) -> String {
+    format!("{SNAPSHOT_KEY_PREFIX}{table_name}::")
+}
+
+fn meta_key(table_name: &str, name: &str) -> String {
+    format!("{}{}", meta_prefix(table_name), name)
+}
+
+fn version_key(table_name: &str) -> String {
+    format!("{VERSION_KEY_PREFIX}{table_name}")
+}
+
+/// Turns a failed claim of a snapshot name into `AlreadyExists`.
+fn claim(claimed: Result<(), CompareAndSwapError>) -> Result<(), io::Error> {
+    match claimed {
+        Ok(()) => Ok(()),
+        Err(CompareAndSwapError::Mismatch { .. }) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "snapshot already exists",
+        )),
+        Err(CompareAndSwapError::Io(e)) => Err(e),
+    }
+}
+
+/// Whether a finished snapshot is listed under the key whose value is
+/// `listing`.
+fn is_listed(listing: Option<Vec<u8>>) -> bool {
+    listing.is_some_and(|listing| listing != PENDING)
+}
+
+fn snapshot_batch(
+    table_name: &str,
+    snapshot: &NamedSnapshot,
+    entries: Vec<(String, Vec<u8>)>,
+) -> WriteBatch {
+    let snapshot_table = snapshot_table(table_name, &snapshot.name);
+    let mut batch = WriteBatch::new();
+    for (key, value) in entries {
+        batch.insert(&snapshot_table, &key, &value);
+    }
+    batch.insert(
+        META_TABLE,
+        &meta_key(table_name, &snapshot.name),
+        &snapshot.version.to_le_bytes(),
+    );
+    batch
+}
+
+fn restore_batch(table_name: &str, entries: Vec<(String, Vec<u8>)>) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    for (key, value) in entries {
+        batch.insert(table_name, &key, &value);
+    }
+    batch
+}
+
+/// Skips the snapshots of tables whose name extends `table_name` with `::`,
+/// which share the prefix of its keys.
+fn parse_snapshots(
+    table_name: &str,
+    entries: Vec<(String, Vec<u8>)>,
+) -> Result<Vec<NamedSnapshot>, io::Error> {
+    let prefix_len = meta_prefix(table_name).len();
+    let mut snapshots = Vec::new();
+    for (key, value) in entries {
+        let name = &key[prefix_len..];
+        if name.contains("::") || value == PENDING {
+            continue;
+        }
+        let version = <[u8; 8]>::try_from(value.as_slice())
+            .map(u64::from_le_bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid snapshot version"))?;
+        snapshots.push(NamedSnapshot {
+            name: name.into(),
+            version,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.version);
+    Ok(snapshots)
+}
+
+fn write_header(writer: &mut impl Write) -> Result<(), io::Error> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[FORMAT_VERSION])
+}
+
+fn write_table(
+    writer: &mut impl Write,
+    table_name: &str,
+    entries: Vec<(String, Vec<u8>)>,
+) -> Result<u64, io::Error> {
+    writer.write_all(&[TABLE_TAG])?;
+    write_str(writer, table_name)?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+    for (key, value) in &entries {
+        write_str(writer, key)?;
+        writer.write_all(&(value.len() as u64).to_le_bytes())?;
+        writer.write_all(value)?;
+    }
+    Ok(entries.len() as u64)
+}
+
+fn write_str(writer: &mut impl Write, value: &str) -> Result<(), io::Error> {
+    let len = u32::try_from(value.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name or key too long"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_header(reader: &mut impl Read) -> Result<(), io::Error> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a keyvalue snapshot",
+        ));
+    }
+    if read_array::<1>(reader)?[0] != FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported snapshot version",
+        ));
+    }
+    Ok(())
+}
+
+fn read_table_header(reader: &mut impl Read) -> Result<Option<(String, u64)>, io::Error> {
+    match read_array::<1>(reader)?[0] {
+        END_TAG => Ok(None),
+        TABLE_TAG => {
+            let table_name = read_string(reader)?;
+            let len = u64::from_le_bytes(read_array(reader)?);
+            Ok(Some((table_name, len)))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid snapshot record",
+        )),
+    }
+}
+
+fn read_entry(reader: &mut impl Read) -> Result<(String, Vec<u8>), io::Error> {
+    let key = read_string(reader)?;
+    let len = u64::from_le_bytes(read_array(reader)?);
+    let value = read_bytes(reader, len)?;
+    Ok((key, value))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], io::Error> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, io::Error> {
+    let len = u32::from_le_bytes(read_array(reader)?);
+    String::from_utf8(read_bytes(reader, len.into())?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8 in snapshot"))
+}
+
+/// Reads `len` bytes, growing the buffer as data arrives so that a corrupt
+/// length cannot trigger a huge allocation up front.
+fn read_bytes(reader: &mut impl Read, len: u64) -> Result<Vec<u8>, io::Error> {
+    let mut bytes = Vec::new();
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(READ_CHUNK as u64) as usize;
+        let start = bytes.len();
+        bytes.resize(start + chunk, 0);
+        reader.read_exact(&mut bytes[start..])?;
+        remaining -= chunk as u64;
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,193 @@
+use crate::io;
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::KeyValueDB;
+
+use xxhash_rust::xxh3::xxh3_64;
+
+/// The number of values listed in [`DedupeReport::most_duplicated`].
+pub const MOST_DUPLICATED_LEN: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixStats {
+    pub prefix: String,
+    pub count: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+/// Aggregates the entries of `table_name` by key prefix, sorted by prefix.
+///
+/// With a `delimiter`, the prefix of a key is made of its first `depth`
+/// delimited segments (including the trailing delimiter); otherwise it is
+/// made of its first `depth` characters. Keys shorter than that are their
+/// own prefix.
+pub fn prefix_stats(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+    delimiter: Option<char>,
+    depth: usize,
+) -> Result<Vec<PrefixStats>, io::Error> {
+    Ok(aggregate(db.iter(table_name)?, delimiter, depth))
+}
+
+#[cfg(feature = "async")]
+pub async fn prefix_stats_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    delimiter: Option<char>,
+    depth: usize,
+) -> Result<Vec<PrefixStats>, io::Error> {
+    Ok(aggregate(db.iter(table_name).await?, delimiter, depth))
+}
+
+fn aggregate(
+    entries: Vec<(String, Vec<u8>)>,
+    delimiter: Option<char>,
+    depth: usize,
+) -> Vec<PrefixStats> {
+    let mut stats = BTreeMap::<&str, PrefixStats>::new();
+    for (key, value) in &entries {
+        let prefix = key_prefix(key, delimiter, depth);
+        let entry = stats.entry(prefix).or_default();
+        entry.count += 1;
+        entry.key_bytes += key.len() as u64;
+        entry.value_bytes += value.len() as u64;
+    }
+    stats
+        .into_iter()
+        .map(|(prefix, stats)| PrefixStats {
+            prefix: prefix.into(),
+            ..stats
+        })
+        .collect()
+}
+
+fn key_prefix(key: &str, delimiter: Option<char>, depth: usize) -> &str {
+    let end = match delimiter {
+        Some(delimiter) => key
+            .match_indices(delimiter)
+            .nth(depth.saturating_sub(1))
+            .filter(|_| depth > 0)
+            .map(|(index, _)| index + delimiter.len_utf8()),
+        None => key.char_indices().nth(depth).map(|(index, _)| index),
+    };
+    &key[..end.unwrap_or(if depth == 0 { 0 } else { key.len() })]
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateValue {
+    /// The 64-bit XXH3 hash of the value, as returned by `checksum`.
+    pub checksum: u64,
+    pub size: u64,
+    /// The number of keys holding the value.
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupeReport {
+    pub entries: u64,
+    pub distinct_values: u64,
+    pub value_bytes: u64,
+    /// The bytes that storing every distinct value once would save.
+    pub duplicate_bytes: u64,
+    /// The values held by more than one key that take the most space in
+    /// their copies, largest first.
+    pub most_duplicated: Vec<DuplicateValue>,
+}
+
+/// Counts how many values of `table_name` are identical, to tell whether
+/// storing each distinct value once would be worth it.
+pub fn dedupe_report(db: &dyn KeyValueDB, table_name: &str) -> Result<DedupeReport, io::Error> {
+    Ok(dedupe(db.iter(table_name)?))
+}
+
+#[cfg(feature = "async")]
+pub async fn dedupe_report_async(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+) -> Result<DedupeReport, io::Error> {
+    Ok(dedupe(db.iter(table_name).await?))
+}
+
+fn dedupe(entries: Vec<(String, Vec<u8>)>) -> DedupeReport {
+    let mut counts = BTreeMap::<&[u8], u64>::new();
+    for (_, value) in &entries {
+        *counts.entry(value).or_default() += 1;
+    }
+    let value_bytes = entries.iter().map(|(_, value)| value.len() as u64).sum();
+    let distinct_bytes = counts.keys().map(|value| value.len() as u64).sum::<u64>();
+
+    let mut most_duplicated = counts
+        .iter()
+        .filter(|(_, count)| **count > 1)
+        .map(|(value, count)| DuplicateValue {
+            checksum: xxh3_64(value),
+            size: value.len() as u64,
+            count: *count,
+        })
+        .collect::<Vec<_>>();
+    most_duplicated.sort_by_key(|value| core::cmp::Reverse(value.size * (value.count - 1)));
+    most_duplicated.truncate(MOST_DUPLICATED_LEN);
+
+    DedupeReport {
+        entries: entries.len() as u64,
+        distinct_values: counts.len() as u64,
+        value_bytes,
+        duplicate_bytes: value_bytes - distinct_bytes,
+        most_duplicated,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_prefix_by_delimiter_and_length() {
+        assert_eq!(key_prefix("a/b/c", Some('/'), 1), "a/");
+        assert_eq!(key_prefix("a/b/c", Some('/'), 2), "a/b/");
+        assert_eq!(key_prefix("a/b/c", Some('/'), 3), "a/b/c");
+        assert_eq!(key_prefix("a/b/c", Some('/'), 0), "");
+        assert_eq!(key_prefix("abc", None, 2), "ab");
+        assert_eq!(key_prefix("abc", None, 5), "abc");
+        assert_eq!(key_prefix("abc", None, 0), "");
+    }
+
+    #[test]
+    fn dedupe_counts_identical_values() {
+        let entry = |key: &str, value: &[u8]| (String::from(key), value.to_vec());
+        let report = dedupe(vec![
+            entry("a", b"config"),
+            entry("b", b"config"),
+            entry("c", b"config"),
+            entry("d", b"xy"),
+            entry("e", b"xy"),
+            entry("f", b"unique"),
+        ]);
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.distinct_values, 3);
+        assert_eq!(report.value_bytes, 28);
+        assert_eq!(report.duplicate_bytes, 14);
+        assert_eq!(
+            report.most_duplicated,
+            vec![
+                DuplicateValue {
+                    checksum: xxh3_64(b"config"),
+                    size: 6,
+                    count: 3,
+                },
+                DuplicateValue {
+                    checksum: xxh3_64(b"xy"),
+                    size: 2,
+                    count: 2,
+                },
+            ]
+        );
+        assert_eq!(dedupe(Vec::new()), DedupeReport::default());
+    }
+}
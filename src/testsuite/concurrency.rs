@@ -0,0 +1,493 @@
+//! Runs a mixed workload from several threads, or tasks for async backends,
+//! and checks the guarantees documented on [`KeyValueDB`]:
+//!
+//! - every worker has a private table, whose contents must always be exactly
+//!   what the worker wrote, whatever the others do;
+//! - the workers write their own keys in a shared table, whose values must
+//!   never be torn or mixed up between keys;
+//! - the workers insert into and delete a churn table at the same time,
+//!   which must never fail or return entries that were not written;
+//! - the workers increment a counter with `compare_and_swap`, which must end
+//!   up equal to the number of successful swaps, when the backend supports
+//!   it.
+
+use std::{collections::BTreeMap, io};
+
+use super::Rng;
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB};
+
+const PRIVATE_TABLE: &str = "__keyvalue_concurrency_private";
+const SHARED_TABLE: &str = "__keyvalue_concurrency_shared";
+const CHURN_TABLE: &str = "__keyvalue_concurrency_churn";
+const COUNTER_TABLE: &str = "__keyvalue_concurrency_counter";
+const COUNTER_KEY: &str = "counter";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub workers: usize,
+    /// The number of operations each worker performs.
+    pub operations: usize,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            operations: 200,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    pub operations: u64,
+    /// The final value of the counter, or `None` if the backend does not
+    /// support `compare_and_swap`.
+    pub increments: Option<u64>,
+}
+
+enum Action {
+    PrivateInsert { key: String, value: Vec<u8> },
+    PrivateGet { key: String },
+    PrivateRemove { key: String },
+    PrivateIter,
+    PrivateDelete,
+    SharedInsert { key: String, value: Vec<u8> },
+    SharedGet { key: String },
+    SharedIter,
+    ChurnInsert { key: String, value: Vec<u8> },
+    ChurnIter,
+    ChurnDelete,
+    Increment,
+}
+
+enum Answer {
+    Unit,
+    Value(Option<Vec<u8>>),
+    Entries(Vec<(String, Vec<u8>)>),
+    /// Whether the counter was incremented, or `None` if `compare_and_swap`
+    /// is unsupported.
+    Swapped(Option<bool>),
+}
+
+struct Worker {
+    id: usize,
+    workers: usize,
+    rng: Rng,
+    private: BTreeMap<String, Vec<u8>>,
+    shared: BTreeMap<String, Vec<u8>>,
+    churn: BTreeMap<String, Vec<u8>>,
+    increments: u64,
+    compare_and_swap: bool,
+}
+
+impl Worker {
+    fn new(id: usize, config: &Config) -> Self {
+        Self {
+            id,
+            workers: config.workers,
+            rng: Rng::new(config.seed.wrapping_add(id as u64)),
+            private: BTreeMap::new(),
+            shared: BTreeMap::new(),
+            churn: BTreeMap::new(),
+            increments: 0,
+            compare_and_swap: true,
+        }
+    }
+
+    fn private_table(&self) -> String {
+        format!("{}_{}", PRIVATE_TABLE, self.id)
+    }
+
+    /// Keys in the shared tables are prefixed with the worker id, and their
+    /// values start with the key, so a value read under the wrong key or
+    /// partially written is detected.
+    fn shared_entry(&mut self, worker: usize) -> (String, Vec<u8>) {
+        let key = format!("{}/{}", worker, self.rng.key());
+        let mut value = format!("{}=", key).into_bytes();
+        value.extend(self.rng.value());
+        (key, value)
+    }
+
+    fn next_action(&mut self) -> Action {
+        match self.rng.below(24) {
+            0..=3 => Action::PrivateInsert {
+                key: self.rng.key(),
+                value: self.rng.value(),
+            },
+            4..=5 => Action::PrivateGet {
+                key: self.rng.key(),
+            },
+            6 => Action::PrivateRemove {
+                key: self.rng.key(),
+            },
+            7 => Action::PrivateIter,
+            8 => Action::PrivateDelete,
+            9..=11 => {
+                let (key, value) = self.shared_entry(self.id);
+                Action::SharedInsert { key, value }
+            }
+            12..=13 => {
+                let worker = self.rng.below(self.workers);
+                let (key, _) = self.shared_entry(worker);
+                Action::SharedGet { key }
+            }
+            14 => Action::SharedIter,
+            15..=17 => {
+                let (key, value) = self.shared_entry(self.id);
+                Action::ChurnInsert { key, value }
+            }
+            18 => Action::ChurnIter,
+            19 => Action::ChurnDelete,
+            _ if self.compare_and_swap => Action::Increment,
+            _ => Action::SharedIter,
+        }
+    }
+
+    fn check(&mut self, action: &Action, answer: Answer) -> Result<(), String> {
+        match (action, answer) {
+            (Action::PrivateInsert { key, value }, Answer::Value(old)) => {
+                expect(old, self.private.insert(key.clone(), value.clone()))
+            }
+            (Action::PrivateGet { key }, Answer::Value(value)) => {
+                expect(value, self.private.get(key).cloned())
+            }
+            (Action::PrivateRemove { key }, Answer::Value(old)) => {
+                expect(old, self.private.remove(key))
+            }
+            (Action::PrivateIter, Answer::Entries(entries)) => {
+                expect(entries, model_entries(&self.private))
+            }
+            (Action::PrivateDelete, Answer::Unit) => {
+                self.private.clear();
+                Ok(())
+            }
+            (Action::SharedInsert { key, value }, Answer::Value(old)) => {
+                expect(old, self.shared.insert(key.clone(), value.clone()))
+            }
+            (Action::SharedGet { key }, Answer::Value(value)) => {
+                if key.starts_with(&format!("{}/", self.id)) {
+                    expect(value, self.shared.get(key).cloned())
+                } else {
+                    check_entries(value.map(|value| (key.clone(), value)))
+                }
+            }
+            (Action::SharedIter, Answer::Entries(entries)) => {
+                let prefix = format!("{}/", self.id);
+                let own = entries
+                    .iter()
+                    .filter(|(key, _)| key.starts_with(&prefix))
+                    .cloned()
+                    .collect();
+                check_entries(entries)?;
+                expect(own, model_entries(&self.shared))
+            }
+            (Action::ChurnInsert { key, value }, Answer::Value(old)) => {
+                let last = self.churn.insert(key.clone(), value.clone());
+                if old.is_some() && old != last {
+                    return Err(format!(
+                        "{} in the churn table was {:?}, but was last set to {:?}",
+                        key, old, last
+                    ));
+                }
+                Ok(())
+            }
+            (Action::ChurnIter, Answer::Entries(entries)) => check_entries(entries),
+            (Action::ChurnDelete, Answer::Unit) => Ok(()),
+            (Action::Increment, Answer::Swapped(swapped)) => {
+                match swapped {
+                    Some(true) => self.increments += 1,
+                    Some(false) => {}
+                    None => self.compare_and_swap = false,
+                }
+                Ok(())
+            }
+            _ => unreachable!("answer does not match the action"),
+        }
+    }
+}
+
+fn expect<T: PartialEq + std::fmt::Debug>(actual: T, expected: T) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("expected {:?}, got {:?}", expected, actual))
+    }
+}
+
+fn model_entries(model: &BTreeMap<String, Vec<u8>>) -> Vec<(String, Vec<u8>)> {
+    model
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn check_entries(entries: impl IntoIterator<Item = (String, Vec<u8>)>) -> Result<(), String> {
+    for (key, value) in entries {
+        if !value.starts_with(format!("{}=", key).as_bytes()) {
+            return Err(format!("{} has a foreign or torn value {:?}", key, value));
+        }
+    }
+    Ok(())
+}
+
+fn sorted(mut entries: Vec<(String, Vec<u8>)>) -> Answer {
+    entries.sort();
+    Answer::Entries(entries)
+}
+
+fn counter(value: Option<&[u8]>) -> Result<u64, io::Error> {
+    match value {
+        Some(value) => <[u8; 8]>::try_from(value)
+            .map(u64::from_le_bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid counter value")),
+        None => Ok(0),
+    }
+}
+
+fn swapped(result: Result<(), CompareAndSwapError>) -> Result<Answer, io::Error> {
+    match result {
+        Ok(()) => Ok(Answer::Swapped(Some(true))),
+        Err(CompareAndSwapError::Mismatch { .. }) => Ok(Answer::Swapped(Some(false))),
+        Err(CompareAndSwapError::Io(e)) if e.kind() == io::ErrorKind::Unsupported => {
+            Ok(Answer::Swapped(None))
+        }
+        Err(CompareAndSwapError::Io(e)) => Err(e),
+    }
+}
+
+fn violation(worker: usize, operation: usize, detail: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("worker {}, operation {}: {}", worker, operation, detail),
+    )
+}
+
+fn tables(workers: &[Worker]) -> Vec<String> {
+    let mut tables = workers
+        .iter()
+        .map(Worker::private_table)
+        .collect::<Vec<_>>();
+    tables.extend([SHARED_TABLE, CHURN_TABLE, COUNTER_TABLE].map(String::from));
+    tables
+}
+
+/// Checks the final contents of the private and shared tables and the
+/// counter against the workers' models.
+fn check_final(
+    workers: &[Worker],
+    private: Vec<Vec<(String, Vec<u8>)>>,
+    shared: Vec<(String, Vec<u8>)>,
+    counter: u64,
+    operations: u64,
+) -> Result<Report, io::Error> {
+    let invalid = |detail| io::Error::new(io::ErrorKind::InvalidData, detail);
+    for (worker, entries) in workers.iter().zip(private) {
+        expect(entries, model_entries(&worker.private))
+            .map_err(|e| invalid(format!("private table of worker {}: {}", worker.id, e)))?;
+    }
+    let mut expected = workers
+        .iter()
+        .flat_map(|worker| model_entries(&worker.shared))
+        .collect::<Vec<_>>();
+    expected.sort();
+    expect(shared, expected).map_err(|e| invalid(format!("shared table: {}", e)))?;
+
+    let compare_and_swap = workers.iter().all(|worker| worker.compare_and_swap);
+    let increments = workers.iter().map(|worker| worker.increments).sum::<u64>();
+    if compare_and_swap {
+        expect(counter, increments).map_err(|e| invalid(format!("counter: {}", e)))?;
+    }
+    Ok(Report {
+        operations,
+        increments: compare_and_swap.then_some(counter),
+    })
+}
+
+fn perform(db: &dyn KeyValueDB, worker: &Worker, action: &Action) -> Result<Answer, io::Error> {
+    let private = worker.private_table();
+    Ok(match action {
+        Action::PrivateInsert { key, value } => Answer::Value(db.insert(&private, key, value)?),
+        Action::PrivateGet { key } => Answer::Value(db.get(&private, key)?),
+        Action::PrivateRemove { key } => Answer::Value(db.remove(&private, key)?),
+        Action::PrivateIter => sorted(db.iter(&private)?),
+        Action::PrivateDelete => {
+            db.delete_table(&private)?;
+            Answer::Unit
+        }
+        Action::SharedInsert { key, value } => {
+            Answer::Value(db.insert(SHARED_TABLE, key, value)?)
+        }
+        Action::SharedGet { key } => Answer::Value(db.get(SHARED_TABLE, key)?),
+        Action::SharedIter => sorted(db.iter(SHARED_TABLE)?),
+        Action::ChurnInsert { key, value } => Answer::Value(db.insert(CHURN_TABLE, key, value)?),
+        Action::ChurnIter => sorted(db.iter(CHURN_TABLE)?),
+        Action::ChurnDelete => {
+            db.delete_table(CHURN_TABLE)?;
+            Answer::Unit
+        }
+        Action::Increment => {
+            let current = db.get(COUNTER_TABLE, COUNTER_KEY)?;
+            let next = (counter(current.as_deref())? + 1).to_le_bytes();
+            swapped(db.compare_and_swap(
+                COUNTER_TABLE,
+                COUNTER_KEY,
+                current.as_deref(),
+                Some(&next),
+            ))?
+        }
+    })
+}
+
+#[cfg(feature = "async")]
+async fn perform_async(
+    db: &dyn AsyncKeyValueDB,
+    worker: &Worker,
+    action: &Action,
+) -> Result<Answer, io::Error> {
+    let private = worker.private_table();
+    Ok(match action {
+        Action::PrivateInsert { key, value } => {
+            Answer::Value(db.insert(&private, key, value).await?)
+        }
+        Action::PrivateGet { key } => Answer::Value(db.get(&private, key).await?),
+        Action::PrivateRemove { key } => Answer::Value(db.remove(&private, key).await?),
+        Action::PrivateIter => sorted(db.iter(&private).await?),
+        Action::PrivateDelete => {
+            db.delete_table(&private).await?;
+            Answer::Unit
+        }
+        Action::SharedInsert { key, value } => {
+            Answer::Value(db.insert(SHARED_TABLE, key, value).await?)
+        }
+        Action::SharedGet { key } => Answer::Value(db.get(SHARED_TABLE, key).await?),
+        Action::SharedIter => sorted(db.iter(SHARED_TABLE).await?),
+        Action::ChurnInsert { key, value } => {
+            Answer::Value(db.insert(CHURN_TABLE, key, value).await?)
+        }
+        Action::ChurnIter => sorted(db.iter(CHURN_TABLE).await?),
+        Action::ChurnDelete => {
+            db.delete_table(CHURN_TABLE).await?;
+            Answer::Unit
+        }
+        Action::Increment => {
+            let current = db.get(COUNTER_TABLE, COUNTER_KEY).await?;
+            let next = (counter(current.as_deref())? + 1).to_le_bytes();
+            swapped(
+                db.compare_and_swap(COUNTER_TABLE, COUNTER_KEY, current.as_deref(), Some(&next))
+                    .await,
+            )?
+        }
+    })
+}
+
+/// Runs `config.operations` operations on each of `config.workers` threads
+/// and checks the invariants after every operation and at the end. The test
+/// tables are deleted before and after the run.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(db: &dyn KeyValueDB, config: &Config) -> Result<Report, io::Error> {
+    let mut workers = (0..config.workers)
+        .map(|id| Worker::new(id, config))
+        .collect::<Vec<_>>();
+    let tables = tables(&workers);
+    for table in &tables {
+        db.delete_table(table)?;
+    }
+
+    std::thread::scope(|scope| {
+        let handles = workers
+            .iter_mut()
+            .map(|worker| {
+                scope.spawn(move || {
+                    for operation in 0..config.operations {
+                        let action = worker.next_action();
+                        let answer = perform(db, worker, &action)?;
+                        worker
+                            .check(&action, answer)
+                            .map_err(|e| violation(worker.id, operation, e))?;
+                    }
+                    Ok::<_, io::Error>(())
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("worker panicked"))
+    })?;
+
+    let private = workers
+        .iter()
+        .map(|worker| {
+            db.iter(&worker.private_table()).map(|mut entries| {
+                entries.sort();
+                entries
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut shared = db.iter(SHARED_TABLE)?;
+    shared.sort();
+    let counter = counter(db.get(COUNTER_TABLE, COUNTER_KEY)?.as_deref())?;
+    let report = check_final(
+        &workers,
+        private,
+        shared,
+        counter,
+        (config.workers * config.operations) as u64,
+    )?;
+
+    for table in &tables {
+        db.delete_table(table)?;
+    }
+    Ok(report)
+}
+
+/// Async counterpart of [`run`], with the workers running as concurrent
+/// futures on the calling task.
+#[cfg(feature = "async")]
+pub async fn run_async(db: &dyn AsyncKeyValueDB, config: &Config) -> Result<Report, io::Error> {
+    let mut workers = (0..config.workers)
+        .map(|id| Worker::new(id, config))
+        .collect::<Vec<_>>();
+    let tables = tables(&workers);
+    for table in &tables {
+        db.delete_table(table).await?;
+    }
+
+    futures::future::try_join_all(workers.iter_mut().map(|worker| async move {
+        for operation in 0..config.operations {
+            let action = worker.next_action();
+            let answer = perform_async(db, worker, &action).await?;
+            worker
+                .check(&action, answer)
+                .map_err(|e| violation(worker.id, operation, e))?;
+        }
+        Ok::<_, io::Error>(())
+    }))
+    .await?;
+
+    let mut private = Vec::with_capacity(workers.len());
+    for worker in &workers {
+        let mut entries = db.iter(&worker.private_table()).await?;
+        entries.sort();
+        private.push(entries);
+    }
+    let mut shared = db.iter(SHARED_TABLE).await?;
+    shared.sort();
+    let counter = counter(db.get(COUNTER_TABLE, COUNTER_KEY).await?.as_deref())?;
+    let report = check_final(
+        &workers,
+        private,
+        shared,
+        counter,
+        (config.workers * config.operations) as u64,
+    )?;
+
+    for table in &tables {
+        db.delete_table(table).await?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,284 @@
+//! Records the calls that concurrent threads or tasks make on one table and
+//! checks that the history is linearizable: that every call appears to take
+//! effect at a single point between its invocation and its return. A
+//! history containing `ApplyBatch` and `Iter` calls only passes if batches
+//! are atomic, so this also checks the backends that document atomic
+//! batches.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{KeyValueDB, WriteBatch};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Get {
+        key: String,
+    },
+    Insert {
+        key: String,
+        value: Vec<u8>,
+    },
+    Remove {
+        key: String,
+    },
+    /// Inserts the keys with a value and removes the others.
+    ApplyBatch {
+        ops: Vec<(String, Option<Vec<u8>>)>,
+    },
+    Iter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Return {
+    Unit,
+    Value(Option<Vec<u8>>),
+    /// Sorted by key.
+    Entries(Vec<(String, Vec<u8>)>),
+}
+
+/// A completed call. `invoked` and `returned` are ticks of the history's
+/// logical clock, so a call precedes another if it returned before the
+/// other was invoked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub process: usize,
+    pub call: Call,
+    pub ret: Return,
+    pub invoked: u64,
+    pub returned: u64,
+}
+
+/// The calls made on one table, which must be empty when recording starts.
+///
+/// Calls that fail are not recorded, so only failures that leave the table
+/// untouched keep the history meaningful.
+#[derive(Debug, Default)]
+pub struct History {
+    clock: AtomicU64,
+    events: Mutex<Vec<Event>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Performs `call` on `table_name` on behalf of `process` and records it.
+    pub fn call(
+        &self,
+        db: &dyn KeyValueDB,
+        table_name: &str,
+        process: usize,
+        call: Call,
+    ) -> Result<Return, io::Error> {
+        let invoked = self.clock.fetch_add(1, Ordering::SeqCst);
+        let ret = match &call {
+            Call::Get { key } => Return::Value(db.get(table_name, key)?),
+            Call::Insert { key, value } => Return::Value(db.insert(table_name, key, value)?),
+            Call::Remove { key } => Return::Value(db.remove(table_name, key)?),
+            Call::ApplyBatch { ops } => {
+                db.apply_batch(batch(table_name, ops))?;
+                Return::Unit
+            }
+            Call::Iter => entries(db.iter(table_name)?),
+        };
+        self.record(process, call, ret.clone(), invoked);
+        Ok(ret)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn call_async(
+        &self,
+        db: &dyn AsyncKeyValueDB,
+        table_name: &str,
+        process: usize,
+        call: Call,
+    ) -> Result<Return, io::Error> {
+        let invoked = self.clock.fetch_add(1, Ordering::SeqCst);
+        let ret = match &call {
+            Call::Get { key } => Return::Value(db.get(table_name, key).await?),
+            Call::Insert { key, value } => Return::Value(db.insert(table_name, key, value).await?),
+            Call::Remove { key } => Return::Value(db.remove(table_name, key).await?),
+            Call::ApplyBatch { ops } => {
+                db.apply_batch(batch(table_name, ops)).await?;
+                Return::Unit
+            }
+            Call::Iter => entries(db.iter(table_name).await?),
+        };
+        self.record(process, call, ret.clone(), invoked);
+        Ok(ret)
+    }
+
+    fn record(&self, process: usize, call: Call, ret: Return, invoked: u64) {
+        let returned = self.clock.fetch_add(1, Ordering::SeqCst);
+        self.events.lock().unwrap().push(Event {
+            process,
+            call,
+            ret,
+            invoked,
+            returned,
+        });
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn check(&self) -> Result<(), NotLinearizable> {
+        check(&self.events())
+    }
+}
+
+fn batch(table_name: &str, ops: &[(String, Option<Vec<u8>>)]) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    for (key, value) in ops {
+        match value {
+            Some(value) => batch.insert(table_name, key, value),
+            None => batch.remove(table_name, key),
+        };
+    }
+    batch
+}
+
+fn entries(mut entries: Vec<(String, Vec<u8>)>) -> Return {
+    entries.sort();
+    Return::Entries(entries)
+}
+
+/// No order of the events explains every return value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotLinearizable {
+    /// The longest order of events that was consistent with their return
+    /// values.
+    pub linearized: Vec<Event>,
+    /// The events that could not be added to it.
+    pub remaining: Vec<Event>,
+}
+
+impl fmt::Display for NotLinearizable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "history is not linearizable after {} events:",
+            self.linearized.len()
+        )?;
+        for event in &self.linearized {
+            writeln!(f, "  {:?}", event)?;
+        }
+        writeln!(f, "none of the remaining events fits:")?;
+        for event in &self.remaining {
+            writeln!(f, "  {:?}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NotLinearizable {}
+
+type State = BTreeMap<String, Vec<u8>>;
+
+fn apply(state: &mut State, call: &Call) -> Return {
+    match call {
+        Call::Get { key } => Return::Value(state.get(key).cloned()),
+        Call::Insert { key, value } => Return::Value(state.insert(key.clone(), value.clone())),
+        Call::Remove { key } => Return::Value(state.remove(key)),
+        Call::ApplyBatch { ops } => {
+            for (key, value) in ops {
+                match value {
+                    Some(value) => state.insert(key.clone(), value.clone()),
+                    None => state.remove(key),
+                };
+            }
+            Return::Unit
+        }
+        Call::Iter => Return::Entries(
+            state
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        ),
+    }
+}
+
+struct Search<'a> {
+    events: &'a [Event],
+    visited: HashSet<(Vec<bool>, State)>,
+    best: Vec<usize>,
+}
+
+impl Search<'_> {
+    fn linearize(&mut self, done: &mut Vec<bool>, order: &mut Vec<usize>, state: State) -> bool {
+        if order.len() == self.events.len() {
+            return true;
+        }
+        if order.len() > self.best.len() {
+            self.best = order.clone();
+        }
+        if !self.visited.insert((done.clone(), state.clone())) {
+            return false;
+        }
+        // Only events invoked before every pending event returned can come next.
+        let horizon = self
+            .events
+            .iter()
+            .zip(done.iter())
+            .filter(|(_, done)| !**done)
+            .map(|(event, _)| event.returned)
+            .min()
+            .unwrap_or(u64::MAX);
+        for (i, event) in self.events.iter().enumerate() {
+            if done[i] || event.invoked > horizon {
+                continue;
+            }
+            let mut next = state.clone();
+            if apply(&mut next, &event.call) != event.ret {
+                continue;
+            }
+            done[i] = true;
+            order.push(i);
+            if self.linearize(done, order, next) {
+                return true;
+            }
+            done[i] = false;
+            order.pop();
+        }
+        false
+    }
+}
+
+/// Searches for an order of `events` that respects their real-time order
+/// and in which every call returns what it returned in the history, starting
+/// from an empty table. The search is exponential in the number of
+/// overlapping calls, so histories should stay in the hundreds of events.
+pub fn check(events: &[Event]) -> Result<(), NotLinearizable> {
+    let mut search = Search {
+        events,
+        visited: HashSet::new(),
+        best: Vec::new(),
+    };
+    if search.linearize(
+        &mut vec![false; events.len()],
+        &mut Vec::new(),
+        State::new(),
+    ) {
+        return Ok(());
+    }
+    let linearized = search.best.iter().map(|i| events[*i].clone()).collect();
+    let remaining = (0..events.len())
+        .filter(|i| !search.best.contains(i))
+        .map(|i| events[i].clone())
+        .collect();
+    Err(NotLinearizable {
+        linearized,
+        remaining,
+    })
+}
@@ -0,0 +1,568 @@
+//! Checks for third-party backends. [`check_random`] runs seeded random
+//! operation sequences against a backend and an in-memory model of the
+//! [`KeyValueDB`] contract, shrinking any sequence on which they disagree,
+//! [`concurrency`] checks the thread-safety guarantees under a concurrent
+//! workload, and [`linearizability`] checks histories recorded from
+//! concurrent callers.
+//! [`crate::conformance`] covers the individual methods.
+
+pub mod concurrency;
+pub mod linearizability;
+
+use std::{collections::BTreeMap, fmt, io, ops::Range};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// The tables the generated operations use. They are deleted before and
+/// after every run.
+pub const TABLES: [&str; 3] = [
+    "__keyvalue_testsuite_0",
+    "__keyvalue_testsuite_1",
+    "__keyvalue_testsuite_2",
+];
+
+const KEY_CHARS: &[u8] = b"abc";
+
+/// An operation on the table `TABLES[table]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Insert {
+        table: usize,
+        key: String,
+        value: Vec<u8>,
+    },
+    Get {
+        table: usize,
+        key: String,
+    },
+    GetRange {
+        table: usize,
+        key: String,
+        range: Range<u64>,
+    },
+    Remove {
+        table: usize,
+        key: String,
+    },
+    ContainsKey {
+        table: usize,
+        key: String,
+    },
+    Iter {
+        table: usize,
+    },
+    IterFromPrefix {
+        table: usize,
+        prefix: String,
+    },
+    IterFromRange {
+        table: usize,
+        start: String,
+        end: String,
+    },
+    Len {
+        table: usize,
+    },
+    DeleteTable {
+        table: usize,
+    },
+    /// Inserts the keys with a value and removes the others.
+    ApplyBatch {
+        table: usize,
+        ops: Vec<(String, Option<Vec<u8>>)>,
+    },
+    CompareAndSwap {
+        table: usize,
+        key: String,
+        expected: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    },
+}
+
+/// What an operation returned. Entries are compared sorted by key, so
+/// unordered backends pass as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Unit,
+    Value(Option<Vec<u8>>),
+    Bool(bool),
+    Len(u64),
+    Entries(Vec<(String, Vec<u8>)>),
+    /// The result of `compare_and_swap`, with the current value on a
+    /// mismatch.
+    Swap(Result<(), Option<Vec<u8>>>),
+    /// The backend does not support the operation.
+    Unsupported,
+}
+
+/// A sequence of operations on which the backend and the model disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The seed that generated the sequence, before shrinking.
+    pub seed: Option<u64>,
+    pub ops: Vec<Op>,
+    /// The index in `ops` of the operation that failed.
+    pub step: usize,
+    pub detail: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            write!(f, "seed {}: ", seed)?;
+        }
+        writeln!(f, "step {} failed: {}", self.step, self.detail)?;
+        for (i, op) in self.ops.iter().enumerate() {
+            writeln!(f, "{:>4}: {:?}", i, op)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// A splitmix64 generator, so that a seed always produces the same
+/// operations.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn gen_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.gen_u64() % n as u64) as usize
+    }
+
+    /// Keys are short strings over a small alphabet, so operations often hit
+    /// the same keys and prefixes.
+    pub(crate) fn key(&mut self) -> String {
+        let len = 1 + self.below(3);
+        (0..len)
+            .map(|_| KEY_CHARS[self.below(KEY_CHARS.len())] as char)
+            .collect()
+    }
+
+    pub(crate) fn value(&mut self) -> Vec<u8> {
+        let len = self.below(9);
+        (0..len).map(|_| self.gen_u64() as u8).collect()
+    }
+
+    fn maybe_value(&mut self) -> Option<Vec<u8>> {
+        (self.below(4) != 0).then(|| self.value())
+    }
+}
+
+/// Generates `len` operations from `seed`. Writes are more frequent than
+/// reads, and table deletions are rare.
+pub fn random_ops(seed: u64, len: usize) -> Vec<Op> {
+    let mut rng = Rng::new(seed);
+    (0..len)
+        .map(|_| {
+            let table = rng.below(TABLES.len());
+            match rng.below(20) {
+                0..=5 => Op::Insert {
+                    table,
+                    key: rng.key(),
+                    value: rng.value(),
+                },
+                6..=7 => Op::Remove {
+                    table,
+                    key: rng.key(),
+                },
+                8..=9 => Op::Get {
+                    table,
+                    key: rng.key(),
+                },
+                10 => {
+                    let start = rng.below(10) as u64;
+                    Op::GetRange {
+                        table,
+                        key: rng.key(),
+                        range: start..start + rng.below(10) as u64,
+                    }
+                }
+                11 => Op::ContainsKey {
+                    table,
+                    key: rng.key(),
+                },
+                12 => Op::Iter { table },
+                13 => Op::IterFromPrefix {
+                    table,
+                    prefix: rng.key(),
+                },
+                14 => Op::IterFromRange {
+                    table,
+                    start: rng.key(),
+                    end: rng.key(),
+                },
+                15 => Op::Len { table },
+                16 => Op::DeleteTable { table },
+                17..=18 => Op::ApplyBatch {
+                    table,
+                    ops: (0..1 + rng.below(4))
+                        .map(|_| (rng.key(), rng.maybe_value()))
+                        .collect(),
+                },
+                _ => Op::CompareAndSwap {
+                    table,
+                    key: rng.key(),
+                    expected: rng.maybe_value(),
+                    new: rng.maybe_value(),
+                },
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Model {
+    tables: BTreeMap<usize, BTreeMap<String, Vec<u8>>>,
+}
+
+impl Model {
+    fn apply(&mut self, op: &Op) -> Outcome {
+        match op {
+            Op::Insert { table, key, value } => Outcome::Value(
+                self.tables
+                    .entry(*table)
+                    .or_default()
+                    .insert(key.clone(), value.clone()),
+            ),
+            Op::Get { table, key } => Outcome::Value(self.get(*table, key).cloned()),
+            Op::GetRange { table, key, range } => {
+                Outcome::Value(self.get(*table, key).map(|value| {
+                    value[crate::kvdb::clamp_range(range.clone(), value.len() as u64)].to_vec()
+                }))
+            }
+            Op::Remove { table, key } => Outcome::Value(
+                self.tables
+                    .get_mut(table)
+                    .and_then(|entries| entries.remove(key)),
+            ),
+            Op::ContainsKey { table, key } => Outcome::Bool(self.get(*table, key).is_some()),
+            Op::Iter { table } => self.entries(*table, |_| true),
+            Op::IterFromPrefix { table, prefix } => {
+                self.entries(*table, |key| key.starts_with(prefix.as_str()))
+            }
+            Op::IterFromRange { table, start, end } => {
+                self.entries(*table, |key| start.as_str() <= key && key < end.as_str())
+            }
+            Op::Len { table } => Outcome::Len(
+                self.tables
+                    .get(table)
+                    .map_or(0, |entries| entries.len() as u64),
+            ),
+            Op::DeleteTable { table } => {
+                self.tables.remove(table);
+                Outcome::Unit
+            }
+            Op::ApplyBatch { table, ops } => {
+                let entries = self.tables.entry(*table).or_default();
+                for (key, value) in ops {
+                    match value {
+                        Some(value) => entries.insert(key.clone(), value.clone()),
+                        None => entries.remove(key),
+                    };
+                }
+                Outcome::Unit
+            }
+            Op::CompareAndSwap {
+                table,
+                key,
+                expected,
+                new,
+            } => {
+                let current = self.get(*table, key).cloned();
+                if current != *expected {
+                    return Outcome::Swap(Err(current));
+                }
+                let entries = self.tables.entry(*table).or_default();
+                match new {
+                    Some(new) => entries.insert(key.clone(), new.clone()),
+                    None => entries.remove(key),
+                };
+                Outcome::Swap(Ok(()))
+            }
+        }
+    }
+
+    fn get(&self, table: usize, key: &str) -> Option<&Vec<u8>> {
+        self.tables.get(&table).and_then(|entries| entries.get(key))
+    }
+
+    fn entries(&self, table: usize, filter: impl Fn(&str) -> bool) -> Outcome {
+        Outcome::Entries(
+            self.tables
+                .get(&table)
+                .into_iter()
+                .flatten()
+                .filter(|(key, _)| filter(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+}
+
+fn batch(table: usize, ops: &[(String, Option<Vec<u8>>)]) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+    for (key, value) in ops {
+        match value {
+            Some(value) => batch.insert(TABLES[table], key, value),
+            None => batch.remove(TABLES[table], key),
+        };
+    }
+    batch
+}
+
+fn entries(result: Result<Vec<(String, Vec<u8>)>, io::Error>) -> Result<Outcome, io::Error> {
+    let mut entries = result?;
+    entries.sort();
+    Ok(Outcome::Entries(entries))
+}
+
+fn swap(result: Result<(), CompareAndSwapError>) -> Result<Outcome, io::Error> {
+    match result {
+        Ok(()) => Ok(Outcome::Swap(Ok(()))),
+        Err(CompareAndSwapError::Mismatch { current }) => Ok(Outcome::Swap(Err(current))),
+        Err(CompareAndSwapError::Io(e)) if e.kind() == io::ErrorKind::Unsupported => {
+            Ok(Outcome::Unsupported)
+        }
+        Err(CompareAndSwapError::Io(e)) => Err(e),
+    }
+}
+
+fn apply(db: &dyn KeyValueDB, op: &Op) -> Result<Outcome, io::Error> {
+    match op {
+        Op::Insert { table, key, value } => {
+            db.insert(TABLES[*table], key, value).map(Outcome::Value)
+        }
+        Op::Get { table, key } => db.get(TABLES[*table], key).map(Outcome::Value),
+        Op::GetRange { table, key, range } => db
+            .get_range(TABLES[*table], key, range.clone())
+            .map(Outcome::Value),
+        Op::Remove { table, key } => db.remove(TABLES[*table], key).map(Outcome::Value),
+        Op::ContainsKey { table, key } => db.contains_key(TABLES[*table], key).map(Outcome::Bool),
+        Op::Iter { table } => entries(db.iter(TABLES[*table])),
+        Op::IterFromPrefix { table, prefix } => {
+            entries(db.iter_from_prefix(TABLES[*table], prefix))
+        }
+        Op::IterFromRange { table, start, end } => {
+            entries(db.iter_from_range(TABLES[*table], start, end))
+        }
+        Op::Len { table } => db.len(TABLES[*table]).map(Outcome::Len),
+        Op::DeleteTable { table } => db.delete_table(TABLES[*table]).map(|_| Outcome::Unit),
+        Op::ApplyBatch { table, ops } => db.apply_batch(batch(*table, ops)).map(|_| Outcome::Unit),
+        Op::CompareAndSwap {
+            table,
+            key,
+            expected,
+            new,
+        } => swap(db.compare_and_swap(TABLES[*table], key, expected.as_deref(), new.as_deref())),
+    }
+}
+
+#[cfg(feature = "async")]
+async fn apply_async(db: &dyn AsyncKeyValueDB, op: &Op) -> Result<Outcome, io::Error> {
+    match op {
+        Op::Insert { table, key, value } => db
+            .insert(TABLES[*table], key, value)
+            .await
+            .map(Outcome::Value),
+        Op::Get { table, key } => db.get(TABLES[*table], key).await.map(Outcome::Value),
+        Op::GetRange { table, key, range } => db
+            .get_range(TABLES[*table], key, range.clone())
+            .await
+            .map(Outcome::Value),
+        Op::Remove { table, key } => db.remove(TABLES[*table], key).await.map(Outcome::Value),
+        Op::ContainsKey { table, key } => db
+            .contains_key(TABLES[*table], key)
+            .await
+            .map(Outcome::Bool),
+        Op::Iter { table } => entries(db.iter(TABLES[*table]).await),
+        Op::IterFromPrefix { table, prefix } => {
+            entries(db.iter_from_prefix(TABLES[*table], prefix).await)
+        }
+        Op::IterFromRange { table, start, end } => {
+            entries(db.iter_from_range(TABLES[*table], start, end).await)
+        }
+        Op::Len { table } => db.len(TABLES[*table]).await.map(Outcome::Len),
+        Op::DeleteTable { table } => db.delete_table(TABLES[*table]).await.map(|_| Outcome::Unit),
+        Op::ApplyBatch { table, ops } => db
+            .apply_batch(batch(*table, ops))
+            .await
+            .map(|_| Outcome::Unit),
+        Op::CompareAndSwap {
+            table,
+            key,
+            expected,
+            new,
+        } => swap(
+            db.compare_and_swap(TABLES[*table], key, expected.as_deref(), new.as_deref())
+                .await,
+        ),
+    }
+}
+
+/// Compares one outcome of the backend with the model's, which skips
+/// operations the backend does not support.
+fn compare(
+    model: &mut Model,
+    op: &Op,
+    step: usize,
+    actual: Result<Outcome, io::Error>,
+) -> Result<(), (usize, String)> {
+    let actual = actual.map_err(|e| (step, format!("{:?} failed: {}", op, e)))?;
+    if actual == Outcome::Unsupported {
+        return Ok(());
+    }
+    let expected = model.apply(op);
+    if actual != expected {
+        return Err((
+            step,
+            format!("{:?}: expected {:?}, got {:?}", op, expected, actual),
+        ));
+    }
+    Ok(())
+}
+
+fn run(db: &dyn KeyValueDB, ops: &[Op]) -> Result<(), (usize, String)> {
+    let reset = |step| {
+        TABLES
+            .iter()
+            .try_for_each(|table| db.delete_table(table))
+            .map_err(|e| (step, format!("failed to delete the test tables: {}", e)))
+    };
+    reset(0)?;
+    let mut model = Model::default();
+    let result = ops
+        .iter()
+        .enumerate()
+        .try_for_each(|(step, op)| compare(&mut model, op, step, apply(db, op)));
+    result.and(reset(ops.len()))
+}
+
+#[cfg(feature = "async")]
+async fn run_async(db: &dyn AsyncKeyValueDB, ops: &[Op]) -> Result<(), (usize, String)> {
+    async fn reset(db: &dyn AsyncKeyValueDB, step: usize) -> Result<(), (usize, String)> {
+        for table in TABLES {
+            db.delete_table(table)
+                .await
+                .map_err(|e| (step, format!("failed to delete the test tables: {}", e)))?;
+        }
+        Ok(())
+    }
+    reset(db, 0).await?;
+    let mut model = Model::default();
+    let mut result = Ok(());
+    for (step, op) in ops.iter().enumerate() {
+        result = compare(&mut model, op, step, apply_async(db, op).await);
+        if result.is_err() {
+            break;
+        }
+    }
+    result.and(reset(db, ops.len()).await)
+}
+
+/// Runs `ops` against `db` from empty [`TABLES`] and checks every outcome
+/// against the model.
+pub fn check_ops(db: &dyn KeyValueDB, ops: &[Op]) -> Result<(), Failure> {
+    run(db, ops).map_err(|(step, detail)| Failure {
+        seed: None,
+        ops: ops.to_vec(),
+        step,
+        detail,
+    })
+}
+
+#[cfg(feature = "async")]
+pub async fn check_ops_async(db: &dyn AsyncKeyValueDB, ops: &[Op]) -> Result<(), Failure> {
+    run_async(db, ops).await.map_err(|(step, detail)| Failure {
+        seed: None,
+        ops: ops.to_vec(),
+        step,
+        detail,
+    })
+}
+
+/// Runs `cases` random sequences of `len` operations, generated from `seed`,
+/// `seed + 1` and so on. The first failing sequence is shrunk by dropping
+/// the operations it still fails without.
+pub fn check_random(db: &dyn KeyValueDB, seed: u64, cases: u64, len: usize) -> Result<(), Failure> {
+    for seed in seed..seed.saturating_add(cases) {
+        let ops = random_ops(seed, len);
+        if let Err((step, detail)) = run(db, &ops) {
+            let mut failure = Failure {
+                seed: Some(seed),
+                ops,
+                step,
+                detail,
+            };
+            failure.ops.truncate(step + 1);
+            let mut i = 0;
+            while i < failure.ops.len() {
+                let mut ops = failure.ops.clone();
+                ops.remove(i);
+                match run(db, &ops) {
+                    Err((step, detail)) => {
+                        ops.truncate(step + 1);
+                        failure.ops = ops;
+                        failure.step = step;
+                        failure.detail = detail;
+                    }
+                    Ok(()) => i += 1,
+                }
+            }
+            return Err(failure);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+pub async fn check_random_async(
+    db: &dyn AsyncKeyValueDB,
+    seed: u64,
+    cases: u64,
+    len: usize,
+) -> Result<(), Failure> {
+    for seed in seed..seed.saturating_add(cases) {
+        let ops = random_ops(seed, len);
+        if let Err((step, detail)) = run_async(db, &ops).await {
+            let mut failure = Failure {
+                seed: Some(seed),
+                ops,
+                step,
+                detail,
+            };
+            failure.ops.truncate(step + 1);
+            let mut i = 0;
+            while i < failure.ops.len() {
+                let mut ops = failure.ops.clone();
+                ops.remove(i);
+                match run_async(db, &ops).await {
+                    Err((step, detail)) => {
+                        ops.truncate(step + 1);
+                        failure.ops = ops;
+                        failure.step = step;
+                        failure.detail = detail;
+                    }
+                    Ok(()) => i += 1,
+                }
+            }
+            return Err(failure);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,531 @@
+#[cfg(feature = "async")]
+use core::future::Future;
+use core::{fmt, ops::Range};
+
+use crate::io;
+#[cfg(all(not(feature = "std"), feature = "async"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+#[cfg(feature = "async")]
+use tracing::Instrument;
+use tracing::{field, Span};
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB, WriteBatch};
+
+/// Runs every operation in a `keyvalue` debug span. The span records the
+/// `operation`, the `table_name` (empty for the operations on the whole
+/// database), the `key_len` (the prefix length for `iter_from_prefix`), the
+/// `value_len` written, the `result_len` of the returned values in bytes,
+/// and the `outcome`, with the `error` if there is one.
+#[derive(Debug)]
+pub struct TracedDB<T> {
+    inner: T,
+}
+
+impl<T> TracedDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: KeyValueDB> KeyValueDB for TracedDB<T> {
+    fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        traced(
+            span("insert", table_name, Some(key), Some(value.len())),
+            || self.inner.insert(table_name, key, value),
+        )
+    }
+
+    fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        traced(span("get", table_name, Some(key), None), || {
+            self.inner.get(table_name, key)
+        })
+    }
+
+    fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        traced(span("remove", table_name, Some(key), None), || {
+            self.inner.remove(table_name, key)
+        })
+    }
+
+    fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced(span("iter", table_name, None, None), || {
+            self.inner.iter(table_name)
+        })
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        traced(span("table_names", "", None, None), || {
+            self.inner.table_names()
+        })
+    }
+
+    fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        traced(span("create_table", table_name, None, None), || {
+            self.inner.create_table(table_name)
+        })
+    }
+
+    fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        traced(span("delete_table", table_name, None, None), || {
+            self.inner.delete_table(table_name)
+        })
+    }
+
+    fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced(
+            span("iter_from_prefix", table_name, Some(prefix), None),
+            || self.inner.iter_from_prefix(table_name, prefix),
+        )
+    }
+
+    fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced(span("iter_from_range", table_name, None, None), || {
+            self.inner.iter_from_range(table_name, start_key, end_key)
+        })
+    }
+
+    fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        traced(span("contains_key", table_name, Some(key), None), || {
+            self.inner.contains_key(table_name, key)
+        })
+    }
+
+    fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        traced(span("keys", table_name, None, None), || {
+            self.inner.keys(table_name)
+        })
+    }
+
+    fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        traced(span("values", table_name, None, None), || {
+            self.inner.values(table_name)
+        })
+    }
+
+    fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        traced(span("len", table_name, None, None), || {
+            self.inner.len(table_name)
+        })
+    }
+
+    fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        traced(span("is_empty", table_name, None, None), || {
+            self.inner.is_empty(table_name)
+        })
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        traced(span("clear", "", None, None), || self.inner.clear())
+    }
+
+    fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        traced(span("swap_tables", table_a, None, None), || {
+            self.inner.swap_tables(table_a, table_b)
+        })
+    }
+
+    fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut dyn FnMut() -> Vec<(String, Vec<u8>)>,
+    ) -> Result<bool, io::Error> {
+        traced(span("ensure_table_with", table_name, None, None), || {
+            self.inner.ensure_table_with(table_name, init)
+        })
+    }
+
+    fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        traced(
+            span(
+                "compare_and_swap",
+                table_name,
+                Some(key),
+                new.map(<[u8]>::len),
+            ),
+            || self.inner.compare_and_swap(table_name, key, expected, new),
+        )
+    }
+
+    fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        traced(span("checksum", table_name, Some(key), None), || {
+            self.inner.checksum(table_name, key)
+        })
+    }
+
+    fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        traced(span("get_range", table_name, Some(key), None), || {
+            self.inner.get_range(table_name, key, range)
+        })
+    }
+
+    fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        traced(span("apply_batch", "", None, None), || {
+            self.inner.apply_batch(batch)
+        })
+    }
+
+    fn flush(&self) -> Result<(), io::Error> {
+        traced(span("flush", "", None, None), || self.inner.flush())
+    }
+
+    fn compact(&self) -> Result<(), io::Error> {
+        traced(span("compact", "", None, None), || self.inner.compact())
+    }
+}
+
+/// Async counterpart of [`TracedDB`]. The span is entered every time the
+/// operation is polled.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncTracedDB<T> {
+    inner: T,
+}
+
+#[cfg(feature = "async")]
+impl<T> AsyncTracedDB<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(all(not(target_arch = "wasm32"), feature = "std"), async_trait)]
+#[cfg_attr(any(target_arch = "wasm32", not(feature = "std")), async_trait(?Send))]
+impl<T: AsyncKeyValueDB> AsyncKeyValueDB for AsyncTracedDB<T> {
+    async fn insert(
+        &self,
+        table_name: &str,
+        key: &str,
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        traced_async(
+            span("insert", table_name, Some(key), Some(value.len())),
+            self.inner.insert(table_name, key, value),
+        )
+        .await
+    }
+
+    async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        traced_async(
+            span("get", table_name, Some(key), None),
+            self.inner.get(table_name, key),
+        )
+        .await
+    }
+
+    async fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        traced_async(
+            span("remove", table_name, Some(key), None),
+            self.inner.remove(table_name, key),
+        )
+        .await
+    }
+
+    async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced_async(
+            span("iter", table_name, None, None),
+            self.inner.iter(table_name),
+        )
+        .await
+    }
+
+    async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+        traced_async(
+            span("table_names", "", None, None),
+            self.inner.table_names(),
+        )
+        .await
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+        traced_async(
+            span("create_table", table_name, None, None),
+            self.inner.create_table(table_name),
+        )
+        .await
+    }
+
+    async fn delete_table(&self, table_name: &str) -> Result<(), io::Error> {
+        traced_async(
+            span("delete_table", table_name, None, None),
+            self.inner.delete_table(table_name),
+        )
+        .await
+    }
+
+    async fn iter_from_prefix(
+        &self,
+        table_name: &str,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced_async(
+            span("iter_from_prefix", table_name, Some(prefix), None),
+            self.inner.iter_from_prefix(table_name, prefix),
+        )
+        .await
+    }
+
+    async fn iter_from_range(
+        &self,
+        table_name: &str,
+        start_key: &str,
+        end_key: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+        traced_async(
+            span("iter_from_range", table_name, None, None),
+            self.inner.iter_from_range(table_name, start_key, end_key),
+        )
+        .await
+    }
+
+    async fn contains_key(&self, table_name: &str, key: &str) -> Result<bool, io::Error> {
+        traced_async(
+            span("contains_key", table_name, Some(key), None),
+            self.inner.contains_key(table_name, key),
+        )
+        .await
+    }
+
+    async fn keys(&self, table_name: &str) -> Result<Vec<String>, io::Error> {
+        traced_async(
+            span("keys", table_name, None, None),
+            self.inner.keys(table_name),
+        )
+        .await
+    }
+
+    async fn values(&self, table_name: &str) -> Result<Vec<Vec<u8>>, io::Error> {
+        traced_async(
+            span("values", table_name, None, None),
+            self.inner.values(table_name),
+        )
+        .await
+    }
+
+    async fn len(&self, table_name: &str) -> Result<u64, io::Error> {
+        traced_async(
+            span("len", table_name, None, None),
+            self.inner.len(table_name),
+        )
+        .await
+    }
+
+    async fn is_empty(&self, table_name: &str) -> Result<bool, io::Error> {
+        traced_async(
+            span("is_empty", table_name, None, None),
+            self.inner.is_empty(table_name),
+        )
+        .await
+    }
+
+    async fn clear(&self) -> Result<(), io::Error> {
+        traced_async(span("clear", "", None, None), self.inner.clear()).await
+    }
+
+    async fn swap_tables(&self, table_a: &str, table_b: &str) -> Result<(), io::Error> {
+        traced_async(
+            span("swap_tables", table_a, None, None),
+            self.inner.swap_tables(table_a, table_b),
+        )
+        .await
+    }
+
+    async fn ensure_table_with(
+        &self,
+        table_name: &str,
+        init: &mut (dyn FnMut() -> Vec<(String, Vec<u8>)> + Send),
+    ) -> Result<bool, io::Error> {
+        traced_async(
+            span("ensure_table_with", table_name, None, None),
+            self.inner.ensure_table_with(table_name, init),
+        )
+        .await
+    }
+
+    async fn compare_and_swap(
+        &self,
+        table_name: &str,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), CompareAndSwapError> {
+        traced_async(
+            span(
+                "compare_and_swap",
+                table_name,
+                Some(key),
+                new.map(<[u8]>::len),
+            ),
+            self.inner.compare_and_swap(table_name, key, expected, new),
+        )
+        .await
+    }
+
+    async fn checksum(&self, table_name: &str, key: &str) -> Result<Option<u64>, io::Error> {
+        traced_async(
+            span("checksum", table_name, Some(key), None),
+            self.inner.checksum(table_name, key),
+        )
+        .await
+    }
+
+    async fn get_range(
+        &self,
+        table_name: &str,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>, io::Error> {
+        traced_async(
+            span("get_range", table_name, Some(key), None),
+            self.inner.get_range(table_name, key, range),
+        )
+        .await
+    }
+
+    async fn apply_batch(&self, batch: WriteBatch) -> Result<(), io::Error> {
+        traced_async(
+            span("apply_batch", "", None, None),
+            self.inner.apply_batch(batch),
+        )
+        .await
+    }
+
+    async fn flush(&self) -> Result<(), io::Error> {
+        traced_async(span("flush", "", None, None), self.inner.flush()).await
+    }
+
+    async fn compact(&self) -> Result<(), io::Error> {
+        traced_async(span("compact", "", None, None), self.inner.compact()).await
+    }
+}
+
+fn span(
+    operation: &'static str,
+    table_name: &str,
+    key: Option<&str>,
+    value_len: Option<usize>,
+) -> Span {
+    tracing::debug_span!(
+        "keyvalue",
+        operation,
+        table_name,
+        key_len = key.map(str::len),
+        value_len,
+        result_len = field::Empty,
+        outcome = field::Empty,
+        error = field::Empty,
+    )
+}
+
+fn traced<R: ResultLen, E: fmt::Display>(
+    span: Span,
+    f: impl FnOnce() -> Result<R, E>,
+) -> Result<R, E> {
+    let result = span.in_scope(f);
+    record(&span, &result);
+    result
+}
+
+#[cfg(feature = "async")]
+async fn traced_async<R: ResultLen, E: fmt::Display>(
+    span: Span,
+    future: impl Future<Output = Result<R, E>>,
+) -> Result<R, E> {
+    let result = future.instrument(span.clone()).await;
+    record(&span, &result);
+    result
+}
+
+fn record<R: ResultLen, E: fmt::Display>(span: &Span, result: &Result<R, E>) {
+    match result {
+        Ok(value) => {
+            span.record("outcome", "ok");
+            if let Some(len) = value.result_len() {
+                span.record("result_len", len);
+            }
+        }
+        Err(e) => {
+            span.record("outcome", "error");
+            span.record("error", field::display(e));
+        }
+    }
+}
+
+/// The number of value bytes returned by an operation.
+trait ResultLen {
+    fn result_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl ResultLen for Option<Vec<u8>> {
+    fn result_len(&self) -> Option<usize> {
+        Some(self.as_ref().map_or(0, Vec::len))
+    }
+}
+
+impl ResultLen for Vec<(String, Vec<u8>)> {
+    fn result_len(&self) -> Option<usize> {
+        Some(self.iter().map(|(_, value)| value.len()).sum())
+    }
+}
+
+impl ResultLen for Vec<Vec<u8>> {
+    fn result_len(&self) -> Option<usize> {
+        Some(self.iter().map(Vec::len).sum())
+    }
+}
+
+impl ResultLen for () {}
+impl ResultLen for bool {}
+impl ResultLen for u64 {}
+impl ResultLen for Option<u64> {}
+impl ResultLen for Vec<String> {}
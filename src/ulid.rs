@@ -0,0 +1,150 @@
+use core::{fmt, str::FromStr};
+
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const ENCODED_LEN: usize = 26;
+const RANDOM_BITS: u32 = 80;
+const RANDOM_MASK: u128 = (1 << RANDOM_BITS) - 1;
+const TIMESTAMP_MASK: u64 = (1 << 48) - 1;
+
+/// A ULID: a 48-bit millisecond timestamp followed by 80 random bits. Its
+/// string form is 26 characters of Crockford base32, and sorts like the
+/// timestamp, so keys made of ULIDs are stored in creation order by every
+/// ordered backend and time windows can be read with `iter_from_range`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ulid(u128);
+
+impl Ulid {
+    /// Only the low 48 bits of `timestamp_ms` and the low 80 bits of `random`
+    /// are kept.
+    pub fn from_parts(timestamp_ms: u64, random: u128) -> Self {
+        Self(((timestamp_ms & TIMESTAMP_MASK) as u128) << RANDOM_BITS | (random & RANDOM_MASK))
+    }
+
+    /// The smallest ULID of `timestamp_ms`, to use as the start of a range.
+    pub fn min_at(timestamp_ms: u64) -> Self {
+        Self::from_parts(timestamp_ms, 0)
+    }
+
+    /// The largest ULID of `timestamp_ms`.
+    pub fn max_at(timestamp_ms: u64) -> Self {
+        Self::from_parts(timestamp_ms, RANDOM_MASK)
+    }
+
+    pub fn timestamp_ms(&self) -> u64 {
+        (self.0 >> RANDOM_BITS) as u64
+    }
+
+    pub fn random(&self) -> u128 {
+        self.0 & RANDOM_MASK
+    }
+
+    /// Formats the UUIDv7 with the same timestamp and the first 74 random
+    /// bits. UUIDv7 strings sort by timestamp as well.
+    pub fn to_uuid_v7(&self) -> String {
+        let random = self.random();
+        let rand_a = (random >> 68) & 0xFFF;
+        let rand_b = (random >> 6) & ((1 << 62) - 1);
+        let value =
+            (self.timestamp_ms() as u128) << 80 | 0x7 << 76 | rand_a << 64 | 0b10 << 62 | rand_b;
+        let hex = alloc::format!("{:032x}", value);
+        alloc::format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    /// Returns a new ULID for the current time.
+    #[cfg(feature = "ulid")]
+    pub fn generate() -> Result<Self, io::Error> {
+        Ok(Self::from_parts(now_ms()?, random()?))
+    }
+}
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut encoded = [0; ENCODED_LEN];
+        for (i, byte) in encoded.iter_mut().enumerate() {
+            let shift = 5 * (ENCODED_LEN - 1 - i);
+            *byte = ALPHABET[((self.0 >> shift) & 0x1F) as usize];
+        }
+        f.write_str(core::str::from_utf8(&encoded).unwrap())
+    }
+}
+
+impl FromStr for Ulid {
+    type Err = io::Error;
+
+    /// Parses the base32 form, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid ULID");
+        if s.len() != ENCODED_LEN || !matches!(s.as_bytes()[0], b'0'..=b'7') {
+            return Err(invalid());
+        }
+        let mut value = 0u128;
+        for byte in s.bytes() {
+            let digit = ALPHABET
+                .iter()
+                .position(|c| *c == byte.to_ascii_uppercase())
+                .ok_or_else(invalid)?;
+            value = value << 5 | digit as u128;
+        }
+        Ok(Self(value))
+    }
+}
+
+/// Hands out ULIDs that strictly increase, even within a millisecond or when
+/// the clock goes back, by incrementing the random part of the last one.
+#[cfg(feature = "ulid")]
+#[derive(Debug, Default)]
+pub struct UlidGenerator {
+    last: Option<Ulid>,
+}
+
+#[cfg(feature = "ulid")]
+impl UlidGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn generate(&mut self) -> Result<Ulid, io::Error> {
+        let ulid = Ulid::generate()?;
+        let ulid = match self.last {
+            Some(last) if ulid <= last => Ulid(
+                last.0
+                    .checked_add(1)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "ULID space exhausted"))?,
+            ),
+            _ => ulid,
+        };
+        self.last = Some(ulid);
+        Ok(ulid)
+    }
+}
+
+#[cfg(all(feature = "ulid", not(target_arch = "wasm32")))]
+fn now_ms() -> Result<u64, io::Error> {
+    let elapsed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(elapsed.as_millis() as u64)
+}
+
+#[cfg(all(feature = "ulid", target_arch = "wasm32"))]
+fn now_ms() -> Result<u64, io::Error> {
+    Ok(js_sys::Date::now() as u64)
+}
+
+#[cfg(feature = "ulid")]
+fn random() -> Result<u128, io::Error> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(u128::from_le_bytes(bytes))
+}
@@ -0,0 +1,133 @@
+use crate::io;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "async")]
+use crate::AsyncKeyValueDB;
+use crate::{CompareAndSwapError, KeyValueDB};
+
+/// The number of attempts [`run_update`] makes by default.
+pub const DEFAULT_MAX_ATTEMPTS: usize = 16;
+
+/// Replaces the value of `key` with the one computed by `f` from the current
+/// value, where `None` stands for a missing key on both sides. The new value
+/// is written with `compare_and_swap`, and `f` is called again with the
+/// value found if another writer changed it in between.
+///
+/// Returns the value written, or the last mismatch if the value changed
+/// during each of `max_attempts` attempts.
+pub fn run_update<D, F>(
+    db: &D,
+    table_name: &str,
+    key: &str,
+    max_attempts: usize,
+    mut f: F,
+) -> Result<Option<Vec<u8>>, CompareAndSwapError>
+where
+    D: KeyValueDB + ?Sized,
+    F: FnMut(Option<&[u8]>) -> Result<Option<Vec<u8>>, io::Error>,
+{
+    let mut current = db.get(table_name, key)?;
+    let mut attempts = 1;
+    loop {
+        let new = f(current.as_deref())?;
+        match db.compare_and_swap(table_name, key, current.as_deref(), new.as_deref()) {
+            Ok(()) => return Ok(new),
+            Err(CompareAndSwapError::Mismatch { current: actual }) if attempts < max_attempts => {
+                current = actual;
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub async fn run_update_async<D, F>(
+    db: &D,
+    table_name: &str,
+    key: &str,
+    max_attempts: usize,
+    mut f: F,
+) -> Result<Option<Vec<u8>>, CompareAndSwapError>
+where
+    D: AsyncKeyValueDB + ?Sized,
+    F: FnMut(Option<&[u8]>) -> Result<Option<Vec<u8>>, io::Error> + Send,
+{
+    let mut current = db.get(table_name, key).await?;
+    let mut attempts = 1;
+    loop {
+        let new = f(current.as_deref())?;
+        match db
+            .compare_and_swap(table_name, key, current.as_deref(), new.as_deref())
+            .await
+        {
+            Ok(()) => return Ok(new),
+            Err(CompareAndSwapError::Mismatch { current: actual }) if attempts < max_attempts => {
+                current = actual;
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Converts the error of an update into an `io::Error`, a mismatch meaning
+/// that the value changed during every attempt.
+pub(crate) fn update_error(e: CompareAndSwapError) -> io::Error {
+    match e {
+        CompareAndSwapError::Io(e) => e,
+        CompareAndSwapError::Mismatch { .. } => io::Error::new(
+            io::ErrorKind::Other,
+            "the value changed during every attempt of the update",
+        ),
+    }
+}
+
+/// Combines the value of `key` with `operand` using `merge_fn`, which gets
+/// the current value, or `None` if the key is missing, and returns the new
+/// one. The write is atomic, as `merge_fn` is applied again until it is
+/// based on the latest value, so accumulators such as counters or CRDTs can
+/// be shared by several writers without losing updates.
+///
+/// Returns the merged value, or an error if the value changed during each of
+/// `max_attempts` attempts, [`DEFAULT_MAX_ATTEMPTS`] being a sensible
+/// default. No backend has a native merge operator, so this relies on
+/// `compare_and_swap` and fails on backends without it.
+pub fn merge<F>(
+    db: &dyn KeyValueDB,
+    table_name: &str,
+    key: &str,
+    operand: &[u8],
+    max_attempts: usize,
+    merge_fn: F,
+) -> Result<Vec<u8>, io::Error>
+where
+    F: Fn(Option<&[u8]>, &[u8]) -> Vec<u8>,
+{
+    let merged = run_update(db, table_name, key, max_attempts, |current| {
+        Ok(Some(merge_fn(current, operand)))
+    })
+    .map_err(update_error)?;
+    Ok(merged.unwrap_or_default())
+}
+
+#[cfg(feature = "async")]
+pub async fn merge_async<F>(
+    db: &dyn AsyncKeyValueDB,
+    table_name: &str,
+    key: &str,
+    operand: &[u8],
+    max_attempts: usize,
+    merge_fn: F,
+) -> Result<Vec<u8>, io::Error>
+where
+    F: Fn(Option<&[u8]>, &[u8]) -> Vec<u8> + Send + Sync,
+{
+    let merged = run_update_async(db, table_name, key, max_attempts, |current| {
+        Ok(Some(merge_fn(current, operand)))
+    })
+    .await
+    .map_err(update_error)?;
+    Ok(merged.unwrap_or_default())
+}
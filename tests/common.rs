@@ -106,6 +106,150 @@ pub fn test_db(db: &dyn keyvalue::KeyValueDB) {
     assert!(!db.contains_key(table1, key2).unwrap());
     assert!(!db.contains_key(table1, "non-existent").unwrap());
     assert!(db.table_names().unwrap().is_empty());
+
+    let table = keyvalue::TableHandle::new(db, table1);
+    assert_eq!(table.name(), table1);
+    assert!(table.insert(key1, value1).unwrap().is_none());
+    assert_eq!(table.get(key1).unwrap(), Some(value1.to_vec()));
+    assert!(table.contains_key(key1).unwrap());
+    assert_eq!(
+        table.iter().unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert_eq!(table.remove(key1).unwrap(), Some(value1.to_vec()));
+    assert!(table.keys().unwrap().is_empty());
+    assert!(table.delete().is_ok());
+    assert!(db.clear().is_ok());
+
+    assert!(db.insert(table1, key1, value1).unwrap().is_none());
+    assert!(db.insert(table2, key2, value2).unwrap().is_none());
+    assert!(db.swap_tables(table1, table2).is_ok());
+    assert_eq!(
+        db.iter(table1).unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert_eq!(
+        db.iter(table2).unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert!(db.delete_table(table2).is_ok());
+    assert!(db.swap_tables(table1, table2).is_ok());
+    assert!(db.iter(table1).unwrap().is_empty());
+    assert_eq!(
+        db.iter(table2).unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert!(db.clear().is_ok());
+
+    for key in ["2024-01-01", "2024-01-31", "2024-02-01", "2023-12-31"] {
+        assert!(db.insert(table1, key, key.as_bytes()).unwrap().is_none());
+    }
+    let mut range = db.iter_from_range(table1, "2024-01", "2024-02").unwrap();
+    range.sort();
+    assert_eq!(
+        range,
+        vec![
+            ("2024-01-01".to_string(), b"2024-01-01".to_vec()),
+            ("2024-01-31".to_string(), b"2024-01-31".to_vec()),
+        ]
+    );
+    assert!(db
+        .iter_from_range(table1, "2024-02", "2024-01")
+        .unwrap()
+        .is_empty());
+    assert!(db
+        .iter_from_range(table2, "2024-01", "2024-02")
+        .unwrap()
+        .is_empty());
+    assert!(db.clear().is_ok());
+
+    assert!(db
+        .compare_and_swap(table1, key1, None, Some(value1))
+        .is_ok());
+    assert!(matches!(
+        db.compare_and_swap(table1, key1, None, Some(value2)),
+        Err(keyvalue::CompareAndSwapError::Mismatch { current }) if current.as_deref() == Some(value1)
+    ));
+    assert!(db
+        .compare_and_swap(table1, key1, Some(value1), Some(value2))
+        .is_ok());
+    assert_eq!(db.get(table1, key1).unwrap(), Some(value2.to_vec()));
+    assert!(matches!(
+        db.compare_and_swap(table1, key1, Some(value1), None),
+        Err(keyvalue::CompareAndSwapError::Mismatch { .. })
+    ));
+    assert!(db
+        .compare_and_swap(table1, key1, Some(value2), None)
+        .is_ok());
+    assert!(db.get(table1, key1).unwrap().is_none());
+    assert!(matches!(
+        db.compare_and_swap(table2, key2, Some(value2), None),
+        Err(keyvalue::CompareAndSwapError::Mismatch { current: None })
+    ));
+    assert!(db.clear().is_ok());
+
+    let mut init = || vec![(key1.to_string(), value1.to_vec())];
+    assert!(db.ensure_table_with(table1, &mut init).unwrap());
+    assert!(!db
+        .ensure_table_with(table1, &mut || unreachable!())
+        .unwrap());
+    assert_eq!(
+        db.iter(table1).unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert!(db.clear().is_ok());
+
+    assert_eq!(db.checksum(table1, key1).unwrap(), None);
+    db.insert(table1, key1, value1).unwrap();
+    db.insert(table2, key1, value1).unwrap();
+    db.insert(table2, key2, value2).unwrap();
+    let checksum = db.checksum(table1, key1).unwrap();
+    assert!(checksum.is_some());
+    assert_eq!(db.checksum(table2, key1).unwrap(), checksum);
+    assert_ne!(db.checksum(table2, key2).unwrap(), checksum);
+    assert!(db.clear().is_ok());
+
+    assert_eq!(db.get_range(table1, key1, 0..4).unwrap(), None);
+    db.insert(table1, key1, b"0123456789").unwrap();
+    assert_eq!(
+        db.get_range(table1, key1, 2..5).unwrap(),
+        Some(b"234".to_vec())
+    );
+    assert_eq!(
+        db.get_range(table1, key1, 8..20).unwrap(),
+        Some(b"89".to_vec())
+    );
+    assert_eq!(db.get_range(table1, key1, 12..20).unwrap(), Some(vec![]));
+    assert_eq!(db.get_range(table1, key1, 3..3).unwrap(), Some(vec![]));
+    assert!(db.clear().is_ok());
+
+    let mut batch = keyvalue::WriteBatch::new();
+    batch
+        .insert(table1, key1, value1)
+        .insert(table2, key2, value2)
+        .insert(table1, key2, value2)
+        .remove(table1, key2)
+        .remove(table2, key1);
+    assert_eq!(batch.len(), 5);
+    assert!(db.apply_batch(batch).is_ok());
+    assert_eq!(
+        db.iter(table1).unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert_eq!(
+        db.iter(table2).unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert!(db.clear().is_ok());
+
+    assert_eq!(db.len(table1).unwrap(), 0);
+    assert!(db.is_empty(table1).unwrap());
+    db.insert(table1, key1, value1).unwrap();
+    db.insert(table1, key2, value2).unwrap();
+    assert_eq!(db.len(table1).unwrap(), 2);
+    assert!(!db.is_empty(table1).unwrap());
+    assert!(db.is_empty(table2).unwrap());
+    assert!(db.clear().is_ok());
 }
 
 #[cfg(feature = "async")]
@@ -213,6 +357,169 @@ pub async fn test_async_db(db: &dyn keyvalue::AsyncKeyValueDB) {
     assert!(!db.contains_key(table1, key2).await.unwrap());
     assert!(!db.contains_key(table1, "non-existent").await.unwrap());
     assert!(db.table_names().await.unwrap().is_empty());
+
+    let table = keyvalue::AsyncTableHandle::new(db, table1);
+    assert_eq!(table.name(), table1);
+    assert!(table.insert(key1, value1).await.unwrap().is_none());
+    assert_eq!(table.get(key1).await.unwrap(), Some(value1.to_vec()));
+    assert!(table.contains_key(key1).await.unwrap());
+    assert_eq!(
+        table.iter().await.unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert_eq!(table.remove(key1).await.unwrap(), Some(value1.to_vec()));
+    assert!(table.keys().await.unwrap().is_empty());
+    assert!(table.delete().await.is_ok());
+    assert!(db.clear().await.is_ok());
+
+    assert!(db.insert(table1, key1, value1).await.unwrap().is_none());
+    assert!(db.insert(table2, key2, value2).await.unwrap().is_none());
+    assert!(db.swap_tables(table1, table2).await.is_ok());
+    assert_eq!(
+        db.iter(table1).await.unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert_eq!(
+        db.iter(table2).await.unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert!(db.delete_table(table2).await.is_ok());
+    assert!(db.swap_tables(table1, table2).await.is_ok());
+    assert!(db.iter(table1).await.unwrap().is_empty());
+    assert_eq!(
+        db.iter(table2).await.unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert!(db.clear().await.is_ok());
+
+    for key in ["2024-01-01", "2024-01-31", "2024-02-01", "2023-12-31"] {
+        assert!(db
+            .insert(table1, key, key.as_bytes())
+            .await
+            .unwrap()
+            .is_none());
+    }
+    let mut range = db
+        .iter_from_range(table1, "2024-01", "2024-02")
+        .await
+        .unwrap();
+    range.sort();
+    assert_eq!(
+        range,
+        vec![
+            ("2024-01-01".to_string(), b"2024-01-01".to_vec()),
+            ("2024-01-31".to_string(), b"2024-01-31".to_vec()),
+        ]
+    );
+    assert!(db
+        .iter_from_range(table1, "2024-02", "2024-01")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(db
+        .iter_from_range(table2, "2024-01", "2024-02")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(db.clear().await.is_ok());
+
+    assert!(db
+        .compare_and_swap(table1, key1, None, Some(value1))
+        .await
+        .is_ok());
+    assert!(matches!(
+        db.compare_and_swap(table1, key1, None, Some(value2)).await,
+        Err(keyvalue::CompareAndSwapError::Mismatch { current }) if current.as_deref() == Some(value1)
+    ));
+    assert!(db
+        .compare_and_swap(table1, key1, Some(value1), Some(value2))
+        .await
+        .is_ok());
+    assert_eq!(db.get(table1, key1).await.unwrap(), Some(value2.to_vec()));
+    assert!(matches!(
+        db.compare_and_swap(table1, key1, Some(value1), None).await,
+        Err(keyvalue::CompareAndSwapError::Mismatch { .. })
+    ));
+    assert!(db
+        .compare_and_swap(table1, key1, Some(value2), None)
+        .await
+        .is_ok());
+    assert!(db.get(table1, key1).await.unwrap().is_none());
+    assert!(matches!(
+        db.compare_and_swap(table2, key2, Some(value2), None).await,
+        Err(keyvalue::CompareAndSwapError::Mismatch { current: None })
+    ));
+    assert!(db.clear().await.is_ok());
+
+    let mut init = || vec![(key1.to_string(), value1.to_vec())];
+    assert!(db.ensure_table_with(table1, &mut init).await.unwrap());
+    assert!(!db
+        .ensure_table_with(table1, &mut || unreachable!())
+        .await
+        .unwrap());
+    assert_eq!(
+        db.iter(table1).await.unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert!(db.clear().await.is_ok());
+
+    assert_eq!(db.checksum(table1, key1).await.unwrap(), None);
+    db.insert(table1, key1, value1).await.unwrap();
+    db.insert(table2, key1, value1).await.unwrap();
+    db.insert(table2, key2, value2).await.unwrap();
+    let checksum = db.checksum(table1, key1).await.unwrap();
+    assert!(checksum.is_some());
+    assert_eq!(db.checksum(table2, key1).await.unwrap(), checksum);
+    assert_ne!(db.checksum(table2, key2).await.unwrap(), checksum);
+    assert!(db.clear().await.is_ok());
+
+    assert_eq!(db.get_range(table1, key1, 0..4).await.unwrap(), None);
+    db.insert(table1, key1, b"0123456789").await.unwrap();
+    assert_eq!(
+        db.get_range(table1, key1, 2..5).await.unwrap(),
+        Some(b"234".to_vec())
+    );
+    assert_eq!(
+        db.get_range(table1, key1, 8..20).await.unwrap(),
+        Some(b"89".to_vec())
+    );
+    assert_eq!(
+        db.get_range(table1, key1, 12..20).await.unwrap(),
+        Some(vec![])
+    );
+    assert_eq!(
+        db.get_range(table1, key1, 3..3).await.unwrap(),
+        Some(vec![])
+    );
+    assert!(db.clear().await.is_ok());
+
+    let mut batch = keyvalue::WriteBatch::new();
+    batch
+        .insert(table1, key1, value1)
+        .insert(table2, key2, value2)
+        .insert(table1, key2, value2)
+        .remove(table1, key2)
+        .remove(table2, key1);
+    assert_eq!(batch.len(), 5);
+    assert!(db.apply_batch(batch).await.is_ok());
+    assert_eq!(
+        db.iter(table1).await.unwrap(),
+        vec![(key1.to_string(), value1.to_vec())]
+    );
+    assert_eq!(
+        db.iter(table2).await.unwrap(),
+        vec![(key2.to_string(), value2.to_vec())]
+    );
+    assert!(db.clear().await.is_ok());
+
+    assert_eq!(db.len(table1).await.unwrap(), 0);
+    assert!(db.is_empty(table1).await.unwrap());
+    db.insert(table1, key1, value1).await.unwrap();
+    db.insert(table1, key2, value2).await.unwrap();
+    assert_eq!(db.len(table1).await.unwrap(), 2);
+    assert!(!db.is_empty(table1).await.unwrap());
+    assert!(db.is_empty(table2).await.unwrap());
+    assert!(db.clear().await.is_ok());
 }
 
 pub fn persist_test_data(db: Box<dyn keyvalue::KeyValueDB>) {
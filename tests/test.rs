@@ -27,6 +27,1986 @@ mod tests {
             .is_empty());
     }
 
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_table_handles() {
+        use keyvalue::{AsyncKeyValueDB, KeyValueDB};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.table("table").insert("key", b"value").unwrap();
+        assert_eq!(
+            db.table_async("table").get("key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_in_memory_usage() {
+        use keyvalue::KeyValueDB;
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        assert_eq!(db.memory_usage(), 0);
+        db.insert("table", "key", &[0; 1024]).unwrap();
+        let usage = db.memory_usage();
+        assert!(usage >= 1024 + "table".len() + "key".len());
+        db.insert("table", "key2", &[0; 1024]).unwrap();
+        assert!(db.memory_usage() > usage + 1024);
+        db.clear().unwrap();
+        assert_eq!(db.memory_usage(), 0);
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_in_memory_limits() {
+        use keyvalue::{in_memory::InMemoryDB, KeyValueDB};
+
+        let db = InMemoryDB::with_limits(keyvalue::in_memory::CacheLimits {
+            max_entries: Some(2),
+            max_bytes: None,
+        });
+        db.insert("table", "b", b"2").unwrap();
+        db.insert("table", "a", b"1").unwrap();
+        assert_eq!(db.get("table", "b").unwrap(), Some(b"2".to_vec()));
+        db.insert("table", "c", b"3").unwrap();
+
+        assert_eq!(db.keys("table").unwrap(), vec!["b", "c"]);
+        assert_eq!(
+            db.iter_from_prefix("table", "c").unwrap(),
+            vec![("c".to_string(), b"3".to_vec())]
+        );
+    }
+
+    #[cfg(all(feature = "encryption", feature = "in-memory"))]
+    #[test]
+    fn test_encrypted() {
+        use keyvalue::KeyValueDB;
+
+        let key = [7; 32];
+        let db =
+            keyvalue::encrypted::EncryptedDB::new(keyvalue::in_memory::InMemoryDB::new(), &key);
+        common::test_db(&db);
+
+        db.insert("table", "key", b"secret").unwrap();
+        let stored = db.inner().get("table", "key").unwrap().unwrap();
+        assert!(!stored.windows(6).any(|window| window == b"secret"));
+        db.inner().insert("table", "other", &stored).unwrap();
+        assert!(db.get("table", "other").is_err());
+        db.inner().insert("moved", "key", &stored).unwrap();
+        assert!(db.get("moved", "key").is_err());
+        db.inner().remove("moved", "key").unwrap();
+        db.inner().remove("table", "other").unwrap();
+
+        db.swap_tables("table", "swapped").unwrap();
+        assert_eq!(db.get("swapped", "key").unwrap(), Some(b"secret".to_vec()));
+        db.swap_tables("table", "swapped").unwrap();
+
+        let init = &mut || vec![("seed".to_string(), b"value".to_vec())];
+        assert!(db.ensure_table_with("seeded", init).unwrap());
+        assert!(!db.ensure_table_with("seeded", init).unwrap());
+        assert_eq!(db.get("seeded", "seed").unwrap(), Some(b"value".to_vec()));
+        assert_ne!(
+            db.inner().get("seeded", "seed").unwrap(),
+            Some(b"value".to_vec())
+        );
+
+        let db = keyvalue::encrypted::EncryptedDB::new(db.into_inner(), &[8; 32]);
+        assert!(db.get("table", "key").is_err());
+        // The write goes through, and the replaced value is handed back as
+        // stored.
+        let e = db.insert("table", "key", b"rekeyed").unwrap_err();
+        let replaced = keyvalue::encrypted::UndecryptableOldValue::from_io_error(&e)
+            .unwrap()
+            .clone();
+        assert_eq!(db.get("table", "key").unwrap(), Some(b"rekeyed".to_vec()));
+        db.inner().insert("table", "key", &replaced.stored).unwrap();
+
+        let db =
+            keyvalue::encrypted::EncryptedDB::with_key_provider(db.into_inner(), &key).unwrap();
+        assert_eq!(db.get("table", "key").unwrap(), Some(b"secret".to_vec()));
+    }
+
+    #[cfg(all(feature = "encryption", feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_encrypted() {
+        let db = keyvalue::encrypted::AsyncEncryptedDB::new(
+            keyvalue::in_memory::InMemoryDB::new(),
+            &[7; 32],
+        );
+        common::test_async_db(&db).await;
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_default_methods() {
+        use std::io;
+
+        use keyvalue::{in_memory::InMemoryDB, meta::META_TABLE, CompareAndSwapError, KeyValueDB};
+
+        /// Only implements the required methods, so that the defaults of the
+        /// others are used.
+        struct MinimalDB(InMemoryDB);
+
+        impl KeyValueDB for MinimalDB {
+            fn insert(
+                &self,
+                table_name: &str,
+                key: &str,
+                value: &[u8],
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.insert(table_name, key, value)
+            }
+            fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.get(table_name, key)
+            }
+            fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.remove(table_name, key)
+            }
+            fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+                self.0.iter(table_name)
+            }
+            fn table_names(&self) -> Result<Vec<String>, io::Error> {
+                self.0.table_names()
+            }
+        }
+
+        let db = MinimalDB(InMemoryDB::new());
+        db.insert("a", "shared", b"a").unwrap();
+        db.insert("a", "only_a", b"a").unwrap();
+        db.insert("b", "shared", b"b").unwrap();
+        db.insert("b", "only_b", b"b").unwrap();
+        db.swap_tables("a", "b").unwrap();
+        assert_eq!(
+            db.iter("a").unwrap(),
+            vec![
+                ("only_b".to_string(), b"b".to_vec()),
+                ("shared".to_string(), b"b".to_vec())
+            ]
+        );
+        assert_eq!(
+            db.iter("b").unwrap(),
+            vec![
+                ("only_a".to_string(), b"a".to_vec()),
+                ("shared".to_string(), b"a".to_vec())
+            ]
+        );
+
+        db.swap_tables("b", "missing").unwrap();
+        assert!(db.iter("b").unwrap().is_empty());
+        assert_eq!(db.iter("missing").unwrap().len(), 2);
+
+        // Without compare_and_swap the seeding is not claimed.
+        let init = &mut || vec![("seed".to_string(), b"value".to_vec())];
+        assert!(db.ensure_table_with("seeded", init).unwrap());
+        assert!(!db.ensure_table_with("seeded", init).unwrap());
+        assert!(!db.table_names().unwrap().contains(&META_TABLE.to_string()));
+
+        /// Also has tables of its own and `compare_and_swap`.
+        struct TableDB(MinimalDB);
+
+        impl KeyValueDB for TableDB {
+            fn insert(
+                &self,
+                table_name: &str,
+                key: &str,
+                value: &[u8],
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.insert(table_name, key, value)
+            }
+            fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.get(table_name, key)
+            }
+            fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                self.0.remove(table_name, key)
+            }
+            fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+                self.0.iter(table_name)
+            }
+            fn table_names(&self) -> Result<Vec<String>, io::Error> {
+                self.0.table_names()
+            }
+            fn create_table(&self, table_name: &str) -> Result<(), io::Error> {
+                self.0 .0.create_table(table_name)
+            }
+            fn compare_and_swap(
+                &self,
+                table_name: &str,
+                key: &str,
+                expected: Option<&[u8]>,
+                new: Option<&[u8]>,
+            ) -> Result<(), CompareAndSwapError> {
+                self.0 .0.compare_and_swap(table_name, key, expected, new)
+            }
+        }
+
+        let db = TableDB(MinimalDB(InMemoryDB::new()));
+        // An empty table is created, so it is only seeded once.
+        assert!(db.ensure_table_with("empty", &mut Vec::new).unwrap());
+        assert!(!db
+            .ensure_table_with("empty", &mut || unreachable!())
+            .unwrap());
+        assert!(db.iter(META_TABLE).unwrap().is_empty());
+        // A table claimed by another caller is left to it.
+        db.insert(META_TABLE, "seeding::claimed", b"").unwrap();
+        assert!(!db
+            .ensure_table_with("claimed", &mut || unreachable!())
+            .unwrap());
+        assert!(!db.table_names().unwrap().contains(&"claimed".to_string()));
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_cached() {
+        use keyvalue::{
+            cache::{CacheLimits, CachedDB},
+            in_memory::InMemoryDB,
+            AsyncKeyValueDB,
+        };
+
+        let db = CachedDB::new(InMemoryDB::new(), InMemoryDB::new(), CacheLimits::default());
+        common::test_async_db(&db).await;
+
+        let limits = CacheLimits {
+            max_entries: Some(2),
+            max_bytes: None,
+        };
+        let db = CachedDB::new(InMemoryDB::new(), InMemoryDB::new(), limits);
+        for key in ["a", "b", "c"] {
+            db.insert("table", key, key.as_bytes()).await.unwrap();
+        }
+        assert_eq!(db.cache_usage(), (2, 4));
+        assert_eq!(db.fast().get("table", "a").await.unwrap(), None);
+        assert_eq!(db.get("table", "a").await.unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.fast().get("table", "b").await.unwrap(), None);
+        assert_eq!(db.keys("table").await.unwrap().len(), 3);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[test]
+    fn test_cached_miss_racing_write() {
+        use std::{
+            io,
+            sync::{mpsc, Mutex},
+        };
+
+        use futures::executor::block_on;
+        use keyvalue::{
+            cache::{CacheLimits, CachedDB},
+            in_memory::InMemoryDB,
+            AsyncKeyValueDB, KeyValueDB,
+        };
+
+        /// Holds its next `get` after reading the value, until released.
+        struct PausingDB {
+            inner: InMemoryDB,
+            read: Mutex<Option<(mpsc::Sender<()>, mpsc::Receiver<()>)>>,
+        }
+
+        impl KeyValueDB for PausingDB {
+            fn insert(
+                &self,
+                table_name: &str,
+                key: &str,
+                value: &[u8],
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                KeyValueDB::insert(&self.inner, table_name, key, value)
+            }
+            fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                let value = KeyValueDB::get(&self.inner, table_name, key)?;
+                if let Some((read, release)) = self.read.lock().unwrap().take() {
+                    read.send(()).unwrap();
+                    release.recv().unwrap();
+                }
+                Ok(value)
+            }
+            fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                KeyValueDB::remove(&self.inner, table_name, key)
+            }
+            fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+                KeyValueDB::iter(&self.inner, table_name)
+            }
+            fn table_names(&self) -> Result<Vec<String>, io::Error> {
+                KeyValueDB::table_names(&self.inner)
+            }
+        }
+
+        let (read_tx, read_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let slow = PausingDB {
+            inner: InMemoryDB::new(),
+            read: Mutex::new(None),
+        };
+        KeyValueDB::insert(&slow, "table", "key", b"old").unwrap();
+        *slow.read.lock().unwrap() = Some((read_tx, release_rx));
+        let db = CachedDB::new(InMemoryDB::new(), slow, CacheLimits::default());
+
+        std::thread::scope(|scope| {
+            let miss = scope.spawn(|| block_on(AsyncKeyValueDB::get(&db, "table", "key")));
+            read_rx.recv().unwrap();
+            block_on(AsyncKeyValueDB::insert(&db, "table", "key", b"new")).unwrap();
+            release_tx.send(()).unwrap();
+            assert_eq!(miss.join().unwrap().unwrap(), Some(b"old".to_vec()));
+        });
+        assert_eq!(
+            block_on(AsyncKeyValueDB::get(&db, "table", "key")).unwrap(),
+            Some(b"new".to_vec())
+        );
+        assert_eq!(
+            block_on(AsyncKeyValueDB::get(db.fast(), "table", "key")).unwrap(),
+            Some(b"new".to_vec())
+        );
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[test]
+    fn test_cached_racing_inserts() {
+        use std::{
+            io,
+            sync::{mpsc, Mutex},
+            time::Duration,
+        };
+
+        use futures::executor::block_on;
+        use keyvalue::{
+            cache::{CacheLimits, CachedDB},
+            in_memory::InMemoryDB,
+            AsyncKeyValueDB, KeyValueDB,
+        };
+
+        /// Holds its next `insert` after writing the value, until released.
+        struct PausingDB {
+            inner: InMemoryDB,
+            written: Mutex<Option<(mpsc::Sender<()>, mpsc::Receiver<()>)>>,
+        }
+
+        impl KeyValueDB for PausingDB {
+            fn insert(
+                &self,
+                table_name: &str,
+                key: &str,
+                value: &[u8],
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                let old_value = KeyValueDB::insert(&self.inner, table_name, key, value)?;
+                let pause = self.written.lock().unwrap().take();
+                if let Some((written, release)) = pause {
+                    written.send(()).unwrap();
+                    release.recv().unwrap();
+                }
+                Ok(old_value)
+            }
+            fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                KeyValueDB::get(&self.inner, table_name, key)
+            }
+            fn remove(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                KeyValueDB::remove(&self.inner, table_name, key)
+            }
+            fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+                KeyValueDB::iter(&self.inner, table_name)
+            }
+            fn table_names(&self) -> Result<Vec<String>, io::Error> {
+                KeyValueDB::table_names(&self.inner)
+            }
+        }
+
+        let (written_tx, written_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let slow = PausingDB {
+            inner: InMemoryDB::new(),
+            written: Mutex::new(Some((written_tx, release_rx))),
+        };
+        let db = CachedDB::new(InMemoryDB::new(), slow, CacheLimits::default());
+
+        std::thread::scope(|scope| {
+            let first =
+                scope.spawn(|| block_on(AsyncKeyValueDB::insert(&db, "table", "key", b"v1")));
+            written_rx.recv().unwrap();
+            // The second insert must wait for the first one to be cached
+            // instead of caching its value first.
+            let second =
+                scope.spawn(|| block_on(AsyncKeyValueDB::insert(&db, "table", "key", b"v2")));
+            std::thread::sleep(Duration::from_millis(20));
+            release_tx.send(()).unwrap();
+            first.join().unwrap().unwrap();
+            second.join().unwrap().unwrap();
+        });
+        assert_eq!(
+            block_on(AsyncKeyValueDB::get(db.fast(), "table", "key")).unwrap(),
+            Some(b"v2".to_vec())
+        );
+        assert_eq!(
+            block_on(AsyncKeyValueDB::get(&db, "table", "key")).unwrap(),
+            Some(b"v2".to_vec())
+        );
+    }
+
+    #[cfg(all(feature = "test", feature = "in-memory", feature = "redb"))]
+    #[test]
+    fn test_conformance() {
+        let report = keyvalue::conformance::run(&keyvalue::in_memory::InMemoryDB::new());
+        assert!(report.passed(), "{}", report.to_json());
+        assert!(report.compare_and_swap);
+        assert_eq!(report.atomic_old_value, Some(true));
+        assert_eq!(report.atomic_batches, Some(true));
+        assert!(report.prefix_pushdown.is_some());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_conformance_db");
+        let db = keyvalue::redb::RedbDB::open(&path).unwrap();
+        let report = keyvalue::conformance::run(&db);
+        assert!(report.passed(), "{}", report.to_json());
+        assert!(report.ordered_iteration);
+        assert!(report.ordered_prefix_iteration);
+        assert_eq!(report.atomic_old_value, Some(true));
+        assert_eq!(report.atomic_batches, Some(true));
+        assert!(keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
+
+        let report = futures::executor::block_on(keyvalue::conformance::run_async(
+            &keyvalue::in_memory::InMemoryDB::new(),
+        ));
+        assert!(report.passed(), "{}", report.to_json());
+        assert_eq!(report.atomic_batches, Some(true));
+    }
+
+    #[cfg(all(
+        feature = "test",
+        feature = "in-memory",
+        feature = "redb",
+        feature = "fs"
+    ))]
+    #[test]
+    fn test_testsuite() {
+        use keyvalue::testsuite::{
+            self,
+            linearizability::{self, Call, Event, History, Return},
+        };
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        testsuite::check_random(&db, 0, 50, 100).unwrap_or_else(|e| panic!("{}", e));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = keyvalue::redb::RedbDB::open(&temp_dir.path().join("test_testsuite_db")).unwrap();
+        testsuite::check_random(&db, 0, 20, 100).unwrap_or_else(|e| panic!("{}", e));
+        let db = keyvalue::fs::FsDB::open(&temp_dir.path().join("test_testsuite_fs")).unwrap();
+        testsuite::check_random(&db, 0, 10, 100).unwrap_or_else(|e| panic!("{}", e));
+        assert!(keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let history = History::new();
+        std::thread::scope(|scope| {
+            for process in 0..3 {
+                let (db, history) = (&db, &history);
+                scope.spawn(move || {
+                    for i in 0..10u8 {
+                        let call = match i % 4 {
+                            0 => Call::Insert {
+                                key: format!("{}", i % 3),
+                                value: vec![process as u8, i],
+                            },
+                            1 => Call::ApplyBatch {
+                                ops: vec![("a".into(), Some(vec![i])), ("b".into(), None)],
+                            },
+                            2 => Call::Iter,
+                            _ => Call::Remove {
+                                key: format!("{}", i % 3),
+                            },
+                        };
+                        history.call(db, "table", process, call).unwrap();
+                    }
+                });
+            }
+        });
+        history.check().unwrap_or_else(|e| panic!("{}", e));
+
+        let event = |call, ret, invoked, returned| Event {
+            process: 0,
+            call,
+            ret,
+            invoked,
+            returned,
+        };
+        let stale_read = [
+            event(
+                Call::Insert {
+                    key: "a".into(),
+                    value: b"1".to_vec(),
+                },
+                Return::Value(None),
+                0,
+                1,
+            ),
+            event(Call::Get { key: "a".into() }, Return::Value(None), 2, 3),
+        ];
+        let error = linearizability::check(&stale_read).unwrap_err();
+        assert_eq!(error.linearized.len(), 1);
+        assert_eq!(error.remaining, stale_read[1..]);
+    }
+
+    #[cfg(all(
+        feature = "test",
+        feature = "in-memory",
+        feature = "redb",
+        feature = "fs"
+    ))]
+    #[test]
+    fn test_concurrency() {
+        use keyvalue::testsuite::concurrency::{self, Config};
+
+        let config = Config::default();
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let report = concurrency::run(&db, &config).unwrap();
+        assert_eq!(report.operations, 800);
+        assert!(report.increments.is_some());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db =
+            keyvalue::redb::RedbDB::open(&temp_dir.path().join("test_concurrency_db")).unwrap();
+        concurrency::run(&db, &config).unwrap();
+        let db = keyvalue::fs::FsDB::open(&temp_dir.path().join("test_concurrency_fs")).unwrap();
+        concurrency::run(&db, &config).unwrap();
+        assert!(keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "test", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_testsuite() {
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        keyvalue::testsuite::check_random_async(&db, 0, 20, 100)
+            .await
+            .unwrap_or_else(|e| panic!("{}", e));
+        let config = keyvalue::testsuite::concurrency::Config::default();
+        keyvalue::testsuite::concurrency::run_async(&db, &config)
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_bytes_keys() {
+        use keyvalue::{decode_key, encode_key, KeyValueDB, KeyValueDBBytes};
+
+        assert_eq!(encode_key(&[0x00, 0xAB, 0xFF]), "00abff");
+        assert_eq!(decode_key("00abff").unwrap(), vec![0x00, 0xAB, 0xFF]);
+        assert!(decode_key("0").is_err());
+        assert!(decode_key("zz").is_err());
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let keys: [&[u8]; 4] = [&[0xFF], &[0x00, 0x01], &[0x00], &[0x10, 0x80]];
+        for key in keys {
+            assert_eq!(db.insert_bytes("table", key, key).unwrap(), None);
+        }
+        assert_eq!(
+            db.get_bytes("table", &[0x10, 0x80]).unwrap(),
+            Some(vec![0x10, 0x80])
+        );
+        assert_eq!(
+            db.keys_bytes("table").unwrap(),
+            vec![vec![0x00], vec![0x00, 0x01], vec![0x10, 0x80], vec![0xFF]]
+        );
+        assert_eq!(
+            db.iter_from_prefix_bytes("table", &[0x00]).unwrap(),
+            vec![
+                (vec![0x00], vec![0x00]),
+                (vec![0x00, 0x01], vec![0x00, 0x01])
+            ]
+        );
+        assert_eq!(
+            db.iter_from_range_bytes("table", &[0x00, 0x01], &[0xFF])
+                .unwrap()
+                .len(),
+            2
+        );
+        db.compare_and_swap_bytes("table", &[0xFF], Some(&[0xFF]), None)
+            .unwrap();
+        assert!(!db.contains_key_bytes("table", &[0xFF]).unwrap());
+
+        db.insert("table", "not hex", b"").unwrap();
+        assert!(db.iter_bytes("table").is_err());
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_native_async() {
+        use std::{collections::BTreeMap, io, sync::Mutex};
+
+        use keyvalue::{BoxedAsyncDB, NativeAsyncKeyValueDB};
+
+        let db = BoxedAsyncDB::new(keyvalue::in_memory::InMemoryDB::new());
+        common::test_async_db(&db).await;
+
+        #[derive(Default)]
+        struct NativeDB(Mutex<BTreeMap<String, BTreeMap<String, Vec<u8>>>>);
+
+        impl NativeAsyncKeyValueDB for NativeDB {
+            async fn insert(
+                &self,
+                table_name: &str,
+                key: &str,
+                value: &[u8],
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                let mut tables = self.0.lock().unwrap();
+                let table = tables.entry(table_name.into()).or_default();
+                Ok(table.insert(key.into(), value.to_vec()))
+            }
+            async fn get(&self, table_name: &str, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+                let tables = self.0.lock().unwrap();
+                Ok(tables
+                    .get(table_name)
+                    .and_then(|table| table.get(key).cloned()))
+            }
+            async fn remove(
+                &self,
+                table_name: &str,
+                key: &str,
+            ) -> Result<Option<Vec<u8>>, io::Error> {
+                let mut tables = self.0.lock().unwrap();
+                Ok(tables
+                    .get_mut(table_name)
+                    .and_then(|table| table.remove(key)))
+            }
+            async fn iter(&self, table_name: &str) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+                let tables = self.0.lock().unwrap();
+                Ok(tables
+                    .get(table_name)
+                    .map(|table| table.clone().into_iter().collect())
+                    .unwrap_or_default())
+            }
+            async fn table_names(&self) -> Result<Vec<String>, io::Error> {
+                Ok(self.0.lock().unwrap().keys().cloned().collect())
+            }
+        }
+
+        let db = NativeDB::default();
+        db.insert("table", "a", b"1").await.unwrap();
+        db.insert("table", "ab", b"2").await.unwrap();
+        db.insert("table", "b", b"3").await.unwrap();
+        assert_eq!(db.len("table").await.unwrap(), 3);
+        assert_eq!(
+            db.iter_from_prefix("table", "a").await.unwrap(),
+            vec![("a".into(), b"1".to_vec()), ("ab".into(), b"2".to_vec())]
+        );
+        assert_eq!(
+            db.get_range("table", "b", 0..4).await.unwrap(),
+            Some(b"3".to_vec())
+        );
+        db.delete_table("table").await.unwrap();
+        assert!(db.is_empty("table").await.unwrap());
+
+        let db = BoxedAsyncDB::new(db);
+        let db: &dyn keyvalue::AsyncKeyValueDB = &db;
+        assert_eq!(
+            keyvalue::AsyncKeyValueDBBytes::get_bytes(db, "table", &[0x01])
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(db.insert("table", "a", b"1").await.unwrap(), None);
+        assert_eq!(db.keys("table").await.unwrap(), vec!["a".to_string()]);
+        assert!(db.compare_and_swap("table", "a", None, None).await.is_err());
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_diff() {
+        use keyvalue::KeyValueDB;
+
+        let a = keyvalue::in_memory::InMemoryDB::new();
+        let b = keyvalue::in_memory::InMemoryDB::new();
+        a.insert("table1", "same", b"value").unwrap();
+        b.insert("table1", "same", b"value").unwrap();
+        a.insert("table1", "changed", b"old").unwrap();
+        b.insert("table1", "changed", b"new").unwrap();
+        a.insert("table1", "removed", b"value").unwrap();
+        b.insert("table2", "added", b"value").unwrap();
+
+        let report = keyvalue::diff(&a, &b).unwrap();
+        assert_eq!(report.tables.len(), 2);
+        let table1 = &report.tables["table1"];
+        assert!(table1.added.is_empty());
+        assert_eq!(table1.removed, vec!["removed".to_string()]);
+        assert_eq!(table1.changed, vec!["changed".to_string()]);
+        assert_eq!(report.tables["table2"].added, vec!["added".to_string()]);
+        assert!(keyvalue::diff(&a, &a).unwrap().is_empty());
+
+        assert_eq!(keyvalue::diff_checksums(&a, &b).unwrap(), report);
+        assert!(keyvalue::diff_checksums(&a, &a).unwrap().is_empty());
+        #[cfg(feature = "async")]
+        assert_eq!(
+            futures::executor::block_on(keyvalue::diff_checksums_async(&a, &b)).unwrap(),
+            report
+        );
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_prefix_stats() {
+        use keyvalue::{
+            stats::{prefix_stats, PrefixStats},
+            KeyValueDB,
+        };
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.insert("table", "user:1:name", b"alice").unwrap();
+        db.insert("table", "user:2:name", b"bob").unwrap();
+        db.insert("table", "order:1", b"12345678").unwrap();
+        db.insert("table", "config", b"{}").unwrap();
+
+        assert_eq!(
+            prefix_stats(&db, "table", Some(':'), 1).unwrap(),
+            vec![
+                PrefixStats {
+                    prefix: "config".to_string(),
+                    count: 1,
+                    key_bytes: 6,
+                    value_bytes: 2,
+                },
+                PrefixStats {
+                    prefix: "order:".to_string(),
+                    count: 1,
+                    key_bytes: 7,
+                    value_bytes: 8,
+                },
+                PrefixStats {
+                    prefix: "user:".to_string(),
+                    count: 2,
+                    key_bytes: 22,
+                    value_bytes: 8,
+                },
+            ]
+        );
+        let by_user = prefix_stats(&db, "table", Some(':'), 2).unwrap();
+        assert_eq!(
+            by_user
+                .iter()
+                .map(|stats| (stats.prefix.as_str(), stats.count))
+                .collect::<Vec<_>>(),
+            vec![
+                ("config", 1),
+                ("order:1", 1),
+                ("user:1:", 1),
+                ("user:2:", 1)
+            ]
+        );
+        let by_char = prefix_stats(&db, "table", None, 1).unwrap();
+        assert_eq!(
+            by_char
+                .iter()
+                .map(|stats| (stats.prefix.as_str(), stats.count, stats.value_bytes))
+                .collect::<Vec<_>>(),
+            vec![("c", 1, 2), ("o", 1, 8), ("u", 2, 8)]
+        );
+        assert!(prefix_stats(&db, "missing", Some(':'), 1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_dedupe_report() {
+        use keyvalue::{
+            stats::{dedupe_report, DuplicateValue},
+            KeyValueDB,
+        };
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.insert("table", "a", b"large value").unwrap();
+        db.insert("table", "b", b"large value").unwrap();
+        db.insert("table", "c", b"large value").unwrap();
+        db.insert("table", "d", b"tiny").unwrap();
+        db.insert("table", "e", b"tiny").unwrap();
+        db.insert("table", "f", b"unique").unwrap();
+
+        let report = dedupe_report(&db, "table").unwrap();
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.distinct_values, 3);
+        assert_eq!(report.value_bytes, 3 * 11 + 2 * 4 + 6);
+        assert_eq!(report.duplicate_bytes, 2 * 11 + 4);
+        assert_eq!(
+            report.most_duplicated,
+            vec![
+                DuplicateValue {
+                    checksum: db.checksum("table", "a").unwrap().unwrap(),
+                    size: 11,
+                    count: 3,
+                },
+                DuplicateValue {
+                    checksum: db.checksum("table", "d").unwrap().unwrap(),
+                    size: 4,
+                    count: 2,
+                },
+            ]
+        );
+
+        db.remove("table", "b").unwrap();
+        db.remove("table", "c").unwrap();
+        db.remove("table", "e").unwrap();
+        let report = dedupe_report(&db, "table").unwrap();
+        assert_eq!((report.entries, report.distinct_values), (3, 3));
+        assert_eq!(report.duplicate_bytes, 0);
+        assert!(report.most_duplicated.is_empty());
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_db_info() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_db_info_db");
+        let db = keyvalue::redb::RedbDB::open(&path).unwrap();
+        assert!(keyvalue::meta::db_info(&db).unwrap().is_none());
+        let info = keyvalue::meta::init_db_info(&db).unwrap();
+        assert_eq!(info.layout_version, keyvalue::meta::LAYOUT_VERSION);
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.backend, "redb");
+        assert!(info.created_at > 0);
+        drop(db);
+        let db = keyvalue::redb::RedbDB::open(&path).unwrap();
+        assert_eq!(keyvalue::meta::db_info(&db).unwrap(), Some(info.clone()));
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert_eq!(keyvalue::meta::init_db_info(&db).unwrap(), info);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_ingest() {
+        use keyvalue::ingest::{ingest, IngestOptions, IngestReport};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let stream = futures::stream::iter((0..10).map(|i| (format!("key{}", i), vec![i])));
+        let mut calls = 0;
+        let mut progress = |_: &IngestReport| calls += 1;
+        let report = ingest(
+            &db,
+            "table",
+            stream,
+            IngestOptions {
+                batch_size: 4,
+                progress: Some(&mut progress),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.written, 10);
+        assert_eq!(report.skipped, 0);
+        assert_eq!(calls, 3);
+        assert_eq!(
+            keyvalue::AsyncKeyValueDB::get(&db, "table", "key3")
+                .await
+                .unwrap(),
+            Some(vec![3])
+        );
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory", feature = "redb"))]
+    #[tokio::test]
+    async fn test_migrate() {
+        use keyvalue::{
+            migrate::{copy_all, copy_table, MigrateOptions, MigrateReport},
+            AsyncKeyValueDB,
+        };
+
+        let src = keyvalue::in_memory::InMemoryDB::new();
+        for i in 0..10 {
+            src.insert("table1", &format!("key{}", i), &[i])
+                .await
+                .unwrap();
+        }
+        src.insert("table2", "key", b"value").await.unwrap();
+        src.insert("table2", "skip", b"value").await.unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dst = keyvalue::redb::RedbDB::open(&temp_dir.path().join("test_migrate_db")).unwrap();
+        let mut batches = 0;
+        let mut progress = |_: &MigrateReport| batches += 1;
+        let report = copy_all(
+            &src,
+            &dst,
+            MigrateOptions {
+                batch_size: 4,
+                filter: Some(&|_, key| key != "skip"),
+                progress: Some(&mut progress),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report,
+            MigrateReport {
+                tables: 2,
+                entries: 11
+            }
+        );
+        assert_eq!(batches, 4);
+        let mut entries = src.iter("table1").await.unwrap();
+        entries.sort();
+        assert_eq!(dst.iter("table1").await.unwrap(), entries);
+        assert_eq!(dst.get("table2", "skip").await.unwrap(), None);
+
+        let other = keyvalue::in_memory::InMemoryDB::new();
+        let report = copy_table(&dst, &other, "table2", MigrateOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            MigrateReport {
+                tables: 1,
+                entries: 1
+            }
+        );
+        assert_eq!(
+            other.table_names().await.unwrap(),
+            vec!["table2".to_string()]
+        );
+    }
+
+    #[cfg(all(feature = "in-memory", feature = "redb"))]
+    #[test]
+    fn test_snapshot() {
+        use keyvalue::{
+            snapshot::{export_snapshot, import_snapshot},
+            KeyValueDB,
+        };
+
+        let src = keyvalue::in_memory::InMemoryDB::new();
+        src.insert("table1", "key", b"value").unwrap();
+        src.insert("table1", "empty", b"").unwrap();
+        src.insert("table2", "", &[0; 100_000]).unwrap();
+
+        let mut snapshot = Vec::new();
+        assert_eq!(export_snapshot(&src, &mut snapshot).unwrap(), 3);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dst = keyvalue::redb::RedbDB::open(&temp_dir.path().join("test_snapshot_db")).unwrap();
+        assert_eq!(import_snapshot(&dst, snapshot.as_slice()).unwrap(), 3);
+        assert!(keyvalue::diff(&src, &dst).unwrap().is_empty());
+
+        assert!(import_snapshot(&dst, &snapshot[..snapshot.len() - 1]).is_err());
+        assert!(import_snapshot(&dst, &b"not a snapshot"[..]).is_err());
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_named_snapshots() {
+        use keyvalue::{
+            snapshot::{
+                create_named_snapshot, delete_snapshot, list_snapshots, read_snapshot,
+                restore_snapshot,
+            },
+            KeyValueDB,
+        };
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.insert("table", "a", b"1").unwrap();
+        let first = create_named_snapshot(&db, "table", "first").unwrap();
+        db.insert("table", "b", b"2").unwrap();
+        db.insert("table::nested", "c", b"3").unwrap();
+        create_named_snapshot(&db, "table::nested", "nested").unwrap();
+        let second = create_named_snapshot(&db, "table", "second").unwrap();
+        assert_eq!((first.version, second.version), (1, 2));
+        assert_eq!(
+            create_named_snapshot(&db, "table", "first")
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert!(create_named_snapshot(&db, "table", "a::b").is_err());
+        assert_eq!(list_snapshots(&db, "table").unwrap(), vec![first, second]);
+
+        db.insert("table", "a", b"changed").unwrap();
+        assert_eq!(
+            read_snapshot(&db, "table", "second").unwrap(),
+            vec![
+                ("a".to_string(), b"1".to_vec()),
+                ("b".to_string(), b"2".to_vec())
+            ]
+        );
+        restore_snapshot(&db, "table", "first").unwrap();
+        assert_eq!(
+            db.iter("table").unwrap(),
+            vec![("a".to_string(), b"1".to_vec())]
+        );
+
+        delete_snapshot(&db, "table", "first").unwrap();
+        assert_eq!(
+            read_snapshot(&db, "table", "first").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert_eq!(list_snapshots(&db, "table").unwrap().len(), 1);
+        delete_snapshot(&db, "table", "second").unwrap();
+        let third = create_named_snapshot(&db, "table", "third").unwrap();
+        assert_eq!(third.version, 3);
+        assert_eq!(list_snapshots(&db, "table").unwrap(), vec![third.clone()]);
+
+        // A name claimed by a snapshot still being copied is taken but not
+        // listed yet.
+        db.insert(keyvalue::meta::META_TABLE, "snapshot::table::copying", b"")
+            .unwrap();
+        assert_eq!(
+            create_named_snapshot(&db, "table", "copying")
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            read_snapshot(&db, "table", "copying").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert_eq!(list_snapshots(&db, "table").unwrap(), vec![third]);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_named_snapshots_async() {
+        use keyvalue::{
+            snapshot::{create_named_snapshot_async, read_snapshot_async, restore_snapshot_async},
+            AsyncKeyValueDB,
+        };
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.insert("table", "key", b"before").await.unwrap();
+        create_named_snapshot_async(&db, "table", "before")
+            .await
+            .unwrap();
+        db.insert("table", "key", b"after").await.unwrap();
+        restore_snapshot_async(&db, "table", "before")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get("table", "key").await.unwrap(),
+            Some(b"before".to_vec())
+        );
+        assert!(read_snapshot_async(&db, "table", "missing").await.is_err());
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[test]
+    fn test_observable() {
+        use keyvalue::{
+            observable::{ChangeEvent, ObservableDB},
+            KeyValueDB,
+        };
+
+        let db = ObservableDB::new(keyvalue::in_memory::InMemoryDB::new());
+        common::test_db(&db);
+        let watcher = db.watch("table", "a");
+        drop(db.watch("other", ""));
+        db.insert("table", "a1", b"value").unwrap();
+        db.insert("table", "b1", b"value").unwrap();
+        db.insert("other", "a1", b"value").unwrap();
+        db.remove("table", "a1").unwrap();
+        db.remove("table", "a1").unwrap();
+        db.compare_and_swap("table", "a2", None, Some(b"new"))
+            .unwrap();
+        db.delete_table("table").unwrap();
+        db.insert("other", "a3", b"other").unwrap();
+        db.swap_tables("table", "other").unwrap();
+        assert!(!db
+            .ensure_table_with("table", &mut || vec![("a4".to_string(), b"seed".to_vec())])
+            .unwrap());
+        db.clear().unwrap();
+        assert!(db
+            .ensure_table_with("table", &mut || vec![("a4".to_string(), b"seed".to_vec())])
+            .unwrap());
+        drop(db);
+
+        let events = futures::executor::block_on_stream(watcher).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                ChangeEvent::Insert {
+                    table_name: "table".to_string(),
+                    key: "a1".to_string(),
+                    value: b"value".to_vec(),
+                },
+                ChangeEvent::Remove {
+                    table_name: "table".to_string(),
+                    key: "a1".to_string(),
+                },
+                ChangeEvent::Insert {
+                    table_name: "table".to_string(),
+                    key: "a2".to_string(),
+                    value: b"new".to_vec(),
+                },
+                ChangeEvent::DeleteTable {
+                    table_name: "table".to_string(),
+                },
+                ChangeEvent::DeleteTable {
+                    table_name: "table".to_string(),
+                },
+                ChangeEvent::Insert {
+                    table_name: "table".to_string(),
+                    key: "a1".to_string(),
+                    value: b"value".to_vec(),
+                },
+                ChangeEvent::Insert {
+                    table_name: "table".to_string(),
+                    key: "a3".to_string(),
+                    value: b"other".to_vec(),
+                },
+                ChangeEvent::DeleteTable {
+                    table_name: "table".to_string(),
+                },
+                ChangeEvent::Insert {
+                    table_name: "table".to_string(),
+                    key: "a4".to_string(),
+                    value: b"seed".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_observable() {
+        use futures::StreamExt;
+        use keyvalue::{
+            observable::{AsyncObservableDB, ChangeEvent},
+            AsyncKeyValueDB,
+        };
+
+        let db = AsyncObservableDB::new(keyvalue::in_memory::InMemoryDB::new());
+        common::test_async_db(&db).await;
+        let mut watcher = db.watch("table", "");
+        db.insert("table", "key", b"value").await.unwrap();
+        assert_eq!(
+            watcher.next().await,
+            Some(ChangeEvent::Insert {
+                table_name: "table".to_string(),
+                key: "key".to_string(),
+                value: b"value".to_vec(),
+            })
+        );
+        db.swap_tables("table", "other").await.unwrap();
+        assert_eq!(
+            watcher.next().await,
+            Some(ChangeEvent::DeleteTable {
+                table_name: "table".to_string(),
+            })
+        );
+        db.swap_tables("table", "other").await.unwrap();
+        assert_eq!(
+            watcher.next().await,
+            Some(ChangeEvent::DeleteTable {
+                table_name: "table".to_string(),
+            })
+        );
+        assert_eq!(
+            watcher.next().await,
+            Some(ChangeEvent::Insert {
+                table_name: "table".to_string(),
+                key: "key".to_string(),
+                value: b"value".to_vec(),
+            })
+        );
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_key_normalizing() {
+        use keyvalue::{
+            normalize::{KeyNormalizingDB, Lowercase},
+            KeyValueDB,
+        };
+
+        let db = KeyNormalizingDB::new(keyvalue::in_memory::InMemoryDB::new(), Lowercase);
+        common::test_db(&db);
+        db.insert("table", "Key", b"value").unwrap();
+        assert_eq!(db.get("table", "KEY").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(
+            db.insert("table", "kEy", b"other").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(db.iter_from_prefix("table", "K").unwrap().len(), 1);
+        assert_eq!(db.keys("table").unwrap(), vec!["key".to_string()]);
+        assert!(db
+            .ensure_table_with("seeded", &mut || vec![(
+                "Seed".to_string(),
+                b"value".to_vec()
+            )])
+            .unwrap());
+        assert_eq!(db.get("seeded", "SEED").unwrap(), Some(b"value".to_vec()));
+
+        let db = KeyNormalizingDB::new(keyvalue::in_memory::InMemoryDB::new(), |key: &str| {
+            key.trim().to_string()
+        });
+        db.insert("table", " key ", b"value").unwrap();
+        assert!(db.contains_key("table", "key").unwrap());
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_key_normalizing() {
+        use keyvalue::{
+            normalize::{AsyncKeyNormalizingDB, Lowercase},
+            AsyncKeyValueDB,
+        };
+
+        let db = AsyncKeyNormalizingDB::new(keyvalue::in_memory::InMemoryDB::new(), Lowercase);
+        common::test_async_db(&db).await;
+        db.insert("table", "Key", b"value").await.unwrap();
+        assert!(db.remove("table", "KEY").await.unwrap().is_some());
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_authorized() {
+        use keyvalue::{
+            authorized::{AuthorizedDB, Operation},
+            KeyValueDB, WriteBatch,
+        };
+
+        let db = AuthorizedDB::new(
+            keyvalue::in_memory::InMemoryDB::new(),
+            (),
+            |_: &(), _: &str, _: Operation| true,
+        );
+        common::test_db(&db);
+
+        let db = AuthorizedDB::new(
+            db.into_inner(),
+            "alice".to_string(),
+            |actor: &String, table_name: &str, operation: Operation| {
+                table_name == actor.as_str()
+                    || (table_name == "public" && operation == Operation::Read)
+            },
+        );
+        db.inner().insert("public", "key", b"value").unwrap();
+        db.inner().insert("bob", "key", b"value").unwrap();
+
+        db.insert("alice", "key", b"value").unwrap();
+        assert_eq!(db.get("public", "key").unwrap(), Some(b"value".to_vec()));
+        let denied = db.insert("public", "key", b"other").unwrap_err();
+        assert_eq!(denied.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(db.get("bob", "key").is_err());
+        assert!(db.delete_table("public").is_err());
+        assert!(db.clear().is_err());
+
+        let mut table_names = db.table_names().unwrap();
+        table_names.sort();
+        assert_eq!(table_names, vec!["alice".to_string(), "public".to_string()]);
+
+        let mut batch = WriteBatch::new();
+        batch
+            .insert("alice", "other", b"value")
+            .insert("bob", "key", b"other");
+        assert!(db.apply_batch(batch).is_err());
+        assert!(!db.contains_key("alice", "other").unwrap());
+
+        // A write-only actor can neither read a value through the mismatch of
+        // compare_and_swap nor move an unreadable table into a readable one.
+        let db = AuthorizedDB::new(
+            db.into_inner(),
+            (),
+            |_: &(), table_name: &str, operation: Operation| {
+                operation == Operation::Write || table_name == "public"
+            },
+        );
+        let denied = db
+            .compare_and_swap("bob", "key", Some(b"guess"), None)
+            .unwrap_err();
+        assert!(matches!(denied, keyvalue::CompareAndSwapError::Io(e)
+            if e.kind() == std::io::ErrorKind::PermissionDenied));
+        assert!(db.swap_tables("bob", "public").is_err());
+        assert_eq!(db.get("public", "key").unwrap(), Some(b"value".to_vec()));
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_authorized() {
+        use keyvalue::{
+            authorized::{AsyncAuthorizedDB, Operation},
+            AsyncKeyValueDB,
+        };
+
+        let db = AsyncAuthorizedDB::new(
+            keyvalue::in_memory::InMemoryDB::new(),
+            (),
+            |_: &(), _: &str, _: Operation| true,
+        );
+        common::test_async_db(&db).await;
+
+        let db = AsyncAuthorizedDB::new(
+            db.into_inner(),
+            (),
+            |_: &(), _: &str, operation: Operation| operation == Operation::Read,
+        );
+        assert!(db.insert("table", "key", b"value").await.is_err());
+        assert_eq!(db.get("table", "key").await.unwrap(), None);
+
+        let db = AsyncAuthorizedDB::new(
+            db.into_inner(),
+            (),
+            |_: &(), table_name: &str, operation: Operation| {
+                operation == Operation::Write || table_name == "public"
+            },
+        );
+        db.inner().insert("secret", "key", b"value").await.unwrap();
+        assert!(db
+            .compare_and_swap("secret", "key", Some(b"guess"), None)
+            .await
+            .is_err());
+        assert!(db.swap_tables("secret", "public").await.is_err());
+        assert_eq!(db.get("public", "key").await.unwrap(), None);
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_readonly() {
+        use keyvalue::{readonly::ReadOnlyDB, KeyValueDB, ReadOnly};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        db.insert("table", "key", b"value").unwrap();
+        let db = ReadOnlyDB::new(db);
+
+        assert_eq!(db.get("table", "key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.table_names().unwrap(), vec!["table".to_string()]);
+        assert!(!db.ensure_table_with("table", &mut Vec::new).unwrap());
+
+        let e = db.insert("table", "key", b"other").unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            ReadOnly::from_io_error(&e),
+            Some(&ReadOnly {
+                operation: "insert"
+            })
+        );
+        assert!(db.remove("table", "key").is_err());
+        assert!(db.ensure_table_with("other", &mut Vec::new).is_err());
+        assert!(db.compare_and_swap("table", "key", None, None).is_err());
+        assert!(db.clear().is_err());
+        assert_eq!(db.len("table").unwrap(), 1);
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_scoped() {
+        use keyvalue::{
+            scoped::{list_namespaces, ScopedDB},
+            KeyValueDB,
+        };
+
+        let db = ScopedDB::new(keyvalue::in_memory::InMemoryDB::new(), "tenant");
+        common::test_db(&db);
+
+        let other = ScopedDB::new(keyvalue::in_memory::InMemoryDB::new(), "other");
+        other.inner().insert("shared", "key", b"unscoped").unwrap();
+        other.insert("table", "key", b"other").unwrap();
+        let db = ScopedDB::new(other.into_inner(), "tenant");
+        db.insert("table", "key", b"tenant").unwrap();
+
+        assert_eq!(db.get("table", "key").unwrap(), Some(b"tenant".to_vec()));
+        assert_eq!(db.table_names().unwrap(), vec!["table".to_string()]);
+        assert_eq!(
+            list_namespaces(db.inner()).unwrap(),
+            vec!["other".to_string(), "tenant".to_string()]
+        );
+
+        db.clear().unwrap();
+        assert_eq!(
+            db.inner().get("other::table", "key").unwrap(),
+            Some(b"other".to_vec())
+        );
+        assert!(db.inner().contains_key("shared", "key").unwrap());
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_secondary_index() {
+        use keyvalue::{index::SecondaryIndexDB, KeyValueDB, WriteBatch};
+
+        fn tags(value: &[u8]) -> Vec<String> {
+            String::from_utf8_lossy(value)
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect()
+        }
+        fn first_byte(value: &[u8]) -> Vec<String> {
+            value
+                .first()
+                .map(|byte| byte.to_string())
+                .into_iter()
+                .collect()
+        }
+
+        let db = SecondaryIndexDB::new(keyvalue::in_memory::InMemoryDB::new()).with_index(
+            "table1",
+            "first_byte",
+            first_byte,
+        );
+        common::test_db(&db);
+
+        let db = SecondaryIndexDB::new(keyvalue::in_memory::InMemoryDB::new())
+            .with_index("users", "tag", tags);
+        let by_tag = |tag| {
+            db.get_by_index("users", "tag", tag)
+                .unwrap()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+        db.insert("users", "bob", b"admin,dev").unwrap();
+        db.insert("users", "alice", b"dev").unwrap();
+        assert_eq!(by_tag("dev"), vec!["alice", "bob"]);
+        assert_eq!(by_tag("admin"), vec!["bob"]);
+
+        db.insert("users", "bob", b"ops").unwrap();
+        db.remove("users", "alice").unwrap();
+        assert!(by_tag("dev").is_empty());
+        assert_eq!(by_tag("ops"), vec!["bob"]);
+
+        let mut batch = WriteBatch::new();
+        batch
+            .insert("users", "carol", b"dev")
+            .insert("users", "carol", b"qa")
+            .remove("users", "bob");
+        db.apply_batch(batch).unwrap();
+        assert!(by_tag("dev").is_empty());
+        assert!(by_tag("ops").is_empty());
+        assert_eq!(by_tag("qa"), vec!["carol"]);
+
+        db.compare_and_swap("users", "carol", Some(b"qa"), Some(b"dev"))
+            .unwrap();
+        assert_eq!(by_tag("dev"), vec!["carol"]);
+        assert!(by_tag("qa").is_empty());
+        assert_eq!(db.table_names().unwrap(), vec!["users".to_string()]);
+        assert!(db.get_by_index("users", "missing", "dev").is_err());
+
+        db.inner().insert("users", "dave", b"dev").unwrap();
+        assert_eq!(by_tag("dev"), vec!["carol"]);
+        db.rebuild_index("users", "tag").unwrap();
+        assert_eq!(by_tag("dev"), vec!["carol", "dave"]);
+
+        db.insert("staff", "erin", b"ops").unwrap();
+        db.swap_tables("users", "staff").unwrap();
+        assert_eq!(by_tag("ops"), vec!["erin"]);
+        assert!(by_tag("dev").is_empty());
+        db.swap_tables("users", "staff").unwrap();
+        assert_eq!(by_tag("dev"), vec!["carol", "dave"]);
+        db.delete_table("staff").unwrap();
+
+        db.delete_table("users").unwrap();
+        assert!(db.inner().table_names().unwrap().is_empty());
+        assert!(db
+            .ensure_table_with("users", &mut || vec![("frank".to_string(), b"qa".to_vec())])
+            .unwrap());
+        assert_eq!(by_tag("qa"), vec!["frank"]);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_secondary_index() {
+        use keyvalue::{index::AsyncSecondaryIndexDB, AsyncKeyValueDB};
+
+        fn length(value: &[u8]) -> Vec<String> {
+            vec![value.len().to_string()]
+        }
+
+        let db = AsyncSecondaryIndexDB::new(keyvalue::in_memory::InMemoryDB::new())
+            .with_index("table1", "length", length);
+        common::test_async_db(&db).await;
+
+        db.insert("table1", "a", b"xy").await.unwrap();
+        db.insert("table1", "b", b"zw").await.unwrap();
+        db.insert("table1", "a", b"x").await.unwrap();
+        let entries = db.get_by_index("table1", "length", "2").await.unwrap();
+        assert_eq!(entries, vec![("b".to_string(), b"zw".to_vec())]);
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_run_update() {
+        use keyvalue::{
+            update::{run_update, DEFAULT_MAX_ATTEMPTS},
+            CompareAndSwapError, KeyValueDB,
+        };
+
+        let increment = |value: Option<&[u8]>| {
+            let count = value.map_or(0, |value| u64::from_le_bytes(value.try_into().unwrap()));
+            Ok(Some((count + 1).to_le_bytes().to_vec()))
+        };
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        run_update(&db, "table", "counter", usize::MAX, increment).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            db.get("table", "counter").unwrap(),
+            Some(200u64.to_le_bytes().to_vec())
+        );
+
+        let mut calls = 0;
+        let result = run_update(&db, "table", "key", DEFAULT_MAX_ATTEMPTS, |_| {
+            calls += 1;
+            db.insert("table", "key", &[calls])?;
+            Ok(None)
+        });
+        assert!(matches!(
+            result,
+            Err(CompareAndSwapError::Mismatch { current: Some(_) })
+        ));
+        assert_eq!(calls as usize, DEFAULT_MAX_ATTEMPTS);
+
+        let result = run_update(&db, "table", "key", 1, |value| {
+            assert!(value.is_some());
+            Ok(None)
+        });
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(db.get("table", "key").unwrap(), None);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_run_update_async() {
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        for expected in [b"a".to_vec(), b"aa".to_vec()] {
+            let written = keyvalue::update::run_update_async(&db, "table", "key", 1, |value| {
+                let mut value = value.unwrap_or_default().to_vec();
+                value.push(b'a');
+                Ok(Some(value))
+            })
+            .await
+            .unwrap();
+            assert_eq!(written, Some(expected));
+        }
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_merge() {
+        use keyvalue::{
+            update::{merge, DEFAULT_MAX_ATTEMPTS},
+            KeyValueDB,
+        };
+
+        // A grow-only set of bytes, merged by union.
+        let union = |current: Option<&[u8]>, operand: &[u8]| {
+            let mut set = current.unwrap_or_default().to_vec();
+            for byte in operand {
+                if !set.contains(byte) {
+                    set.push(*byte);
+                }
+            }
+            set.sort();
+            set
+        };
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        std::thread::scope(|scope| {
+            for thread in 0..4u8 {
+                let db = &db;
+                scope.spawn(move || {
+                    for i in 0..25u8 {
+                        merge(db, "table", "set", &[thread * 25 + i], usize::MAX, union).unwrap();
+                    }
+                });
+            }
+        });
+        let merged = merge(&db, "table", "set", &[0, 100], DEFAULT_MAX_ATTEMPTS, union).unwrap();
+        assert_eq!(merged, (0..=100).collect::<Vec<u8>>());
+
+        // A concurrent writer changing the value before every attempt.
+        let attempts = std::sync::atomic::AtomicU8::new(0);
+        let e = merge(&db, "table", "set", &[0], 3, |current, operand| {
+            let attempt = attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            db.insert("table", "set", &[attempt]).unwrap();
+            union(current, operand)
+        })
+        .unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::Other);
+        assert_eq!(attempts.into_inner(), 3);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_merge_async() {
+        use keyvalue::update::{merge_async, DEFAULT_MAX_ATTEMPTS};
+
+        let max = |current: Option<&[u8]>, operand: &[u8]| {
+            current
+                .map_or(operand, |current| current.max(operand))
+                .to_vec()
+        };
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        for (operand, expected) in [(b"b", b"b"), (b"a", b"b"), (b"c", b"c")] {
+            let merged = merge_async(&db, "table", "max", operand, DEFAULT_MAX_ATTEMPTS, max)
+                .await
+                .unwrap();
+            assert_eq!(merged, expected.to_vec());
+        }
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_numeric_values() {
+        use keyvalue::{sequence::next_sequence, KeyValueDB, KeyValueDBNumeric};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        assert_eq!(db.get_u64("table", "u64").unwrap(), None);
+        db.put_u64("table", "u64", u64::MAX).unwrap();
+        assert_eq!(db.get_u64("table", "u64").unwrap(), Some(u64::MAX));
+        db.put_i64("table", "i64", -2).unwrap();
+        assert_eq!(db.get_i64("table", "i64").unwrap(), Some(-2));
+        assert_eq!(
+            db.get("table", "i64").unwrap(),
+            Some((-2i64).to_le_bytes().to_vec())
+        );
+        db.put_f64("table", "f64", 1.5).unwrap();
+        assert_eq!(db.get_f64("table", "f64").unwrap(), Some(1.5));
+
+        assert_eq!(db.increment_u64("table", "counter", 2).unwrap(), 2);
+        assert_eq!(db.increment_u64("table", "counter", 3).unwrap(), 5);
+        assert_eq!(db.get_u64("table", "counter").unwrap(), Some(5));
+        assert!(db.increment_u64("table", "u64", 1).is_err());
+
+        next_sequence(&db, "orders").unwrap();
+        assert_eq!(
+            db.get_u64(keyvalue::sequence::SEQUENCE_TABLE, "orders")
+                .unwrap(),
+            Some(1)
+        );
+
+        db.insert("table", "short", b"1").unwrap();
+        assert!(db.get_u64("table", "short").is_err());
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_numeric_values_async() {
+        use keyvalue::AsyncKeyValueDBNumeric;
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        AsyncKeyValueDBNumeric::put_i64(&db, "table", "i64", i64::MIN)
+            .await
+            .unwrap();
+        assert_eq!(
+            AsyncKeyValueDBNumeric::get_i64(&db, "table", "i64")
+                .await
+                .unwrap(),
+            Some(i64::MIN)
+        );
+        for expected in [4, 8] {
+            assert_eq!(
+                AsyncKeyValueDBNumeric::increment_u64(&db, "table", "counter", 4)
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_sequence() {
+        use keyvalue::sequence::{next_sequence, reserve, Sequence};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        assert_eq!(next_sequence(&db, "orders").unwrap(), 1);
+        assert_eq!(next_sequence(&db, "orders").unwrap(), 2);
+        assert_eq!(next_sequence(&db, "invoices").unwrap(), 1);
+        assert_eq!(reserve(&db, "orders", 3).unwrap(), 3..6);
+
+        let a = Sequence::new("orders", 10);
+        let b = Sequence::new("orders", 10);
+        assert_eq!(a.next(&db).unwrap(), 6);
+        assert_eq!(b.next(&db).unwrap(), 16);
+        assert_eq!(a.next(&db).unwrap(), 7);
+        assert_eq!(next_sequence(&db, "orders").unwrap(), 26);
+    }
+
+    #[cfg(all(feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_sequence() {
+        use keyvalue::sequence::{next_sequence_async, AsyncSequence};
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let sequence = AsyncSequence::new("orders", 2);
+        assert_eq!(sequence.next(&db).await.unwrap(), 1);
+        assert_eq!(sequence.next(&db).await.unwrap(), 2);
+        assert_eq!(next_sequence_async(&db, "orders").await.unwrap(), 3);
+        assert_eq!(sequence.next(&db).await.unwrap(), 4);
+    }
+
+    #[cfg(all(feature = "clock", feature = "in-memory"))]
+    #[test]
+    fn test_hlc_clock() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        use keyvalue::clock::{Hlc, HlcClock};
+
+        static NOW: AtomicU64 = AtomicU64::new(1_000);
+        let now = || NOW.load(Ordering::SeqCst);
+
+        let clock = HlcClock::with_time_source(now).with_max_drift(500);
+        assert_eq!(clock.now(), Hlc::new(1_000, 0));
+        assert_eq!(clock.now(), Hlc::new(1_000, 1));
+        NOW.store(900, Ordering::SeqCst);
+        assert_eq!(clock.now(), Hlc::new(1_000, 2));
+        assert_eq!(
+            clock.update(Hlc::new(1_200, 7)).unwrap(),
+            Hlc::new(1_200, 8)
+        );
+        assert_eq!(
+            clock.update(Hlc::new(1_100, 9)).unwrap(),
+            Hlc::new(1_200, 9)
+        );
+        assert!(clock.update(Hlc::new(1_401, 0)).is_err());
+        NOW.store(2_000, Ordering::SeqCst);
+        assert_eq!(
+            clock.update(Hlc::new(1_500, 3)).unwrap(),
+            Hlc::new(2_000, 0)
+        );
+
+        let hlc = Hlc::new(2_000, 1);
+        assert_eq!(hlc.to_string().parse::<Hlc>().unwrap(), hlc);
+        assert!(Hlc::new(1_999, u16::MAX).to_string() < hlc.to_string());
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        clock.save(&db).unwrap();
+        let restarted = HlcClock::with_time_source(now);
+        NOW.store(1_000, Ordering::SeqCst);
+        assert_eq!(restarted.load(&db).unwrap(), Hlc::new(2_000, 0));
+        assert_eq!(restarted.now(), Hlc::new(2_000, 1));
+        HlcClock::with_time_source(now).save(&db).unwrap();
+        assert_eq!(HlcClock::new().load(&db).unwrap(), Hlc::new(2_000, 0));
+    }
+
+    #[cfg(all(feature = "ulid", feature = "in-memory"))]
+    #[test]
+    fn test_ulid() {
+        use keyvalue::{
+            ulid::{Ulid, UlidGenerator},
+            KeyValueDB,
+        };
+
+        let ulid = Ulid::from_parts(1_700_000_000_000, 42);
+        let encoded = ulid.to_string();
+        assert_eq!(encoded.len(), 26);
+        assert_eq!(encoded.parse::<Ulid>().unwrap(), ulid);
+        assert_eq!(encoded.to_lowercase().parse::<Ulid>().unwrap(), ulid);
+        assert_eq!(ulid.timestamp_ms(), 1_700_000_000_000);
+        assert!("8ZZZZZZZZZZZZZZZZZZZZZZZZZ".parse::<Ulid>().is_err());
+        assert_eq!(
+            Ulid::from_parts(0x0123_4567_89AB, 0).to_uuid_v7(),
+            "01234567-89ab-7000-8000-000000000000"
+        );
+
+        let db = keyvalue::in_memory::InMemoryDB::new();
+        let mut generator = UlidGenerator::new();
+        let ulids = (0..100)
+            .map(|_| generator.generate().unwrap())
+            .collect::<Vec<_>>();
+        assert!(ulids.windows(2).all(|pair| pair[0] < pair[1]));
+        for (i, ulid) in ulids.iter().enumerate() {
+            db.insert("events", &ulid.to_string(), &[i as u8]).unwrap();
+        }
+
+        let first = ulids[0].timestamp_ms();
+        let window = db
+            .iter_from_range(
+                "events",
+                &Ulid::min_at(first).to_string(),
+                &Ulid::max_at(u64::MAX).to_string(),
+            )
+            .unwrap();
+        assert_eq!(window.len(), 100);
+        assert_eq!(window[0].1, vec![0]);
+        assert_eq!(window[99].1, vec![99]);
+    }
+
+    #[cfg(all(feature = "metrics", feature = "in-memory"))]
+    #[test]
+    fn test_instrumented() {
+        use keyvalue::{instrumented::InstrumentedDB, KeyValueDB};
+
+        let db = InstrumentedDB::new(keyvalue::in_memory::InMemoryDB::new());
+        db.insert("table", "key", b"value").unwrap();
+        db.get("table", "key").unwrap();
+        db.get("table", "other").unwrap();
+        db.compare_and_swap("table", "key", None, None).unwrap_err();
+
+        let stats = db.stats();
+        let operations = stats
+            .iter()
+            .map(|stats| (stats.table_name.as_str(), stats.operation, stats.calls))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operations,
+            vec![
+                ("table", "compare_and_swap", 1),
+                ("table", "get", 2),
+                ("table", "insert", 1),
+            ]
+        );
+        assert_eq!(stats[0].errors, 1);
+        assert_eq!(stats[1].latency_histogram.iter().sum::<u64>(), 2);
+
+        db.reset();
+        assert!(db.stats().is_empty());
+    }
+
+    #[cfg(all(feature = "tracing", feature = "in-memory"))]
+    #[test]
+    fn test_traced() {
+        let db = keyvalue::traced::TracedDB::new(keyvalue::in_memory::InMemoryDB::new());
+        common::test_db(&db);
+    }
+
+    #[cfg(all(feature = "tracing", feature = "async", feature = "in-memory"))]
+    #[tokio::test]
+    async fn test_async_traced() {
+        let db = keyvalue::traced::AsyncTracedDB::new(keyvalue::in_memory::InMemoryDB::new());
+        common::test_async_db(&db).await;
+    }
+
+    #[cfg(feature = "in-memory")]
+    #[test]
+    fn test_shadow_read() {
+        use std::sync::{Arc, Mutex};
+
+        use keyvalue::{
+            in_memory::InMemoryDB,
+            shadow::{ShadowMismatch, ShadowReadDB},
+            KeyValueDB,
+        };
+
+        let db = ShadowReadDB::new(InMemoryDB::new(), InMemoryDB::new(), 0, |_| {});
+        common::test_db(&db);
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let db = ShadowReadDB::new(InMemoryDB::new(), InMemoryDB::new(), 2, {
+            let reported = reported.clone();
+            move |mismatch: &ShadowMismatch| reported.lock().unwrap().push(mismatch.clone())
+        });
+        for key in ["a", "b"] {
+            db.insert("table", key, b"value").unwrap();
+            db.shadow().insert("table", key, b"value").unwrap();
+        }
+        db.insert("table", "c", b"value").unwrap();
+        db.shadow().insert("table", "c", b"other").unwrap();
+
+        assert_eq!(db.keys("table").unwrap().len(), 3);
+        db.get("table", "c").unwrap();
+        db.get("table", "a").unwrap();
+        db.get("table", "c").unwrap();
+        assert_eq!(db.mismatches(), 0);
+        db.iter("table").unwrap();
+        assert_eq!(db.mismatches(), 1);
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![ShadowMismatch {
+                operation: "iter",
+                table_name: "table".to_string(),
+                key: None,
+            }]
+        );
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_outbox() {
+        use keyvalue::{outbox::Outbox, KeyValueDB, WriteBatch};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = keyvalue::redb::RedbDB::open(&temp_dir.path().join("outbox")).unwrap();
+        let outbox = Outbox::new("outbox");
+
+        let mut batch = WriteBatch::new();
+        batch.insert("orders", "1", b"order");
+        outbox.enqueue(&mut batch, "0001", b"order created");
+        outbox.enqueue(&mut batch, "0002", b"order paid");
+        db.apply_batch(batch).unwrap();
+
+        let leased = outbox.lease(&db, 1, 100, 50).unwrap();
+        assert_eq!(leased.len(), 1);
+        assert_eq!(leased[0].id, "0001");
+        assert_eq!(leased[0].payload, b"order created");
+
+        let others = outbox.lease(&db, 10, 120, 50).unwrap();
+        assert_eq!(others.len(), 1);
+        assert_eq!(others[0].id, "0002");
+        assert!(outbox.lease(&db, 10, 149, 50).unwrap().is_empty());
+
+        let expired = outbox.lease(&db, 10, 150, 50).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, "0001");
+        assert!(!outbox.mark_published(&db, &leased[0]).unwrap());
+        assert!(outbox.mark_published(&db, &expired[0]).unwrap());
+        assert!(outbox.mark_published(&db, &others[0]).unwrap());
+        assert!(db.is_empty("outbox").unwrap());
+    }
+
+    #[cfg(all(feature = "redb", feature = "fs"))]
+    #[test]
+    fn test_probe() {
+        use keyvalue::probe::Engine;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let redb_path = temp_dir.path().join("redb");
+        let db = keyvalue::redb::RedbDB::open(&redb_path).unwrap();
+        keyvalue::meta::init_db_info(&db).unwrap();
+        let report = keyvalue::probe(&redb_path).unwrap();
+        assert_eq!(report.engine, Engine::Redb);
+        assert_eq!(report.layout_version, Some(keyvalue::meta::LAYOUT_VERSION));
+        drop(db);
+        let report = keyvalue::probe(&redb_path).unwrap();
+        assert_eq!(report.engine, Engine::Redb);
+        assert_eq!(report.layout_version, None);
+
+        let fs_path = temp_dir.path().join("fs");
+        keyvalue::fs::FsDB::open(&fs_path).unwrap();
+        assert_eq!(keyvalue::probe(&fs_path).unwrap().engine, Engine::Fs);
+        let e = keyvalue::redb::RedbDB::open(&fs_path).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(keyvalue::fs::FsDB::open(&redb_path).is_err());
+
+        let sqlite_path = temp_dir.path().join("sqlite");
+        std::fs::write(&sqlite_path, b"SQLite format 3\0rest of the header").unwrap();
+        assert_eq!(
+            keyvalue::probe(&sqlite_path).unwrap().engine,
+            Engine::Sqlite
+        );
+
+        let rocksdb_path = temp_dir.path().join("rocksdb");
+        std::fs::create_dir(&rocksdb_path).unwrap();
+        std::fs::write(rocksdb_path.join("CURRENT"), b"MANIFEST-000001\n").unwrap();
+        assert_eq!(
+            keyvalue::probe(&rocksdb_path).unwrap().engine,
+            Engine::RocksDb
+        );
+
+        let empty_path = temp_dir.path().join("empty");
+        std::fs::write(&empty_path, b"").unwrap();
+        assert_eq!(
+            keyvalue::probe(&empty_path).unwrap().engine,
+            Engine::Unknown
+        );
+        keyvalue::redb::RedbDB::open(&empty_path).unwrap();
+    }
+
+    #[cfg(all(feature = "in-memory", feature = "redb", feature = "fs"))]
+    #[test]
+    fn test_create_table() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dbs: Vec<Box<dyn keyvalue::KeyValueDB>> = vec![
+            Box::new(keyvalue::in_memory::InMemoryDB::new()),
+            Box::new(keyvalue::redb::RedbDB::open(&temp_dir.path().join("redb")).unwrap()),
+            Box::new(keyvalue::fs::FsDB::open(&temp_dir.path().join("fs")).unwrap()),
+        ];
+        for db in dbs {
+            db.create_table("table").unwrap();
+            assert_eq!(db.table_names().unwrap(), vec!["table".to_string()]);
+            assert!(db.is_empty("table").unwrap());
+            db.insert("table", "key", b"value").unwrap();
+            db.create_table("table").unwrap();
+            assert_eq!(db.len("table").unwrap(), 1);
+        }
+    }
+
     #[cfg(feature = "redb")]
     #[test]
     fn test_redb() {
@@ -42,6 +2022,133 @@ mod tests {
         assert!(keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
     }
 
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_redb_shared_open() {
+        use keyvalue::{redb::RedbDB, KeyValueDB};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_redb_shared_open_db");
+        let db = RedbDB::open(&path).unwrap();
+        db.insert("table", "key", b"value").unwrap();
+
+        let relative = temp_dir.path().join(".").join("test_redb_shared_open_db");
+        let threads = (0..4)
+            .map(|i| {
+                let relative = relative.clone();
+                std::thread::spawn(move || {
+                    let db = RedbDB::open(&relative).unwrap();
+                    db.insert("table", &i.to_string(), b"value").unwrap();
+                    db
+                })
+            })
+            .collect::<Vec<_>>();
+        let handles = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(db.len("table").unwrap(), 5);
+        assert_eq!(
+            handles[0].get("table", "key").unwrap(),
+            Some(b"value".to_vec())
+        );
+
+        let clone = db.clone();
+        drop(db);
+        drop(handles);
+        clone.remove("table", "key").unwrap();
+        drop(clone);
+
+        let db = RedbDB::open(&path).unwrap();
+        assert_eq!(db.len("table").unwrap(), 4);
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_redb_get_with() {
+        use keyvalue::{redb::RedbDB, KeyValueDB};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = RedbDB::open(&temp_dir.path().join("test_redb_get_with_db")).unwrap();
+        let value = vec![7u8; 1 << 20];
+        db.insert("table", "key", &value).unwrap();
+
+        assert_eq!(
+            db.get_with("table", "key", |value| (value.len(), value[1000]))
+                .unwrap(),
+            Some((1 << 20, 7))
+        );
+        assert_eq!(db.get_with("table", "missing", |_| ()).unwrap(), None);
+        assert_eq!(db.get_with("missing", "key", |_| ()).unwrap(), None);
+    }
+
+    #[cfg(all(feature = "redb", feature = "fs"))]
+    #[test]
+    fn test_fork() {
+        use keyvalue::KeyValueDB;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let db = keyvalue::redb::RedbDB::open(&temp_dir.path().join("source.redb")).unwrap();
+        common::persist_test_data(Box::new(db));
+        let db = keyvalue::redb::RedbDB::open(&temp_dir.path().join("source.redb")).unwrap();
+        let fork = db.fork(&temp_dir.path().join("fork.redb")).unwrap();
+        common::check_test_data(&fork);
+        fork.clear().unwrap();
+        common::check_test_data(&db);
+        assert!(db.fork(&temp_dir.path().join("fork.redb")).is_err());
+
+        let db = keyvalue::fs::FsDB::open(&temp_dir.path().join("source")).unwrap();
+        common::persist_test_data(Box::new(db));
+        let db = keyvalue::fs::FsDB::open(&temp_dir.path().join("source")).unwrap();
+        let fork = db.fork(&temp_dir.path().join("fork")).unwrap();
+        common::check_test_data(&fork);
+        for table_name in fork.table_names().unwrap() {
+            for key in fork.keys(&table_name).unwrap() {
+                fork.insert(&table_name, &key, b"changed").unwrap();
+            }
+        }
+        common::check_test_data(&db);
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn test_redb_builder() {
+        use keyvalue::{
+            redb::{Durability, RedbDB},
+            KeyValueDB,
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_redb_builder_db");
+        let db = RedbDB::builder()
+            .cache_size(1024 * 1024)
+            .durability(Durability::Eventual)
+            .open(&path)
+            .unwrap();
+        common::test_db(&db);
+        db.insert("table", "key", b"value").unwrap();
+        db.flush().unwrap();
+        db.remove("table", "key").unwrap();
+        db.compact().unwrap();
+        drop(db);
+
+        let db = RedbDB::open(&path).unwrap();
+        assert!(db.is_empty("table").unwrap());
+    }
+
+    #[cfg(all(feature = "test", feature = "redb"))]
+    #[test]
+    fn test_redb_crash() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_redb_crash_db");
+        let written = keyvalue::crash::run("tests::test_redb_crash", &path, 3, |path| {
+            keyvalue::redb::RedbDB::open(path)
+        })
+        .unwrap();
+        assert!(written > 0);
+    }
+
     #[cfg(all(feature = "async", feature = "redb"))]
     #[tokio::test]
     async fn test_async_redb() {
@@ -63,6 +2170,92 @@ mod tests {
             .is_empty());
     }
 
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_fs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_fs_db");
+        let db = keyvalue::fs::FsDB::open(&path).unwrap();
+        common::test_db(&db);
+        common::persist_test_data(Box::new(db));
+        let db = keyvalue::fs::FsDB::open(&path).unwrap();
+        common::check_test_data(&db);
+        assert!(!keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
+        keyvalue::KeyValueDB::clear(&db).unwrap();
+        assert!(keyvalue::KeyValueDB::table_names(&db).unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "async", feature = "fs"))]
+    #[tokio::test]
+    async fn test_async_fs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test_async_fs_db");
+        let db = keyvalue::fs::FsDB::open(&path).unwrap();
+        common::test_async_db(&db).await;
+        common::persist_test_data_async(Box::new(db)).await;
+        let db = keyvalue::fs::FsDB::open(&path).unwrap();
+        common::check_test_data_async(&db).await;
+        assert!(!keyvalue::AsyncKeyValueDB::table_names(&db)
+            .await
+            .unwrap()
+            .is_empty());
+        keyvalue::AsyncKeyValueDB::clear(&db).await.unwrap();
+        assert!(keyvalue::AsyncKeyValueDB::table_names(&db)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "aws-s3")]
+    #[tokio::test]
+    async fn test_aws_s3_concurrency_limit() {
+        use std::{
+            future::Future,
+            sync::atomic::{AtomicUsize, Ordering},
+            task::Context,
+        };
+
+        use keyvalue::aws_s3::ConcurrencyLimit;
+
+        let limit = ConcurrencyLimit::new(2);
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        futures::future::join_all((0..8).map(|_| async {
+            let _permit = limit.acquire().await;
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            for _ in 0..4 {
+                tokio::task::yield_now().await;
+            }
+            running.fetch_sub(1, Ordering::SeqCst);
+        }))
+        .await;
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(limit.available(), 2);
+        assert_eq!(ConcurrencyLimit::new(0).available(), 1);
+
+        // Released permits go to the first waiter, and are passed on by a
+        // waiter dropped before it took them.
+        let limit = ConcurrencyLimit::new(1);
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let permit = limit.acquire().await;
+        let mut first = Box::pin(limit.acquire());
+        let mut second = Box::pin(limit.acquire());
+        let mut third = Box::pin(limit.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        drop(third);
+        drop(permit);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(limit.available(), 0);
+        drop(first);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        drop(second);
+        assert_eq!(limit.available(), 1);
+    }
+
     #[cfg(all(feature = "async", feature = "aws-s3"))]
     #[tokio::test]
     async fn test_async_aws_s3() {
@@ -84,6 +84,98 @@ mod tests {
             .is_empty());
     }
 
+    #[cfg(all(feature = "async", feature = "indexed-db"))]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    async fn test_async_indexed_db_open_with_tables() {
+        use keyvalue::AsyncKeyValueDB;
+
+        let name = "test_async_indexed_db_open_with_tables_db";
+        let db = keyvalue::indexed_db::IndexedDB::open_with_tables(name, &["a", "b"])
+            .await
+            .unwrap();
+        let mut table_names = db.table_names().await.unwrap();
+        table_names.sort();
+        assert_eq!(table_names, vec!["a", "b"]);
+        assert_eq!(db.insert("a", "key", b"value").await.unwrap(), None);
+        assert_eq!(db.insert("b", "key", b"value").await.unwrap(), None);
+        drop(db);
+
+        // The stores exist now, so opening again does not upgrade.
+        let db = keyvalue::indexed_db::IndexedDB::open_with_tables(name, &["a", "b"])
+            .await
+            .unwrap();
+        let mut table_names = db.table_names().await.unwrap();
+        table_names.sort();
+        assert_eq!(table_names, vec!["a", "b"]);
+        assert_eq!(db.get("a", "key").await.unwrap(), Some(b"value".to_vec()));
+
+        db.clear().await.unwrap();
+        assert!(db.table_names().await.unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "async", feature = "indexed-db"))]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    async fn test_async_indexed_db_prefix_and_range() {
+        use keyvalue::AsyncKeyValueDB;
+
+        let name = "test_async_indexed_db_prefix_and_range_db";
+        let db = keyvalue::indexed_db::IndexedDB::open(name).await.unwrap();
+        let keys = [
+            "a",
+            "ab",
+            "ab\u{FFFF}",
+            "ab\u{FFFF}x",
+            "ac",
+            "b",
+            "\u{FFFF}",
+            "\u{FFFF}z",
+        ];
+        for key in keys {
+            db.insert("table", key, key.as_bytes()).await.unwrap();
+        }
+        let entries = |keys: &[&str]| {
+            keys.iter()
+                .map(|key| (key.to_string(), key.as_bytes().to_vec()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            db.iter_from_prefix("table", "ab").await.unwrap(),
+            entries(&["ab", "ab\u{FFFF}", "ab\u{FFFF}x"])
+        );
+        // The upper bound skips trailing U+FFFF code units.
+        assert_eq!(
+            db.iter_from_prefix("table", "ab\u{FFFF}").await.unwrap(),
+            entries(&["ab\u{FFFF}", "ab\u{FFFF}x"])
+        );
+        assert_eq!(
+            db.iter_from_prefix("table", "\u{FFFF}").await.unwrap(),
+            entries(&["\u{FFFF}", "\u{FFFF}z"])
+        );
+        assert_eq!(
+            db.iter_from_prefix("table", "").await.unwrap(),
+            entries(&keys)
+        );
+        assert!(db.iter_from_prefix("table", "c").await.unwrap().is_empty());
+
+        assert_eq!(
+            db.iter_from_range("table", "ab", "b").await.unwrap(),
+            entries(&["ab", "ab\u{FFFF}", "ab\u{FFFF}x", "ac"])
+        );
+        assert!(db
+            .iter_from_range("table", "b", "a")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .iter_from_prefix("missing", "a")
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.clear().await.unwrap();
+    }
+
     #[cfg(all(
         feature = "async",
         feature = "indexed-db",